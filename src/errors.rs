@@ -1,13 +1,32 @@
 use std::fmt;
 
+use crate::handle_autolock::VaultLocked;
+
 pub struct AppError {
     details: String,
+    // set when the vault locked while an operation was waiting on the user, the main
+    // loop unlocks again rather than printing it
+    locked: bool,
 }
 
 impl AppError {
     pub fn new(msg: &str) -> AppError {
         AppError {
             details: msg.to_string(),
+            locked: false,
+        }
+    }
+
+    pub fn is_locked(&self) -> bool {
+        self.locked
+    }
+}
+
+impl From<VaultLocked> for AppError {
+    fn from(_: VaultLocked) -> AppError {
+        AppError {
+            details: String::from("The vault is locked."),
+            locked: true,
         }
     }
 }
@@ -22,4 +41,4 @@ impl fmt::Debug for AppError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "AppError {{ {} }}", self.details)
     }
-}
\ No newline at end of file
+}
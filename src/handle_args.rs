@@ -0,0 +1,207 @@
+// my stuff
+use crate::errors::AppError;
+use crate::handle_audit::AUDIT_OPERATIONS;
+use crate::handle_operations::SORT_ORDERS;
+
+// ------------------ //
+
+pub enum CLICommand {
+    Interactive,
+    Help,
+    List { sort: Option<String> },
+    View { name: String, reveal: bool },
+    Expiring { days: Option<i64> },
+    Match { query: String },
+    FindUrl { url: String },
+    Health { json: bool },
+    BreachCheck { path: String, ntlm: bool },
+    Audit { filter: AuditFilter },
+}
+
+// which audit records to show, all of them when neither is given
+pub struct AuditFilter {
+    pub entry: Option<String>,
+    pub operation: Option<String>,
+}
+
+pub trait ParseArgs {
+    fn new(args: Vec<String>) -> Self;
+    fn parse(&self) -> Result<CLICommand, AppError>;
+    fn print_usage(&self);
+    fn skip_password_policy(&self) -> bool;
+}
+
+// lets a weak master password through, meant for tests and throwaway vaults
+const SKIP_POLICY_FLAG: &str = "--insecure-skip-password-policy";
+
+pub struct ArgsHandler {
+    args: Vec<String>,
+    skip_password_policy: bool,
+}
+
+impl ArgsHandler {
+    // returns the value following a flag like `--sort name`
+    fn flag_value(&self, flag: &str) -> Result<Option<String>, AppError> {
+        match self.args.iter().position(|a| a == flag) {
+            Some(i) => match self.args.get(i + 1) {
+                Some(value) => Ok(Some(value.clone())),
+                None => Err(AppError::new(&format!("Missing value for {}.", flag))),
+            },
+            None => Ok(None),
+        }
+    }
+}
+
+impl ParseArgs for ArgsHandler {
+    fn new(args: Vec<String>) -> Self {
+        // the first argument is the name of the binary, the policy flag can go
+        // anywhere and works with every command
+        let args = args.into_iter().skip(1).collect::<Vec<String>>();
+
+        ArgsHandler {
+            skip_password_policy: args.iter().any(|a| a == SKIP_POLICY_FLAG),
+            args: args.into_iter().filter(|a| a != SKIP_POLICY_FLAG).collect(),
+        }
+    }
+
+    fn parse(&self) -> Result<CLICommand, AppError> {
+        let command = match self.args.first() {
+            Some(command) => command.as_str(),
+            None => return Ok(CLICommand::Interactive),
+        };
+
+        match command {
+            "help" | "--help" | "-h" => Ok(CLICommand::Help),
+            "list" => {
+                let sort = self.flag_value("--sort")?;
+
+                if let Some(sort) = &sort {
+                    if !SORT_ORDERS.iter().any(|(name, _)| name == sort) {
+                        return Err(AppError::new(&format!("Unknown sort order \"{}\".", sort)));
+                    }
+                }
+
+                Ok(CLICommand::List { sort })
+            }
+            "view" => {
+                let name = match self.args.get(1) {
+                    Some(name) if !name.starts_with("--") => name.clone(),
+                    _ => return Err(AppError::new("Missing entry name.")),
+                };
+                let reveal = self.args.iter().any(|a| a == "--reveal");
+
+                Ok(CLICommand::View { name, reveal })
+            }
+            "match" => match self.args.get(1) {
+                Some(query) => Ok(CLICommand::Match {
+                    query: query.clone(),
+                }),
+                None => Err(AppError::new("Missing search text.")),
+            },
+            "find-url" => match self.args.get(1) {
+                Some(url) => Ok(CLICommand::FindUrl { url: url.clone() }),
+                None => Err(AppError::new("Missing URL.")),
+            },
+            "health" => Ok(CLICommand::Health {
+                json: self.args.iter().any(|a| a == "--json"),
+            }),
+            "breach-check" => match self.args.get(1) {
+                Some(path) if !path.starts_with("--") => Ok(CLICommand::BreachCheck {
+                    path: path.clone(),
+                    ntlm: self.args.iter().any(|a| a == "--ntlm"),
+                }),
+                _ => Err(AppError::new("Missing breach file.")),
+            },
+            "audit" => {
+                let operation = self.flag_value("--operation")?;
+
+                if let Some(operation) = &operation {
+                    if !AUDIT_OPERATIONS.iter().any(|(name, _)| name == operation) {
+                        return Err(AppError::new(&format!(
+                            "Unknown operation \"{}\".",
+                            operation
+                        )));
+                    }
+                }
+
+                Ok(CLICommand::Audit {
+                    filter: AuditFilter {
+                        entry: self.flag_value("--entry")?,
+                        operation,
+                    },
+                })
+            }
+            "expiring" => {
+                let days = match self.flag_value("--days")? {
+                    Some(days) => match days.parse::<i64>() {
+                        Ok(days) if days >= 0 => Some(days),
+                        _ => {
+                            return Err(AppError::new(&format!(
+                                "Invalid number of days \"{}\".",
+                                days
+                            )))
+                        }
+                    },
+                    None => None,
+                };
+
+                Ok(CLICommand::Expiring { days })
+            }
+            _ => Err(AppError::new(&format!("Unknown command \"{}\".", command))),
+        }
+    }
+
+    fn print_usage(&self) {
+        let sort_orders = SORT_ORDERS
+            .iter()
+            .map(|(name, _)| *name)
+            .collect::<Vec<&str>>()
+            .join("|");
+        let audit_operations = AUDIT_OPERATIONS
+            .iter()
+            .map(|(name, _)| *name)
+            .collect::<Vec<&str>>()
+            .join("|");
+
+        println!("Usage: rpassman [command]");
+        println!();
+        println!("Commands:");
+        println!("  (none)                  Start the interactive password manager");
+        println!("  list [--sort <order>]   List entries, favorites first");
+        println!(
+            "                          <order> is one of {}",
+            sort_orders
+        );
+        println!("  view <name> [--reveal]  Show an entry, --reveal shows concealed values");
+        println!("                          <name> can be a close match if only one entry fits");
+        println!("  match <text>            List the entries whose names best match <text>");
+        println!("  find-url <url>          List the entries with a URL matching <url>");
+        println!("  health [--json]         Check the vault for reused, weak and old passwords");
+        println!("                          and other problems, --json for dashboards");
+        println!("  breach-check <path> [--ntlm]");
+        println!("                          Look up every password in a downloaded Have I Been");
+        println!("                          Pwned file or folder of range files, --ntlm if it");
+        println!("                          holds NTLM hashes instead of SHA-1");
+        println!("  audit [--entry <name>] [--operation <op>]");
+        println!("                          Verify the audit log and show its records, for one");
+        println!(
+            "                          entry or operation, <op> is one of {}",
+            audit_operations
+        );
+        println!("  expiring [--days <n>]   List credentials that are overdue or expire within");
+        println!("                          <n> days, without unlocking (for use in cron),");
+        println!("                          exits with 2 if there are any, 1 on an error");
+        println!("  help                    Show this message");
+        println!();
+        println!("Options:");
+        println!(
+            "  {}  Allow a master password that breaks the",
+            SKIP_POLICY_FLAG
+        );
+        println!("                                  master password policy (for tests only)");
+    }
+
+    fn skip_password_policy(&self) -> bool {
+        self.skip_password_policy
+    }
+}
@@ -0,0 +1,420 @@
+// file stuff
+use std::fs::{self, File};
+use std::io::prelude::*;
+use std::path::{Path, PathBuf};
+
+// password stuff
+use chacha20::cipher::StreamCipher;
+
+// other stuff
+use chrono::prelude::*;
+
+// my stuff
+use crate::errors::AppError;
+use crate::handle_files::{discard_staged, write_staged};
+use crate::handle_operations::{entry_cipher, random_nonce, rekey_data};
+
+// ------------------ //
+
+// files are encrypted in chunks so large attachments never have to be held in
+// memory all at once, each chunk gets its own nonce
+const CHUNK_SIZE: usize = 64 * 1024;
+
+// attachments larger than this are suggested to be kept out of the vault file
+pub const SIDECAR_THRESHOLD: u64 = 1024 * 1024;
+
+pub trait ProcessAttachments {
+    fn new(dir: PathBuf) -> Self;
+    fn add_attachment(
+        &self,
+        key: &str,
+        entry_name: &str,
+        file_path: &Path,
+        sidecar: bool,
+    ) -> Result<serde_json::Value, AppError>;
+    fn attachment_name(
+        &self,
+        key: &str,
+        entry_name: &str,
+        attachment: &serde_json::Value,
+    ) -> String;
+    fn extract_attachment(
+        &self,
+        key: &str,
+        entry_name: &str,
+        attachment: &serde_json::Value,
+        out_path: &Path,
+    ) -> Result<(), AppError>;
+    fn copy_attachment(
+        &self,
+        key: &str,
+        entry_name: &str,
+        new_entry_name: &str,
+        attachment: &serde_json::Value,
+    ) -> Result<serde_json::Value, AppError>;
+    fn copy_attachments(
+        &self,
+        key: &str,
+        entry_name: &str,
+        new_entry_name: &str,
+        attachments: &[serde_json::Value],
+    ) -> Result<Vec<serde_json::Value>, AppError>;
+    fn stage_rekey_chunks(
+        &self,
+        old_key: &str,
+        new_key: &str,
+        entry_name: &str,
+        attachment: &serde_json::Value,
+    ) -> Result<Vec<PathBuf>, AppError>;
+    fn rekey_attachment(
+        &self,
+        old_key: &str,
+        new_key: &str,
+        entry_name: &str,
+        attachment: &mut serde_json::Value,
+    );
+    fn remove_attachment(&self, attachment: &serde_json::Value);
+}
+
+pub struct AttachmentHandler {
+    dir: PathBuf,
+}
+
+impl AttachmentHandler {
+    fn sidecar_dir(&self, attachment_id: &str) -> PathBuf {
+        self.dir.join(attachment_id)
+    }
+
+    fn read_chunk(
+        &self,
+        key: &str,
+        entry_name: &str,
+        attachment: &serde_json::Value,
+        index: usize,
+    ) -> Result<Vec<u8>, AppError> {
+        let chunk = &attachment["chunks"][index];
+        let nonce = hex::decode(chunk["nonce"].as_str().unwrap()).unwrap();
+
+        let mut data = if attachment["storage"].as_str() == Some("sidecar") {
+            let chunk_path = self
+                .sidecar_dir(attachment["id"].as_str().unwrap())
+                .join(index.to_string());
+            match fs::read(&chunk_path) {
+                Err(why) => {
+                    return Err(AppError::new(&format!(
+                        "Couldn't read attachment chunk {}: {}",
+                        chunk_path.display(),
+                        why
+                    )))
+                }
+                Ok(data) => data,
+            }
+        } else {
+            hex::decode(chunk["data"].as_str().unwrap()).unwrap()
+        };
+
+        entry_cipher(key, entry_name, &nonce).apply_keystream(&mut data);
+
+        Ok(data)
+    }
+
+    // encrypts chunks as they are produced, storing them either in the returned
+    // json or as files in the sidecar directory
+    fn write_chunks(
+        &self,
+        key: &str,
+        entry_name: &str,
+        name: &str,
+        sidecar: bool,
+        mut next_chunk: impl FnMut() -> Result<Option<Vec<u8>>, AppError>,
+    ) -> Result<serde_json::Value, AppError> {
+        let attachment_id = hex::encode(random_nonce());
+
+        if sidecar {
+            if let Err(why) = fs::create_dir_all(self.sidecar_dir(&attachment_id)) {
+                return Err(AppError::new(&format!(
+                    "Couldn't create attachments directory: {}",
+                    why
+                )));
+            }
+        }
+
+        // the file name can say a lot about what is attached, so it is encrypted too
+        let name_nonce = random_nonce();
+        let mut encrypted_name = name.as_bytes().to_vec();
+        entry_cipher(key, entry_name, &name_nonce).apply_keystream(&mut encrypted_name);
+
+        let mut chunks: Vec<serde_json::Value> = Vec::new();
+        let mut size: u64 = 0;
+
+        let written = loop {
+            let mut data = match next_chunk() {
+                Ok(Some(data)) => data,
+                Ok(None) => break Ok(()),
+                Err(e) => break Err(e),
+            };
+            let nonce = random_nonce();
+            size += data.len() as u64;
+
+            entry_cipher(key, entry_name, &nonce).apply_keystream(&mut data);
+
+            if sidecar {
+                let chunk_path = self
+                    .sidecar_dir(&attachment_id)
+                    .join(chunks.len().to_string());
+                if let Err(why) = fs::write(&chunk_path, &data) {
+                    break Err(AppError::new(&format!(
+                        "Couldn't write attachment chunk {}: {}",
+                        chunk_path.display(),
+                        why
+                    )));
+                }
+
+                chunks.push(serde_json::json!({ "nonce": hex::encode(nonce) }));
+            } else {
+                chunks.push(serde_json::json!({
+                    "data": hex::encode(data),
+                    "nonce": hex::encode(nonce),
+                }));
+            }
+        };
+
+        // nothing refers to the chunks written so far, so they don't stay behind
+        if let Err(e) = written {
+            if sidecar {
+                let _ = fs::remove_dir_all(self.sidecar_dir(&attachment_id));
+            }
+            return Err(e);
+        }
+
+        Ok(serde_json::json!({
+            "id": attachment_id,
+            "name": {
+                "data": hex::encode(encrypted_name),
+                "nonce": hex::encode(name_nonce),
+            },
+            "size": size,
+            "storage": if sidecar { "sidecar" } else { "vault" },
+            "chunks": chunks,
+            "added": Local::now().to_rfc3339(),
+        }))
+    }
+}
+
+impl ProcessAttachments for AttachmentHandler {
+    fn new(dir: PathBuf) -> Self {
+        AttachmentHandler { dir }
+    }
+
+    fn add_attachment(
+        &self,
+        key: &str,
+        entry_name: &str,
+        file_path: &Path,
+        sidecar: bool,
+    ) -> Result<serde_json::Value, AppError> {
+        let mut file = match File::open(file_path) {
+            Err(why) => return Err(AppError::new(&format!("Couldn't open file: {}", why))),
+            Ok(file) => file,
+        };
+
+        let name = match file_path.file_name() {
+            Some(name) => name.to_string_lossy().to_string(),
+            None => return Err(AppError::new("That path is not a file.")),
+        };
+
+        self.write_chunks(key, entry_name, &name, sidecar, || {
+            let mut buffer = vec![0u8; CHUNK_SIZE];
+            let mut filled = 0;
+
+            // a single read can return less than a chunk, so keep going until the
+            // chunk is full or the file runs out
+            while filled < CHUNK_SIZE {
+                match file.read(&mut buffer[filled..]) {
+                    Err(why) => return Err(AppError::new(&format!("Couldn't read file: {}", why))),
+                    Ok(0) => break,
+                    Ok(n) => filled += n,
+                }
+            }
+
+            if filled == 0 {
+                Ok(None)
+            } else {
+                buffer.truncate(filled);
+                Ok(Some(buffer))
+            }
+        })
+    }
+
+    fn attachment_name(
+        &self,
+        key: &str,
+        entry_name: &str,
+        attachment: &serde_json::Value,
+    ) -> String {
+        let nonce = hex::decode(attachment["name"]["nonce"].as_str().unwrap()).unwrap();
+        let mut name = hex::decode(attachment["name"]["data"].as_str().unwrap()).unwrap();
+
+        entry_cipher(key, entry_name, &nonce).apply_keystream(&mut name);
+
+        String::from_utf8_lossy(&name).to_string()
+    }
+
+    fn extract_attachment(
+        &self,
+        key: &str,
+        entry_name: &str,
+        attachment: &serde_json::Value,
+        out_path: &Path,
+    ) -> Result<(), AppError> {
+        let mut file = match File::create(out_path) {
+            Err(why) => return Err(AppError::new(&format!("Couldn't create file: {}", why))),
+            Ok(file) => file,
+        };
+
+        for i in 0..attachment["chunks"].as_array().unwrap().len() {
+            let data = self.read_chunk(key, entry_name, attachment, i)?;
+
+            if let Err(why) = file.write_all(&data) {
+                return Err(AppError::new(&format!("Couldn't write file: {}", why)));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn copy_attachment(
+        &self,
+        key: &str,
+        entry_name: &str,
+        new_entry_name: &str,
+        attachment: &serde_json::Value,
+    ) -> Result<serde_json::Value, AppError> {
+        // each chunk is decrypted under the old entry and encrypted under the new one
+        // as it is copied
+        let name = self.attachment_name(key, entry_name, attachment);
+        let sidecar = attachment["storage"].as_str() == Some("sidecar");
+        let num_chunks = attachment["chunks"].as_array().unwrap().len();
+        let mut index = 0;
+
+        let mut copy = self.write_chunks(key, new_entry_name, &name, sidecar, || {
+            if index == num_chunks {
+                return Ok(None);
+            }
+
+            let data = self.read_chunk(key, entry_name, attachment, index)?;
+            index += 1;
+
+            Ok(Some(data))
+        })?;
+        copy["added"] = attachment["added"].clone();
+
+        Ok(copy)
+    }
+
+    // copies all of an entry's attachments or none, the copies made before one
+    // fails are removed again
+    fn copy_attachments(
+        &self,
+        key: &str,
+        entry_name: &str,
+        new_entry_name: &str,
+        attachments: &[serde_json::Value],
+    ) -> Result<Vec<serde_json::Value>, AppError> {
+        let mut copies: Vec<serde_json::Value> = Vec::new();
+
+        for attachment in attachments {
+            match self.copy_attachment(key, entry_name, new_entry_name, attachment) {
+                Ok(copy) => copies.push(copy),
+                Err(e) => {
+                    for copy in &copies {
+                        self.remove_attachment(copy);
+                    }
+                    return Err(e);
+                }
+            }
+        }
+
+        Ok(copies)
+    }
+
+    // writes every sidecar chunk re-encrypted for a new key next to the old one,
+    // returning the chunks to move into place once the new key is committed
+    fn stage_rekey_chunks(
+        &self,
+        old_key: &str,
+        new_key: &str,
+        entry_name: &str,
+        attachment: &serde_json::Value,
+    ) -> Result<Vec<PathBuf>, AppError> {
+        let mut staged: Vec<PathBuf> = Vec::new();
+        if attachment["storage"].as_str() != Some("sidecar") {
+            return Ok(staged);
+        }
+
+        let attachment_dir = self.sidecar_dir(attachment["id"].as_str().unwrap());
+
+        for (index, chunk) in attachment["chunks"].as_array().unwrap().iter().enumerate() {
+            let nonce = hex::decode(chunk["nonce"].as_str().unwrap()).unwrap();
+            let chunk_path = attachment_dir.join(index.to_string());
+
+            let mut data = match fs::read(&chunk_path) {
+                Err(why) => {
+                    discard_staged(&staged);
+                    return Err(AppError::new(&format!(
+                        "Couldn't read attachment chunk {}: {}",
+                        chunk_path.display(),
+                        why
+                    )));
+                }
+                Ok(data) => data,
+            };
+
+            rekey_data(old_key, new_key, entry_name, &nonce, &mut data);
+            if let Err(why) = write_staged(&chunk_path, |writer| writer.write_all(&data)) {
+                discard_staged(&staged);
+                return Err(AppError::new(&format!(
+                    "Couldn't write attachment chunk {}: {}",
+                    chunk_path.display(),
+                    why
+                )));
+            }
+            staged.push(chunk_path);
+        }
+
+        Ok(staged)
+    }
+
+    // re-encrypts the name and the chunks kept in the vault for a new key, sidecar
+    // chunks are staged separately
+    fn rekey_attachment(
+        &self,
+        old_key: &str,
+        new_key: &str,
+        entry_name: &str,
+        attachment: &mut serde_json::Value,
+    ) {
+        let nonce = hex::decode(attachment["name"]["nonce"].as_str().unwrap()).unwrap();
+        let mut name = hex::decode(attachment["name"]["data"].as_str().unwrap()).unwrap();
+        rekey_data(old_key, new_key, entry_name, &nonce, &mut name);
+        attachment["name"]["data"] = serde_json::Value::String(hex::encode(name));
+
+        if attachment["storage"].as_str() == Some("sidecar") {
+            return;
+        }
+
+        for chunk in attachment["chunks"].as_array_mut().unwrap() {
+            let nonce = hex::decode(chunk["nonce"].as_str().unwrap()).unwrap();
+            let mut data = hex::decode(chunk["data"].as_str().unwrap()).unwrap();
+            rekey_data(old_key, new_key, entry_name, &nonce, &mut data);
+            chunk["data"] = serde_json::Value::String(hex::encode(data));
+        }
+    }
+
+    fn remove_attachment(&self, attachment: &serde_json::Value) {
+        if attachment["storage"].as_str() == Some("sidecar") {
+            let attachment_id = attachment["id"].as_str().unwrap();
+            let _ = fs::remove_dir_all(self.sidecar_dir(attachment_id));
+        }
+    }
+}
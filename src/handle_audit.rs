@@ -0,0 +1,808 @@
+// question stuff
+use requestty::Question;
+
+// file stuff
+use std::fs::{File, OpenOptions};
+use std::io::prelude::*;
+use std::io::{BufReader, SeekFrom};
+use std::path::{Path, PathBuf};
+
+// password stuff
+use chacha20::cipher::StreamCipher;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+// other stuff
+use chrono::prelude::*;
+use colored::Colorize;
+
+// my stuff
+use crate::errors::AppError;
+use crate::handle_autolock::prompt_one;
+use crate::handle_files::write_staged;
+use crate::handle_operations::{entry_cipher, format_timestamp, random_nonce, rekey_data};
+use crate::initialize::{read_settings_file, update_settings_file};
+
+// ------------------ //
+
+// everything that gets recorded, along with how it is described in the viewer
+pub const AUDIT_OPERATIONS: [(&str, &str); 10] = [
+    ("unlock", "Unlocked"),
+    ("failed_unlock", "Failed unlock"),
+    ("view", "Viewed"),
+    ("create", "Created"),
+    ("update", "Updated"),
+    ("delete", "Moved to the trash"),
+    ("restore", "Restored from the trash"),
+    ("purge", "Deleted for good"),
+    ("export", "Exported"),
+    ("change_password", "Changed master password"),
+];
+
+// records are encrypted like the fields of an entry with this name, the random
+// nonces keep them apart from any real entry that happens to share it
+const LOG_NAME: &str = "audit log";
+
+// the hash the first record is chained to
+const FIRST_HASH: [u8; 32] = [0u8; 32];
+
+// enough to hold the last record of the log in almost every case
+const TAIL_SIZE: u64 = 4096;
+
+// where the end of the log is noted in the settings file
+const ANCHOR_SETTING: &str = "audit_anchor";
+
+pub struct AuditRecord {
+    pub seq: u64,
+    pub time: String,
+    pub operation: String,
+    pub entry: Option<String>,
+    pub detail: Option<String>,
+}
+
+// a record as it is stored, still encrypted
+struct LogLine {
+    seq: u64,
+    nonce: Vec<u8>,
+    data: Vec<u8>,
+    hash: Vec<u8>,
+}
+
+impl LogLine {
+    // the parts of a line of the log, or nothing if it has been mangled
+    fn parse(line: &str) -> Option<Self> {
+        let json: serde_json::Value = serde_json::from_str(line).ok()?;
+
+        let nonce = hex::decode(json["nonce"].as_str()?).ok()?;
+        if nonce.len() != 12 {
+            return None;
+        }
+
+        Some(LogLine {
+            seq: json["seq"].as_u64()?,
+            nonce,
+            data: hex::decode(json["data"].as_str()?).ok()?,
+            hash: hex::decode(json["hash"].as_str()?).ok()?,
+        })
+    }
+
+    fn to_line(&self) -> String {
+        serde_json::json!({
+            "seq": self.seq,
+            "nonce": hex::encode(&self.nonce),
+            "data": hex::encode(&self.data),
+            "hash": hex::encode(&self.hash),
+        })
+        .to_string()
+    }
+}
+
+pub trait KeepAuditLog {
+    fn new(path: PathBuf) -> Self;
+    fn record(&self, key: &str, operation: &str, entry: Option<&str>, detail: Option<&str>);
+    fn record_at(
+        &self,
+        key: &str,
+        time: &str,
+        operation: &str,
+        entry: Option<&str>,
+        detail: Option<&str>,
+    );
+    fn read_log(&self, key: &str) -> Result<(Vec<AuditRecord>, Option<u64>), AppError>;
+    fn stage_rekey_log(&self, old_key: &str, new_key: &str)
+        -> Option<(PathBuf, serde_json::Value)>;
+    fn print_log(
+        &self,
+        key: &str,
+        entry: Option<&str>,
+        operation: Option<&str>,
+    ) -> Result<(), AppError>;
+    fn review_log(&self, key: &str) -> Result<(), AppError>;
+}
+
+// an append-only file of encrypted records next to the vault, each one holds a mac
+// of the one before it so records can't be changed, removed or reordered without
+// the chain breaking, and the last one is noted in the settings file so cutting
+// records off the end shows up too
+pub struct AuditLog {
+    path: PathBuf,
+    settings_path: PathBuf,
+}
+
+type HmacSha256 = Hmac<Sha256>;
+
+// the log is chained with a key of its own, derived from the master key so nothing
+// without it can rewrite a record and give it a mac that still checks out
+fn log_mac_key(key: &str) -> Vec<u8> {
+    let mut mac = HmacSha256::new_from_slice(key.as_bytes()).unwrap();
+    mac.update(LOG_NAME.as_bytes());
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn chain_hash(mac_key: &[u8], previous: &[u8], seq: u64, nonce: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = HmacSha256::new_from_slice(mac_key).unwrap();
+    mac.update(previous);
+    mac.update(&seq.to_be_bytes());
+    mac.update(nonce);
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn anchor_mac(mac_key: &[u8], seq: u64, hash: &[u8]) -> Vec<u8> {
+    let mut mac = HmacSha256::new_from_slice(mac_key).unwrap();
+    mac.update(ANCHOR_SETTING.as_bytes());
+    mac.update(&seq.to_be_bytes());
+    mac.update(hash);
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn anchor_json(mac_key: &[u8], seq: u64, hash: &[u8]) -> serde_json::Value {
+    serde_json::json!({
+        "seq": seq,
+        "hash": hex::encode(hash),
+        "mac": hex::encode(anchor_mac(mac_key, seq, hash)),
+    })
+}
+
+// what the settings file says the last record of the log is
+#[derive(Debug, PartialEq)]
+enum Anchor {
+    Missing,
+    Forged,
+    At(u64, Vec<u8>),
+}
+
+fn parse_anchor(mac_key: &[u8], json: &serde_json::Value) -> Anchor {
+    if json.is_null() {
+        return Anchor::Missing;
+    }
+
+    let seq = json["seq"].as_u64();
+    let hash = json["hash"]
+        .as_str()
+        .and_then(|hash| hex::decode(hash).ok());
+    let mac = json["mac"].as_str().and_then(|mac| hex::decode(mac).ok());
+
+    match (seq, hash, mac) {
+        (Some(seq), Some(hash), Some(mac)) if anchor_mac(mac_key, seq, &hash) == mac => {
+            Anchor::At(seq, hash)
+        }
+        _ => Anchor::Forged,
+    }
+}
+
+// the first record the anchor can't vouch for, given the hash of every line of the
+// log, a record past the end means some are missing
+fn unanchored(anchor: &Anchor, hashes: &[Option<Vec<u8>>]) -> Option<u64> {
+    match anchor {
+        Anchor::Missing if hashes.is_empty() => None,
+        Anchor::At(seq, hash) => match hashes.get(*seq as usize) {
+            None => Some(hashes.len() as u64),
+            Some(Some(line_hash)) if line_hash == hash => {
+                if hashes.len() as u64 > seq + 1 {
+                    Some(seq + 1)
+                } else {
+                    None
+                }
+            }
+            Some(_) => Some(*seq),
+        },
+        _ => Some(hashes.len() as u64),
+    }
+}
+
+fn operation_name(operation: &str) -> &str {
+    AUDIT_OPERATIONS
+        .iter()
+        .find(|(name, _)| *name == operation)
+        .map(|(_, description)| *description)
+        .unwrap_or(operation)
+}
+
+impl AuditLog {
+    fn read_anchor(&self) -> serde_json::Value {
+        read_settings_file(&self.settings_path)[ANCHOR_SETTING].clone()
+    }
+
+    fn write_anchor(&self, anchor: serde_json::Value) {
+        update_settings_file(
+            Path::new(&self.settings_path),
+            serde_json::json!({ ANCHOR_SETTING: anchor }),
+        );
+    }
+
+    fn lines(&self) -> Vec<String> {
+        if !self.path.exists() {
+            return Vec::new();
+        }
+
+        let file = match File::open(&self.path) {
+            Err(why) => panic!("Couldn't open audit log: {}", why),
+            Ok(file) => file,
+        };
+
+        BufReader::new(file)
+            .lines()
+            .map(|line| match line {
+                Err(why) => panic!("Couldn't read audit log: {}", why),
+                Ok(line) => line,
+            })
+            .filter(|line| !line.trim().is_empty())
+            .collect()
+    }
+
+    // the sequence number and hash the next record follows on from, only the end of
+    // the file is read so appending stays quick however long the log gets
+    fn last_link(&self) -> (u64, Vec<u8>) {
+        let mut file = match File::open(&self.path) {
+            Err(_) => return (0, FIRST_HASH.to_vec()),
+            Ok(file) => file,
+        };
+        let size = match file.metadata() {
+            Err(why) => panic!("Couldn't read audit log: {}", why),
+            Ok(metadata) => metadata.len(),
+        };
+
+        // the window grows until it reaches back past the start of the last line
+        let mut window = TAIL_SIZE;
+        loop {
+            let start = size.saturating_sub(window);
+            let mut tail = Vec::new();
+            if let Err(why) = file
+                .seek(SeekFrom::Start(start))
+                .and_then(|_| file.read_to_end(&mut tail))
+            {
+                panic!("Couldn't read audit log: {}", why);
+            }
+
+            let tail = String::from_utf8_lossy(&tail).to_string();
+            let mut lines = tail.lines().filter(|line| !line.trim().is_empty());
+            let last = lines.next_back();
+
+            if start > 0 && lines.next().is_none() {
+                window *= 2;
+                continue;
+            }
+
+            // a mangled last record is left for the viewer to point out, the next one
+            // carries on from it as best it can
+            return match last.and_then(LogLine::parse) {
+                Some(line) => (line.seq + 1, line.hash),
+                None if last.is_some() => (self.lines().len() as u64, FIRST_HASH.to_vec()),
+                None => (0, FIRST_HASH.to_vec()),
+            };
+        }
+    }
+}
+
+impl KeepAuditLog for AuditLog {
+    fn new(path: PathBuf) -> Self {
+        // the settings file sits next to the log
+        let settings_path = match path.parent() {
+            Some(parent) => parent.join("settings.json"),
+            None => PathBuf::from("settings.json"),
+        };
+
+        AuditLog {
+            path,
+            settings_path,
+        }
+    }
+
+    fn record(&self, key: &str, operation: &str, entry: Option<&str>, detail: Option<&str>) {
+        self.record_at(key, &Local::now().to_rfc3339(), operation, entry, detail);
+    }
+
+    // failed unlocks are only written once the key is known, with the time they
+    // actually happened
+    fn record_at(
+        &self,
+        key: &str,
+        time: &str,
+        operation: &str,
+        entry: Option<&str>,
+        detail: Option<&str>,
+    ) {
+        let (seq, previous) = self.last_link();
+
+        let mut record = serde_json::json!({
+            "time": time,
+            "operation": operation,
+        });
+        if let Some(entry) = entry {
+            record["entry"] = serde_json::json!(entry);
+        }
+        if let Some(detail) = detail {
+            record["detail"] = serde_json::json!(detail);
+        }
+
+        let nonce = random_nonce();
+        let mut data = record.to_string().into_bytes();
+        entry_cipher(key, LOG_NAME, &nonce).apply_keystream(&mut data);
+
+        let mac_key = log_mac_key(key);
+        let line = LogLine {
+            seq,
+            nonce: nonce.to_vec(),
+            hash: chain_hash(&mac_key, &previous, seq, &nonce, &data),
+            data,
+        };
+
+        // records are only ever added to the end
+        let mut file = match OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+        {
+            Err(why) => panic!("Couldn't open audit log: {}", why),
+            Ok(file) => file,
+        };
+        if let Err(why) = writeln!(file, "{}", line.to_line()) {
+            panic!("Couldn't write to audit log: {}", why);
+        }
+
+        // the anchor only moves on from the record it points at, if the log no longer
+        // ends there it stays put so the gap keeps showing
+        let anchor = parse_anchor(&mac_key, &self.read_anchor());
+        let follows_anchor = match &anchor {
+            Anchor::Missing => seq == 0,
+            Anchor::Forged => false,
+            Anchor::At(anchor_seq, anchor_hash) => {
+                anchor_seq + 1 == seq && *anchor_hash == previous
+            }
+        };
+        if follows_anchor {
+            self.write_anchor(anchor_json(&mac_key, seq, &line.hash));
+        }
+    }
+
+    // every record that can be read, along with the first one whose link in the
+    // chain doesn't check out, or that the anchor can't vouch for
+    fn read_log(&self, key: &str) -> Result<(Vec<AuditRecord>, Option<u64>), AppError> {
+        let mac_key = log_mac_key(key);
+        let mut records: Vec<AuditRecord> = Vec::new();
+        let mut hashes: Vec<Option<Vec<u8>>> = Vec::new();
+        let mut broken: Option<u64> = None;
+        let mut previous = FIRST_HASH.to_vec();
+
+        for (i, line) in self.lines().iter().enumerate() {
+            let mut line = match LogLine::parse(line) {
+                Some(line) => line,
+                None => {
+                    hashes.push(None);
+                    broken = broken.or(Some(i as u64));
+                    continue;
+                }
+            };
+
+            let hash = chain_hash(&mac_key, &previous, line.seq, &line.nonce, &line.data);
+            if line.seq != i as u64 || hash != line.hash {
+                broken = broken.or(Some(i as u64));
+            }
+            hashes.push(Some(line.hash.clone()));
+            previous = line.hash;
+
+            entry_cipher(key, LOG_NAME, &line.nonce).apply_keystream(&mut line.data);
+            let record: serde_json::Value = match serde_json::from_slice(&line.data) {
+                Ok(record) => record,
+                Err(_) => {
+                    broken = broken.or(Some(i as u64));
+                    continue;
+                }
+            };
+
+            records.push(AuditRecord {
+                seq: line.seq,
+                time: String::from(record["time"].as_str().unwrap_or("")),
+                operation: String::from(record["operation"].as_str().unwrap_or("")),
+                entry: record["entry"].as_str().map(String::from),
+                detail: record["detail"].as_str().map(String::from),
+            });
+        }
+
+        let anchor = parse_anchor(&mac_key, &self.read_anchor());
+        if let Some(unanchored) = unanchored(&anchor, &hashes) {
+            broken = Some(broken.map_or(unanchored, |broken| broken.min(unanchored)));
+        }
+
+        Ok((records, broken))
+    }
+
+    // re-encrypts the log for a new master password and rebuilds the chain with the
+    // new key, records that didn't check out before still don't afterwards, the new
+    // log is staged next to the old one and comes back with the anchor to commit
+    // along with the new password
+    fn stage_rekey_log(
+        &self,
+        old_key: &str,
+        new_key: &str,
+    ) -> Option<(PathBuf, serde_json::Value)> {
+        let lines = self.lines();
+        let old_mac_key = log_mac_key(old_key);
+        let new_mac_key = log_mac_key(new_key);
+
+        let mut old_previous = FIRST_HASH.to_vec();
+        let mut new_previous = FIRST_HASH.to_vec();
+        let mut old_hashes: Vec<Option<Vec<u8>>> = Vec::new();
+        let mut rekeyed: Vec<String> = Vec::new();
+
+        for (i, text) in lines.iter().enumerate() {
+            let mut line = match LogLine::parse(text) {
+                Some(line) => line,
+                None => {
+                    old_hashes.push(None);
+                    rekeyed.push(text.clone());
+                    continue;
+                }
+            };
+
+            let valid = line.seq == i as u64
+                && chain_hash(
+                    &old_mac_key,
+                    &old_previous,
+                    line.seq,
+                    &line.nonce,
+                    &line.data,
+                ) == line.hash;
+            old_hashes.push(Some(line.hash.clone()));
+            old_previous = line.hash.clone();
+
+            rekey_data(old_key, new_key, LOG_NAME, &line.nonce, &mut line.data);
+            if valid {
+                line.hash = chain_hash(
+                    &new_mac_key,
+                    &new_previous,
+                    line.seq,
+                    &line.nonce,
+                    &line.data,
+                );
+            }
+            new_previous = line.hash.clone();
+
+            rekeyed.push(line.to_line());
+        }
+
+        // the anchor moves to the end of the new chain only if it vouched for the
+        // end of the old one, otherwise whatever it pointed at is carried over so a
+        // cut short log doesn't become a clean one
+        let old_anchor = parse_anchor(&old_mac_key, &self.read_anchor());
+        let new_anchor = match (&old_anchor, unanchored(&old_anchor, &old_hashes)) {
+            (_, None) if lines.is_empty() => serde_json::Value::Null,
+            (_, None) => anchor_json(&new_mac_key, lines.len() as u64 - 1, &new_previous),
+            (Anchor::At(seq, hash), Some(_)) => anchor_json(&new_mac_key, *seq, hash),
+            _ => serde_json::Value::Null,
+        };
+
+        if lines.is_empty() && new_anchor.is_null() && old_anchor == Anchor::Missing {
+            return None;
+        }
+
+        let mut contents = rekeyed.join("\n");
+        if !contents.is_empty() {
+            contents.push('\n');
+        }
+        if let Err(why) = write_staged(&self.path, |writer| writer.write_all(contents.as_bytes())) {
+            panic!("Couldn't write audit log: {}", why);
+        }
+
+        Some((
+            self.path.clone(),
+            serde_json::json!({ ANCHOR_SETTING: new_anchor }),
+        ))
+    }
+
+    fn print_log(
+        &self,
+        key: &str,
+        entry: Option<&str>,
+        operation: Option<&str>,
+    ) -> Result<(), AppError> {
+        let (records, broken) = self.read_log(key)?;
+
+        if records.is_empty() && broken.is_none() {
+            let empty = "The audit log is empty.".cyan();
+            println!("{}", empty);
+            return Ok(());
+        }
+
+        let shown = records
+            .iter()
+            .filter(|record| entry.is_none() || record.entry.as_deref() == entry)
+            .filter(|record| operation.is_none() || Some(record.operation.as_str()) == operation)
+            .collect::<Vec<&AuditRecord>>();
+
+        let log_title = "Audit log:".cyan();
+        println!("{}", log_title);
+        for record in &shown {
+            let number = format!("{}.", record.seq + 1).cyan();
+            let mut line = format!(
+                "{} {} {}",
+                number,
+                format_timestamp(&record.time),
+                operation_name(&record.operation)
+            );
+            if let Some(entry) = &record.entry {
+                line.push_str(&format!(" \"{}\"", entry));
+            }
+            if let Some(detail) = &record.detail {
+                line.push_str(&format!(" {}", format!("({})", detail).cyan()));
+            }
+
+            // nothing from the first broken link on can be trusted
+            if broken.is_some_and(|broken| record.seq >= broken) {
+                println!("{} {}", line, "[unverified]".red());
+            } else {
+                println!("{}", line);
+            }
+        }
+        if shown.is_empty() {
+            let no_matches = "No matching records.".cyan();
+            println!("{}", no_matches);
+        }
+        println!();
+
+        match broken {
+            // every record there is checks out, but the log should go on further
+            Some(broken) if records.iter().all(|record| record.seq < broken) => {
+                let warning = format!(
+                    "The log ends early, records from {} on are missing or can't be vouched for.",
+                    broken + 1
+                );
+                println!("{}", warning.red());
+            }
+            Some(broken) => {
+                let warning = format!(
+                    "The chain is broken at record {}, the log has been tampered with or damaged from there on.",
+                    broken + 1
+                );
+                println!("{}", warning.red());
+            }
+            None => {
+                let verified = format!("Chain verified, {} records.", records.len()).cyan();
+                println!("{}", verified);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn review_log(&self, key: &str) -> Result<(), AppError> {
+        let q_filter = Question::select("filter")
+            .message("Which records would you like to see?")
+            .choices(vec!["All", "For one entry", "For one operation"])
+            .build();
+
+        let answer = prompt_one(q_filter)?;
+        match answer.as_list_item().unwrap().index {
+            1 => {
+                // entries that have since been deleted or renamed are still in the log
+                let (records, _) = self.read_log(key)?;
+                let mut entries = records
+                    .iter()
+                    .filter_map(|record| record.entry.clone())
+                    .collect::<Vec<String>>();
+                entries.sort();
+                entries.dedup();
+
+                if entries.is_empty() {
+                    return Err(AppError::new("No entries in the audit log."));
+                }
+
+                let q_entry = Question::select("entry")
+                    .message("Which entry?")
+                    .choices(entries.clone())
+                    .build();
+
+                let answer = prompt_one(q_entry)?;
+                let entry = &entries[answer.as_list_item().unwrap().index];
+                self.print_log(key, Some(entry), None)
+            }
+            2 => {
+                let q_operation = Question::select("operation")
+                    .message("Which operation?")
+                    .choices(
+                        AUDIT_OPERATIONS
+                            .iter()
+                            .map(|(_, description)| *description)
+                            .collect::<Vec<&str>>(),
+                    )
+                    .build();
+
+                let answer = prompt_one(q_operation)?;
+                let (operation, _) = AUDIT_OPERATIONS[answer.as_list_item().unwrap().index];
+                self.print_log(key, None, Some(operation))
+            }
+            _ => self.print_log(key, None, None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::Rng;
+    use std::fs;
+
+    // a folder of its own in the temp dir, removed again when the test is done
+    struct TestDir(PathBuf);
+
+    impl TestDir {
+        fn new() -> Self {
+            let name = format!("rpassman-audit-{}", rand::thread_rng().gen::<u64>());
+            let dir = std::env::temp_dir().join(name);
+            fs::create_dir(&dir).unwrap();
+            TestDir(dir)
+        }
+    }
+
+    impl Drop for TestDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn hashes(count: u8) -> Vec<Option<Vec<u8>>> {
+        (0..count).map(|i| Some(vec![i; 32])).collect()
+    }
+
+    #[test]
+    fn chain_hash_covers_every_part_of_a_link() {
+        let mac_key = log_mac_key("master");
+        let hash = chain_hash(&mac_key, &FIRST_HASH, 0, &[1; 12], b"data");
+
+        assert_eq!(
+            hash,
+            chain_hash(&mac_key, &FIRST_HASH, 0, &[1; 12], b"data")
+        );
+        assert_ne!(hash, chain_hash(&mac_key, &[1; 32], 0, &[1; 12], b"data"));
+        assert_ne!(
+            hash,
+            chain_hash(&mac_key, &FIRST_HASH, 1, &[1; 12], b"data")
+        );
+        assert_ne!(
+            hash,
+            chain_hash(&mac_key, &FIRST_HASH, 0, &[2; 12], b"data")
+        );
+        assert_ne!(
+            hash,
+            chain_hash(&mac_key, &FIRST_HASH, 0, &[1; 12], b"date")
+        );
+        assert_ne!(
+            hash,
+            chain_hash(&log_mac_key("other"), &FIRST_HASH, 0, &[1; 12], b"data")
+        );
+    }
+
+    #[test]
+    fn anchors_only_check_out_with_the_right_key() {
+        let mac_key = log_mac_key("master");
+        let anchor = anchor_json(&mac_key, 3, &[7; 32]);
+
+        assert_eq!(
+            parse_anchor(&mac_key, &serde_json::Value::Null),
+            Anchor::Missing
+        );
+        assert_eq!(parse_anchor(&mac_key, &anchor), Anchor::At(3, vec![7; 32]));
+        assert_eq!(parse_anchor(&log_mac_key("other"), &anchor), Anchor::Forged);
+
+        let mut moved = anchor.clone();
+        moved["seq"] = serde_json::json!(2);
+        assert_eq!(parse_anchor(&mac_key, &moved), Anchor::Forged);
+
+        let mut unsigned = anchor;
+        unsigned["mac"] = serde_json::Value::Null;
+        assert_eq!(parse_anchor(&mac_key, &unsigned), Anchor::Forged);
+    }
+
+    #[test]
+    fn anchor_vouches_for_a_log_that_ends_where_it_points() {
+        assert_eq!(unanchored(&Anchor::Missing, &[]), None);
+        assert_eq!(unanchored(&Anchor::At(2, vec![2; 32]), &hashes(3)), None);
+    }
+
+    #[test]
+    fn anchor_points_out_where_the_log_stops_checking_out() {
+        // a record added without moving the anchor
+        assert_eq!(unanchored(&Anchor::At(1, vec![1; 32]), &hashes(3)), Some(2));
+        // records cut off the end
+        assert_eq!(unanchored(&Anchor::At(4, vec![4; 32]), &hashes(3)), Some(3));
+        // the anchored record rewritten
+        assert_eq!(unanchored(&Anchor::At(2, vec![9; 32]), &hashes(3)), Some(2));
+        // a mangled line where the anchor points
+        let mut mangled = hashes(3);
+        mangled[2] = None;
+        assert_eq!(unanchored(&Anchor::At(2, vec![2; 32]), &mangled), Some(2));
+        // no anchor to vouch for anything
+        assert_eq!(unanchored(&Anchor::Missing, &hashes(3)), Some(3));
+        assert_eq!(unanchored(&Anchor::Forged, &hashes(3)), Some(3));
+    }
+
+    #[test]
+    fn log_lines_round_trip() {
+        let line = LogLine {
+            seq: 5,
+            nonce: vec![1; 12],
+            data: b"data".to_vec(),
+            hash: vec![2; 32],
+        };
+        let parsed = LogLine::parse(&line.to_line()).unwrap();
+
+        assert_eq!(parsed.seq, 5);
+        assert_eq!(parsed.nonce, line.nonce);
+        assert_eq!(parsed.data, line.data);
+        assert_eq!(parsed.hash, line.hash);
+
+        let short_nonce = line.to_line().replace(&hex::encode([1u8; 12]), "0101");
+        assert!(LogLine::parse(&short_nonce).is_none());
+        assert!(LogLine::parse("not json").is_none());
+    }
+
+    #[test]
+    fn operations_fall_back_to_their_own_name() {
+        assert_eq!(operation_name("delete"), "Moved to the trash");
+        assert_eq!(operation_name("something_new"), "something_new");
+    }
+
+    #[test]
+    fn recorded_log_reads_back_intact() {
+        let dir = TestDir::new();
+        let log = AuditLog::new(dir.0.join("audit.log"));
+
+        log.record("master", "unlock", None, None);
+        log.record("master", "view", Some("mail"), None);
+        log.record("master", "update", Some("mail"), Some("password"));
+
+        let (records, broken) = log.read_log("master").unwrap();
+        assert_eq!(broken, None);
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[1].seq, 1);
+        assert_eq!(records[1].operation, "view");
+        assert_eq!(records[1].entry.as_deref(), Some("mail"));
+        assert_eq!(records[2].detail.as_deref(), Some("password"));
+
+        // the wrong key can't vouch for any of it
+        assert_eq!(log.read_log("other").unwrap().1, Some(0));
+    }
+
+    #[test]
+    fn tampering_with_the_log_is_detected() {
+        let dir = TestDir::new();
+        let path = dir.0.join("audit.log");
+        let log = AuditLog::new(path.clone());
+        for _ in 0..3 {
+            log.record("master", "view", Some("mail"), None);
+        }
+        let lines: Vec<String> = log.lines();
+
+        // a record rewritten in place
+        let mut line = LogLine::parse(&lines[1]).unwrap();
+        line.data[0] ^= 1;
+        let rewritten = [lines[0].clone(), line.to_line(), lines[2].clone()];
+        fs::write(&path, rewritten.join("\n") + "\n").unwrap();
+        assert_eq!(log.read_log("master").unwrap().1, Some(1));
+
+        // the last record cut off
+        fs::write(&path, lines[..2].join("\n") + "\n").unwrap();
+        assert_eq!(log.read_log("master").unwrap().1, Some(2));
+    }
+}
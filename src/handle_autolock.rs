@@ -0,0 +1,187 @@
+// question stuff
+use requestty::prompt::{backend::CrosstermBackend, events::KeyEvent, Answer, EventIterator};
+use requestty::Question;
+
+// password stuff
+use secrecy::Secret;
+
+// other stuff
+use crossterm::event;
+use std::io::{self, StdoutLock};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// ------------------ //
+
+// how often the watcher checks whether the timeout has passed
+const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+// how long a prompt waits for a key before looking at the lock again
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+pub trait WatchIdle {
+    fn new(key: Arc<Mutex<Secret<String>>>) -> Self;
+    fn start(&self);
+    fn set_timeout(&self, minutes: i64);
+    fn restart(&self);
+}
+
+struct IdleState {
+    last_activity: Instant,
+    // no timeout means the vault never locks by itself
+    timeout: Option<Duration>,
+    locked: bool,
+}
+
+// locks the vault once nothing has happened for a while, from a thread of its own,
+// prompts then stop whatever operation is running so the main loop can wipe the key
+// and unlock again
+pub struct IdleWatcher {
+    key: Arc<Mutex<Secret<String>>>,
+    state: Arc<Mutex<IdleState>>,
+}
+
+// what an operation is stopped with when the vault locks under it, it is passed up
+// to the main loop, dropping everything that was decrypted on the way
+#[derive(Debug)]
+pub struct VaultLocked;
+
+// the watcher that prompts report key presses to and check the lock with, there is
+// none for the one-off commands
+static WATCHED_STATE: OnceLock<Arc<Mutex<IdleState>>> = OnceLock::new();
+
+fn vault_locked() -> bool {
+    WATCHED_STATE
+        .get()
+        .is_some_and(|state| state.lock().unwrap().locked)
+}
+
+// for anything that takes a while without prompting to stop at once the vault locks
+pub fn check_locked() -> Result<(), VaultLocked> {
+    if vault_locked() {
+        Err(VaultLocked)
+    } else {
+        Ok(())
+    }
+}
+
+// counts as activity, for things the user does outside of a prompt
+pub fn record_activity() {
+    if let Some(state) = WATCHED_STATE.get() {
+        state.lock().unwrap().last_activity = Instant::now();
+    }
+}
+
+// every key pressed in a prompt counts as activity, and a prompt that is waiting
+// for one gives up as soon as the vault locks
+pub struct IdleEvents {}
+
+impl EventIterator for IdleEvents {
+    fn next_event(&mut self) -> io::Result<KeyEvent> {
+        loop {
+            if vault_locked() {
+                return Err(io::Error::other("The vault is locked."));
+            }
+            if !event::poll(POLL_INTERVAL)? {
+                continue;
+            }
+
+            if let event::Event::Key(key) = event::read()? {
+                record_activity();
+                if let Ok(key) = KeyEvent::try_from(key) {
+                    return Ok(key);
+                }
+            }
+        }
+    }
+}
+
+// runs a prompt on the terminal, unless the vault is locked before or while it is
+// running, then VaultLocked is returned for the operation asking to pass up, ctrl-c
+// quits like it does anywhere else
+pub fn run_prompt<T>(
+    ask: impl FnOnce(&mut CrosstermBackend<StdoutLock>, &mut IdleEvents) -> requestty::Result<T>,
+) -> Result<T, VaultLocked> {
+    check_locked()?;
+
+    let stdout = io::stdout();
+    let mut backend = CrosstermBackend::new(stdout.lock());
+    let answer = ask(&mut backend, &mut IdleEvents {});
+    drop(backend);
+
+    check_locked()?;
+
+    match answer {
+        Ok(answer) => Ok(answer),
+        Err(requestty::ErrorKind::IoError(why)) => {
+            eprintln!("Couldn't read from the terminal: {}", why);
+            std::process::exit(1);
+        }
+        Err(_) => std::process::exit(1),
+    }
+}
+
+// asks a question like requestty::prompt_one, stopping when the vault locks
+pub fn prompt_one<'a>(question: impl Into<Question<'a>>) -> Result<Answer, VaultLocked> {
+    run_prompt(|backend, events| requestty::prompt_one_with(question, backend, events))
+}
+
+impl WatchIdle for IdleWatcher {
+    fn new(key: Arc<Mutex<Secret<String>>>) -> Self {
+        IdleWatcher {
+            key,
+            state: Arc::new(Mutex::new(IdleState {
+                last_activity: Instant::now(),
+                timeout: None,
+                locked: false,
+            })),
+        }
+    }
+
+    fn start(&self) {
+        let _ = WATCHED_STATE.set(Arc::clone(&self.state));
+
+        let key = Arc::clone(&self.key);
+        let state = Arc::clone(&self.state);
+
+        thread::spawn(move || loop {
+            thread::sleep(CHECK_INTERVAL);
+
+            let mut state = state.lock().unwrap();
+            let timeout = match state.timeout {
+                Some(timeout) if !state.locked => timeout,
+                _ => continue,
+            };
+            if state.last_activity.elapsed() < timeout {
+                continue;
+            }
+
+            // an operation that has the key borrowed gives it back as soon as it sees
+            // the lock, and the main loop wipes it then, the old key is zeroized as it
+            // is dropped
+            if let Ok(mut key) = key.try_lock() {
+                *key = Secret::new(String::new());
+            }
+            state.locked = true;
+        });
+    }
+
+    // timeouts come from the preferences, so they can change while running
+    fn set_timeout(&self, minutes: i64) {
+        let mut state = self.state.lock().unwrap();
+        state.timeout = if minutes > 0 {
+            Some(Duration::from_secs(minutes as u64 * 60))
+        } else {
+            None
+        };
+    }
+
+    // starts watching again from now, the password prompt after a lock is watched
+    // like any other
+    fn restart(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.locked = false;
+        state.last_activity = Instant::now();
+    }
+}
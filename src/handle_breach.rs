@@ -0,0 +1,343 @@
+// file stuff
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+// password stuff
+use md4::Md4;
+use sha1::{Digest, Sha1};
+
+// my stuff
+use crate::errors::AppError;
+
+// ------------------ //
+
+// once the search is down to this many bytes the rest is read line by line
+const SCAN_SIZE: u64 = 4096;
+
+// enough to hold any line of the file, a hash and a count
+const PROBE_SIZE: usize = 256;
+
+pub trait CheckBreaches {
+    fn new(path: &Path, ntlm: bool) -> Result<Self, AppError>
+    where
+        Self: Sized;
+    fn times_seen(&mut self, password: &str) -> Result<u64, AppError>;
+}
+
+// checks passwords against a downloaded Have I Been Pwned dump, either one file
+// sorted by hash or a folder of range files named after the first five characters
+// of the hashes in them, nothing is sent anywhere
+pub struct BreachChecker {
+    path: PathBuf,
+    ntlm: bool,
+    // the sorted file and its size, a folder of range files is opened per lookup
+    file: Option<(File, u64)>,
+}
+
+fn read_error(why: std::io::Error) -> AppError {
+    AppError::new(&format!("Couldn't read the breach file: {}", why))
+}
+
+// the part of a line before the count, in upper case like the hashes we look for
+fn line_hash(line: &str) -> String {
+    line.split(':').next().unwrap_or("").trim().to_uppercase()
+}
+
+fn line_count(line: &str) -> u64 {
+    line.split(':')
+        .nth(1)
+        .and_then(|count| count.trim().parse::<u64>().ok())
+        .unwrap_or(1)
+}
+
+impl BreachChecker {
+    fn password_hash(&self, password: &str) -> String {
+        if self.ntlm {
+            // ntlm hashes are md4 over the password in utf-16
+            let utf16 = password
+                .encode_utf16()
+                .flat_map(|unit| unit.to_le_bytes())
+                .collect::<Vec<u8>>();
+            hex::encode_upper(Md4::digest(utf16))
+        } else {
+            hex::encode_upper(Sha1::digest(password.as_bytes()))
+        }
+    }
+
+    // the first line starting at or after `offset`, along with where it starts
+    fn line_from(file: &mut File, offset: u64) -> Result<Option<(u64, String)>, AppError> {
+        // whether a line starts right at the offset depends on the byte before it
+        let start = offset.saturating_sub(1);
+        file.seek(SeekFrom::Start(start)).map_err(read_error)?;
+
+        let mut buffer = vec![0u8; PROBE_SIZE * 2];
+        let read = file.read(&mut buffer).map_err(read_error)?;
+        let buffer = &buffer[..read];
+
+        let line_start = if offset == 0 {
+            0
+        } else {
+            match buffer.iter().position(|b| *b == b'\n') {
+                Some(newline) => newline + 1,
+                None => return Ok(None),
+            }
+        };
+
+        let rest = &buffer[line_start..];
+        if rest.is_empty() {
+            return Ok(None);
+        }
+        let line_end = rest.iter().position(|b| *b == b'\n').unwrap_or(rest.len());
+        let line = String::from_utf8_lossy(&rest[..line_end]).to_string();
+
+        Ok(Some((start + line_start as u64, line)))
+    }
+
+    // binary search by seeking around the file, so even the full dump only takes a
+    // few dozen reads per password
+    fn search_sorted(&mut self, hash: &str) -> Result<u64, AppError> {
+        let (file, size) = self.file.as_mut().unwrap();
+        let size = *size;
+
+        // every line starting before `low` has a smaller hash, and a line with the
+        // hash starts before `high` if there is one
+        let mut low = 0;
+        let mut high = size;
+
+        while high - low > SCAN_SIZE {
+            let middle = low + (high - low) / 2;
+
+            match BreachChecker::line_from(file, middle)? {
+                Some((start, line)) if start < high => {
+                    let line_hash = line_hash(&line);
+                    if line_hash == hash {
+                        return Ok(line_count(&line));
+                    } else if line_hash.as_str() < hash {
+                        low = start + 1;
+                    } else {
+                        high = middle;
+                    }
+                }
+                _ => high = middle,
+            }
+        }
+
+        let start = match BreachChecker::line_from(file, low)? {
+            Some((start, _)) => start,
+            None => return Ok(0),
+        };
+        file.seek(SeekFrom::Start(start)).map_err(read_error)?;
+
+        for line in BufReader::new(file).lines() {
+            let line = line.map_err(read_error)?;
+            let line_hash = line_hash(&line);
+
+            if line_hash == hash {
+                return Ok(line_count(&line));
+            }
+            if line_hash.as_str() > hash {
+                break;
+            }
+        }
+
+        Ok(0)
+    }
+
+    // range files only hold the rest of each hash after the first five characters
+    fn search_range(&self, hash: &str) -> Result<u64, AppError> {
+        let (prefix, suffix) = hash.split_at(5);
+
+        let range_path = [format!("{}.txt", prefix), String::from(prefix)]
+            .iter()
+            .map(|name| self.path.join(name))
+            .find(|path| path.exists());
+        let range_path = match range_path {
+            Some(range_path) => range_path,
+            None => {
+                return Err(AppError::new(&format!(
+                    "The range file for {} is missing from {}.",
+                    prefix,
+                    self.path.display()
+                )))
+            }
+        };
+
+        let file = File::open(&range_path).map_err(read_error)?;
+        for line in BufReader::new(file).lines() {
+            let line = line.map_err(read_error)?;
+            if line_hash(&line) == suffix {
+                return Ok(line_count(&line));
+            }
+        }
+
+        Ok(0)
+    }
+}
+
+impl CheckBreaches for BreachChecker {
+    fn new(path: &Path, ntlm: bool) -> Result<Self, AppError> {
+        let file = if path.is_dir() {
+            None
+        } else {
+            let mut file = match File::open(path) {
+                Err(why) => {
+                    return Err(AppError::new(&format!(
+                        "Couldn't open the breach file: {}",
+                        why
+                    )))
+                }
+                Ok(file) => file,
+            };
+            let size = file.metadata().map_err(read_error)?.len();
+
+            // searching a file of the other kind of hash would never find anything
+            if let Some((_, line)) = BreachChecker::line_from(&mut file, 0)? {
+                match (line_hash(&line).len(), ntlm) {
+                    (40, true) => {
+                        return Err(AppError::new(
+                            "That file holds SHA-1 hashes, not NTLM ones.",
+                        ))
+                    }
+                    (32, false) => {
+                        return Err(AppError::new(
+                            "That file holds NTLM hashes, not SHA-1 ones.",
+                        ))
+                    }
+                    _ => (),
+                }
+            }
+
+            Some((file, size))
+        };
+
+        Ok(BreachChecker {
+            path: path.to_path_buf(),
+            ntlm,
+            file,
+        })
+    }
+
+    // how many times the password shows up in the breaches, 0 if it doesn't
+    fn times_seen(&mut self, password: &str) -> Result<u64, AppError> {
+        let hash = self.password_hash(password);
+
+        if self.file.is_some() {
+            self.search_sorted(&hash)
+        } else {
+            self.search_range(&hash)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::Rng;
+    use std::fs;
+
+    // a folder of its own in the temp dir, removed again when the test is done
+    struct TestDir(PathBuf);
+
+    impl TestDir {
+        fn new() -> Self {
+            let name = format!("rpassman-breach-{}", rand::thread_rng().gen::<u64>());
+            let dir = std::env::temp_dir().join(name);
+            fs::create_dir(&dir).unwrap();
+            TestDir(dir)
+        }
+    }
+
+    impl Drop for TestDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn sha1(password: &str) -> String {
+        hex::encode_upper(Sha1::digest(password.as_bytes()))
+    }
+
+    // enough passwords that the sorted file is searched rather than scanned, each
+    // seen as many times as its number plus one
+    fn breached() -> Vec<(String, u64)> {
+        let mut breached = (0..2000)
+            .map(|i| (sha1(&format!("breached{}", i)), i + 1))
+            .collect::<Vec<(String, u64)>>();
+        breached.sort();
+        breached
+    }
+
+    fn count_of(password: &str) -> u64 {
+        password
+            .trim_start_matches("breached")
+            .parse::<u64>()
+            .unwrap()
+            + 1
+    }
+
+    #[test]
+    fn finds_passwords_in_a_sorted_file() {
+        let dir = TestDir::new();
+        let path = dir.0.join("pwned.txt");
+
+        let lines = breached()
+            .iter()
+            .map(|(hash, count)| format!("{}:{}\r\n", hash, count))
+            .collect::<String>();
+        fs::write(&path, lines).unwrap();
+
+        let mut checker = BreachChecker::new(&path, false).unwrap();
+        for password in ["breached0", "breached1", "breached999", "breached1999"] {
+            assert_eq!(checker.times_seen(password).unwrap(), count_of(password));
+        }
+        assert_eq!(checker.times_seen("not breached").unwrap(), 0);
+
+        // the first and last hashes in the file are found too
+        let sorted = breached();
+        for (hash, count) in [&sorted[0], &sorted[sorted.len() - 1]] {
+            assert_eq!(checker.search_sorted(hash).unwrap(), *count);
+        }
+    }
+
+    #[test]
+    fn finds_passwords_in_range_files() {
+        let dir = TestDir::new();
+
+        for (hash, count) in breached() {
+            let (prefix, suffix) = hash.split_at(5);
+            let mut range = fs::read_to_string(dir.0.join(prefix)).unwrap_or_default();
+            range.push_str(&format!("{}:{}\r\n", suffix, count));
+            fs::write(dir.0.join(prefix), range).unwrap();
+        }
+
+        let mut checker = BreachChecker::new(&dir.0, false).unwrap();
+        assert_eq!(checker.times_seen("breached42").unwrap(), 43);
+
+        // a password whose range isn't there can't be said to be safe
+        assert!(checker.times_seen("not breached").is_err());
+    }
+
+    #[test]
+    fn refuses_a_file_of_the_other_hash() {
+        let dir = TestDir::new();
+        let path = dir.0.join("pwned.txt");
+        fs::write(&path, format!("{}:3\r\n", sha1("password"))).unwrap();
+
+        assert!(BreachChecker::new(&path, true).is_err());
+        assert!(BreachChecker::new(&path, false).is_ok());
+    }
+
+    #[test]
+    fn hashes_ntlm_passwords_as_utf16() {
+        let dir = TestDir::new();
+        let path = dir.0.join("pwned.txt");
+        fs::write(&path, "").unwrap();
+
+        let checker = BreachChecker::new(&path, true).unwrap();
+        assert_eq!(
+            checker.password_hash("password"),
+            "8846F7EAEE8FB117AD06BDD830B7586C"
+        );
+    }
+}
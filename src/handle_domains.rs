@@ -0,0 +1,216 @@
+// other stuff
+use std::collections::HashSet;
+use std::sync::OnceLock;
+
+// my stuff
+use crate::handle_fields::url_host;
+
+// ------------------ //
+
+// ways an entry's urls can be matched against a url being looked up, along with
+// their description, "domain" is used for entries that don't set one
+pub const URL_MATCH_RULES: [(&str, &str); 4] = [
+    (
+        "domain",
+        "Same domain (login.example.com matches example.com)",
+    ),
+    ("subdomain", "Same host or one of its subdomains"),
+    ("host", "Exact host only"),
+    ("never", "Never match this entry"),
+];
+
+// the public suffix list, the suffixes under which anyone can register a name of
+// their own, including hosting providers like github.io that give every customer a
+// subdomain, so two of them never count as the same domain
+const PUBLIC_SUFFIX_LIST: &str = include_str!("public_suffix_list.dat");
+
+// the rules of the list, parsed the first time a domain is looked up
+static PUBLIC_SUFFIXES: OnceLock<HashSet<&'static str>> = OnceLock::new();
+
+// what a lookup matched, better matches sort first
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum UrlMatch {
+    Host,
+    Subdomain,
+    Domain,
+}
+
+impl UrlMatch {
+    pub fn description(&self) -> &'static str {
+        match self {
+            UrlMatch::Host => "same host",
+            UrlMatch::Subdomain => "subdomain",
+            UrlMatch::Domain => "same domain",
+        }
+    }
+}
+
+pub trait MatchUrls {
+    fn new(url: &str) -> Option<Self>
+    where
+        Self: Sized;
+    fn match_url(&self, rule: &str, stored_url: &str) -> Option<UrlMatch>;
+}
+
+pub struct UrlMatcher {
+    host: String,
+    domain: String,
+}
+
+fn is_ip_address(host: &str) -> bool {
+    host.parse::<std::net::IpAddr>().is_ok()
+}
+
+fn public_suffixes() -> &'static HashSet<&'static str> {
+    PUBLIC_SUFFIXES.get_or_init(|| {
+        PUBLIC_SUFFIX_LIST
+            .lines()
+            .map(|line| line.trim())
+            .filter(|line| !line.is_empty() && !line.starts_with("//"))
+            .collect()
+    })
+}
+
+// how many labels at the end of a host make up its public suffix, the longest rule
+// that matches wins, exceptions beat wildcards, and a top level domain that isn't
+// listed is a suffix by itself
+fn public_suffix_length(labels: &[&str]) -> usize {
+    let suffixes = public_suffixes();
+
+    for i in 0..labels.len() {
+        let candidate = labels[i..].join(".");
+
+        if suffixes.contains(format!("!{}", candidate).as_str()) {
+            return labels.len() - i - 1;
+        }
+        if suffixes.contains(candidate.as_str()) {
+            return labels.len() - i;
+        }
+        if i + 1 < labels.len()
+            && suffixes.contains(format!("*.{}", labels[i + 1..].join(".")).as_str())
+        {
+            return labels.len() - i;
+        }
+    }
+
+    1
+}
+
+// the part of a host name that is registered with a registrar, like example.co.uk
+// for login.example.co.uk, a host that is a public suffix itself is its own domain
+pub fn registrable_domain(host: &str) -> String {
+    if is_ip_address(host) {
+        return String::from(host);
+    }
+
+    let host = host.trim_end_matches('.').to_lowercase();
+    let labels = host.split('.').collect::<Vec<&str>>();
+
+    let suffix_length = public_suffix_length(&labels);
+    if suffix_length >= labels.len() {
+        return host;
+    }
+
+    labels[labels.len() - suffix_length - 1..].join(".")
+}
+
+impl MatchUrls for UrlMatcher {
+    fn new(url: &str) -> Option<Self> {
+        let host = url_host(url)?;
+        let domain = registrable_domain(&host);
+
+        Some(UrlMatcher { host, domain })
+    }
+
+    // how the url being looked up matches a url stored in an entry, if it does
+    // under the entry's rule
+    fn match_url(&self, rule: &str, stored_url: &str) -> Option<UrlMatch> {
+        let stored_host = url_host(stored_url)?;
+
+        if rule == "never" {
+            return None;
+        }
+        if stored_host == self.host {
+            return Some(UrlMatch::Host);
+        }
+        if rule == "host" {
+            return None;
+        }
+
+        // a subdomain of the stored host matches, but not the other way around, a
+        // url saved for login.example.com shouldn't be offered on example.com
+        if !is_ip_address(&self.host) && self.host.ends_with(&format!(".{}", stored_host)) {
+            return Some(UrlMatch::Subdomain);
+        }
+        if rule == "subdomain" {
+            return None;
+        }
+
+        if registrable_domain(&stored_host) == self.domain {
+            Some(UrlMatch::Domain)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn registrable_domain_of_plain_hosts() {
+        assert_eq!(registrable_domain("login.example.com"), "example.com");
+        assert_eq!(registrable_domain("example.com"), "example.com");
+        assert_eq!(registrable_domain("a.b.example.co.uk"), "example.co.uk");
+        assert_eq!(registrable_domain("Login.Example.COM."), "example.com");
+    }
+
+    #[test]
+    fn registrable_domain_keeps_tenants_of_shared_hosts_apart() {
+        assert_eq!(registrable_domain("alice.github.io"), "alice.github.io");
+        assert_eq!(registrable_domain("www.alice.github.io"), "alice.github.io");
+        assert_eq!(registrable_domain("github.io"), "github.io");
+    }
+
+    #[test]
+    fn registrable_domain_follows_wildcards_and_exceptions() {
+        // *.ck is a suffix, except for www.ck
+        assert_eq!(registrable_domain("shop.example.ck"), "shop.example.ck");
+        assert_eq!(registrable_domain("www.ck"), "www.ck");
+        assert_eq!(registrable_domain("a.www.ck"), "www.ck");
+    }
+
+    #[test]
+    fn registrable_domain_of_unlisted_and_ip_hosts() {
+        assert_eq!(registrable_domain("router.internal"), "router.internal");
+        assert_eq!(registrable_domain("a.router.internal"), "router.internal");
+        assert_eq!(registrable_domain("192.168.1.1"), "192.168.1.1");
+    }
+
+    #[test]
+    fn match_url_follows_the_rule() {
+        let matcher = UrlMatcher::new("https://login.example.com/signin").unwrap();
+
+        let stored = "https://login.example.com";
+        assert!(matcher.match_url("host", stored) == Some(UrlMatch::Host));
+        assert!(matcher.match_url("never", stored).is_none());
+
+        let stored = "example.com";
+        assert!(matcher.match_url("subdomain", stored) == Some(UrlMatch::Subdomain));
+        assert!(matcher.match_url("host", stored).is_none());
+
+        let stored = "https://www.example.com";
+        assert!(matcher.match_url("domain", stored) == Some(UrlMatch::Domain));
+        assert!(matcher.match_url("subdomain", stored).is_none());
+    }
+
+    #[test]
+    fn match_url_never_matches_other_tenants() {
+        let matcher = UrlMatcher::new("https://mallory.github.io").unwrap();
+
+        assert!(matcher
+            .match_url("domain", "https://alice.github.io")
+            .is_none());
+    }
+}
@@ -0,0 +1,212 @@
+// file stuff
+use std::fs::{self, DirBuilder, File, OpenOptions};
+use std::io::prelude::*;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, ExitStatus};
+
+// password stuff
+use rand::Rng;
+use secrecy::Secret;
+
+// other stuff
+use crossterm::{cursor, execute, terminal};
+use std::thread;
+use std::time::Duration;
+
+// my stuff
+use crate::errors::AppError;
+use crate::handle_autolock::{check_locked, record_activity};
+
+// ------------------ //
+
+// how often the editor is checked on while it is open
+const WAIT_INTERVAL: Duration = Duration::from_millis(200);
+
+pub trait EditText {
+    fn new() -> Self;
+    fn edit(&self, initial: &str) -> Result<Secret<String>, AppError>;
+}
+
+pub struct EditorHandler {}
+
+// somewhere memory backed to put the temporary file, so the text never reaches disk,
+// the runtime dir is private to the user already unlike /dev/shm
+fn private_temp_dir() -> Option<PathBuf> {
+    let mut candidates = Vec::new();
+    if let Ok(runtime_dir) = std::env::var("XDG_RUNTIME_DIR") {
+        candidates.push(PathBuf::from(runtime_dir));
+    }
+    candidates.push(PathBuf::from("/dev/shm"));
+
+    candidates.into_iter().find(|dir| dir.is_dir())
+}
+
+// the file goes in a folder of its own that only we can get into, editors put swap
+// and backup files next to the file they edit, and those have to go as well
+fn create_private_dir(parent: &Path) -> std::io::Result<PathBuf> {
+    let mut rng = rand::thread_rng();
+    let dir = parent.join(format!("rpassman-{}", hex::encode(rng.gen::<[u8; 8]>())));
+
+    let mut builder = DirBuilder::new();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::DirBuilderExt;
+        builder.mode(0o700);
+    }
+
+    builder.create(&dir)?;
+    Ok(dir)
+}
+
+// overwrite the file before removing it so the text doesn't linger in freed pages
+fn shred_file(path: &Path) {
+    if let Ok(metadata) = fs::metadata(path) {
+        if let Ok(mut file) = OpenOptions::new().write(true).open(path) {
+            let _ = file.write_all(&vec![0u8; metadata.len() as usize]);
+            let _ = file.sync_all();
+        }
+    }
+
+    let _ = fs::remove_file(path);
+}
+
+fn create_private_file(path: &Path) -> std::io::Result<File> {
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+
+    // only we should be able to read the file while the editor has it open
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+
+    options.open(path)
+}
+
+// waits for the editor to close, we can't see what is typed into it so saving the
+// file counts as activity instead, if the vault locks anyway the editor is closed
+// and whatever it had is thrown away
+fn wait_for_editor(editor: &mut Child, path: &Path) -> Result<ExitStatus, AppError> {
+    let modified = || {
+        fs::metadata(path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+    };
+    let mut last_modified = modified();
+
+    loop {
+        match editor.try_wait() {
+            Ok(Some(status)) => return Ok(status),
+            Ok(None) => (),
+            Err(why) => {
+                return Err(AppError::new(&format!(
+                    "Couldn't wait for the editor: {}",
+                    why
+                )))
+            }
+        }
+
+        if let Err(locked) = check_locked() {
+            let _ = editor.kill();
+            let _ = editor.wait();
+
+            // the editor doesn't get to put the terminal back the way it found it
+            let _ = Command::new("stty").arg("sane").status();
+            let _ = execute!(
+                std::io::stdout(),
+                terminal::LeaveAlternateScreen,
+                cursor::Show
+            );
+            return Err(locked.into());
+        }
+
+        let now_modified = modified();
+        if now_modified != last_modified {
+            record_activity();
+            last_modified = now_modified;
+        }
+
+        thread::sleep(WAIT_INTERVAL);
+    }
+}
+
+impl EditText for EditorHandler {
+    fn new() -> Self {
+        EditorHandler {}
+    }
+
+    fn edit(&self, initial: &str) -> Result<Secret<String>, AppError> {
+        // a file on disk can't be reliably wiped, so the text is never put there
+        let dir = match private_temp_dir() {
+            Some(dir) => dir,
+            None => return Err(AppError::new(
+                "No memory backed temp folder was found to edit in, set XDG_RUNTIME_DIR to one.",
+            )),
+        };
+
+        let dir = match create_private_dir(&dir) {
+            Err(why) => {
+                return Err(AppError::new(&format!(
+                    "Couldn't create temp folder: {}",
+                    why
+                )))
+            }
+            Ok(dir) => dir,
+        };
+        let path = dir.join("entry.txt");
+
+        let mut file = match create_private_file(&path) {
+            Err(why) => {
+                let _ = fs::remove_dir_all(&dir);
+                return Err(AppError::new(&format!(
+                    "Couldn't create temp file: {}",
+                    why
+                )));
+            }
+            Ok(file) => file,
+        };
+
+        if let Err(why) = file.write_all(initial.as_bytes()) {
+            shred_file(&path);
+            let _ = fs::remove_dir_all(&dir);
+            return Err(AppError::new(&format!("Couldn't write temp file: {}", why)));
+        }
+        drop(file);
+
+        let editor = std::env::var("VISUAL")
+            .or_else(|_| std::env::var("EDITOR"))
+            .unwrap_or(String::from("vi"));
+
+        // the editor setting can carry its own arguments, like `code --wait`
+        let mut editor_parts = editor.split_whitespace();
+        let status = match Command::new(editor_parts.next().unwrap_or("vi"))
+            .args(editor_parts)
+            .arg(&path)
+            .spawn()
+        {
+            Ok(mut child) => wait_for_editor(&mut child, &path),
+            Err(why) => Err(AppError::new(&format!(
+                "Couldn't start editor \"{}\": {}",
+                editor, why
+            ))),
+        };
+
+        let result = match status {
+            Ok(status) if status.success() => match fs::read_to_string(&path) {
+                // editors like to add a newline at the end of the file
+                Ok(text) => Ok(Secret::new(String::from(
+                    text.strip_suffix('\n').unwrap_or(&text),
+                ))),
+                Err(why) => Err(AppError::new(&format!("Couldn't read temp file: {}", why))),
+            },
+            Ok(_) => Err(AppError::new("The editor exited with an error.")),
+            Err(e) => Err(e),
+        };
+
+        shred_file(&path);
+        let _ = fs::remove_dir_all(&dir);
+
+        result
+    }
+}
@@ -0,0 +1,267 @@
+// question stuff
+use requestty::Question;
+
+// other stuff
+use chrono::prelude::*;
+use colored::Colorize;
+
+// my stuff
+use crate::handle_autolock::{prompt_one, VaultLocked};
+use crate::handle_fields::validate_field_value;
+
+// ------------------ //
+
+// entries and fields can both carry an expiry date ("expires", as YYYY-MM-DD) and a
+// rotation interval ("rotation_days"), these are kept in the clear next to the
+// entry name so expiring credentials can be checked without unlocking the vault
+pub struct ExpiryItem {
+    pub entry_name: String,
+    pub field: Option<(usize, String)>,
+    pub due: NaiveDate,
+    pub rotation: bool,
+}
+
+pub trait CheckExpiry {
+    fn new(warning_days: i64) -> Self;
+    fn expiring_items(&self, entries: &[serde_json::Value]) -> Vec<ExpiryItem>;
+    fn print_items(&self, items: &[ExpiryItem]);
+    fn prompt_expiry(&self, target: &mut serde_json::Value, label: &str)
+        -> Result<(), VaultLocked>;
+}
+
+pub struct ExpiryHandler {
+    warning_days: i64,
+    today: NaiveDate,
+}
+
+fn timestamp_date(timestamp: &serde_json::Value) -> Option<NaiveDate> {
+    DateTime::parse_from_rfc3339(timestamp.as_str()?)
+        .ok()
+        .map(|time| time.with_timezone(&Local).date_naive())
+}
+
+// the earlier of the expiry date and the next rotation, rotations count from
+// `changed`, the last time the value was set
+fn due_date(target: &serde_json::Value, changed: Option<NaiveDate>) -> Option<(NaiveDate, bool)> {
+    let expires = target["expires"]
+        .as_str()
+        .and_then(|date| NaiveDate::parse_from_str(date, "%Y-%m-%d").ok())
+        .map(|date| (date, false));
+
+    let rotation = match (target["rotation_days"].as_i64(), changed) {
+        (Some(days), Some(changed)) => Some((changed + chrono::Duration::days(days), true)),
+        _ => None,
+    };
+
+    match (expires, rotation) {
+        (Some(expires), Some(rotation)) => Some(if rotation.0 < expires.0 {
+            rotation
+        } else {
+            expires
+        }),
+        (expires, rotation) => expires.or(rotation),
+    }
+}
+
+// a short description of the expiry settings, if there are any
+pub fn expiry_summary(target: &serde_json::Value) -> Option<String> {
+    let mut parts: Vec<String> = Vec::new();
+
+    if let Some(expires) = target["expires"].as_str() {
+        parts.push(format!("expires {}", expires));
+    }
+    if let Some(days) = target["rotation_days"].as_i64() {
+        parts.push(format!("rotate every {} days", days));
+    }
+
+    if parts.is_empty() {
+        None
+    } else {
+        Some(parts.join(", "))
+    }
+}
+
+impl ExpiryItem {
+    // field names are encrypted, so fields are shown by their position and type
+    fn label(&self) -> String {
+        match &self.field {
+            Some((number, field_type)) => {
+                format!("{} › field {} ({})", self.entry_name, number, field_type)
+            }
+            None => self.entry_name.clone(),
+        }
+    }
+}
+
+impl CheckExpiry for ExpiryHandler {
+    fn new(warning_days: i64) -> Self {
+        ExpiryHandler {
+            warning_days,
+            today: Local::now().date_naive(),
+        }
+    }
+
+    fn expiring_items(&self, entries: &[serde_json::Value]) -> Vec<ExpiryItem> {
+        let mut items: Vec<ExpiryItem> = Vec::new();
+        let warn_until = self.today + chrono::Duration::days(self.warning_days);
+
+        for entry in entries {
+            let entry_name = String::from(entry["name"].as_str().unwrap());
+            let created = timestamp_date(&entry["created"]);
+            let fields = entry["fields"].as_array().unwrap();
+
+            // rotating a whole entry counts from the last time any of its values
+            // changed, entries from before that was recorded count from creation
+            let last_changed = fields
+                .iter()
+                .filter_map(|field| timestamp_date(&field["changed"]))
+                .max()
+                .or(created);
+
+            if let Some((due, rotation)) = due_date(entry, last_changed) {
+                items.push(ExpiryItem {
+                    entry_name: entry_name.clone(),
+                    field: None,
+                    due,
+                    rotation,
+                });
+            }
+
+            for (i, field) in fields.iter().enumerate() {
+                let changed = timestamp_date(&field["changed"]).or(created);
+
+                if let Some((due, rotation)) = due_date(field, changed) {
+                    items.push(ExpiryItem {
+                        entry_name: entry_name.clone(),
+                        field: Some((i + 1, String::from(field["type"].as_str().unwrap()))),
+                        due,
+                        rotation,
+                    });
+                }
+            }
+        }
+
+        items.retain(|item| item.due <= warn_until);
+        items.sort_by_key(|item| item.due);
+
+        items
+    }
+
+    fn print_items(&self, items: &[ExpiryItem]) {
+        if items.is_empty() {
+            return;
+        }
+
+        let title = "Expiring credentials:".cyan();
+        println!("{}", title);
+
+        for item in items {
+            let days = (item.due - self.today).num_days();
+
+            // overdue items are shown in red, ones coming up in yellow
+            if days < 0 {
+                let what = if item.rotation {
+                    "was due for rotation"
+                } else {
+                    "expired"
+                };
+                let overdue = format!(
+                    "! {} {} {} days ago ({})",
+                    item.label(),
+                    what,
+                    -days,
+                    item.due
+                )
+                .red();
+                println!("{}", overdue);
+            } else {
+                let what = if item.rotation {
+                    "needs rotating"
+                } else {
+                    "expires"
+                };
+                let when = match days {
+                    0 => String::from("today"),
+                    1 => String::from("tomorrow"),
+                    _ => format!("in {} days", days),
+                };
+                let expiring =
+                    format!("- {} {} {} ({})", item.label(), what, when, item.due).yellow();
+                println!("{}", expiring);
+            }
+        }
+    }
+
+    fn prompt_expiry(
+        &self,
+        target: &mut serde_json::Value,
+        label: &str,
+    ) -> Result<(), VaultLocked> {
+        let mut choices = vec!["Set expiry date", "Set rotation interval"];
+        if !target["expires"].is_null() {
+            choices.push("Clear expiry date");
+        }
+        if !target["rotation_days"].is_null() {
+            choices.push("Clear rotation interval");
+        }
+        choices.push("Back");
+
+        let q_action = Question::select("expiry_action")
+            .message(match expiry_summary(target) {
+                Some(summary) => format!("{} ({}):", label, summary),
+                None => format!("{}:", label),
+            })
+            .choices(choices)
+            .build();
+
+        let answer = prompt_one(q_action)?;
+        let action = answer.as_list_item().unwrap().text.clone();
+
+        match action.as_str() {
+            "Set expiry date" => {
+                let mut q_expires = Question::input("expires")
+                    .message("Expiry date (YYYY-MM-DD):")
+                    .validate(|value, _| validate_field_value("Date", value));
+                if let Some(expires) = target["expires"].as_str() {
+                    q_expires = q_expires.default(String::from(expires));
+                }
+
+                let answer = prompt_one(q_expires.build())?;
+                target["expires"] =
+                    serde_json::Value::String(String::from(answer.as_string().unwrap()));
+            }
+            "Set rotation interval" => {
+                let q_rotation = Question::int("rotation_days")
+                    .message("Rotate every how many days?")
+                    .default(target["rotation_days"].as_i64().unwrap_or(90))
+                    .validate(|value, _| {
+                        if value > 0 {
+                            Ok(())
+                        } else {
+                            Err(String::from("The interval has to be at least one day."))
+                        }
+                    })
+                    .build();
+
+                let answer = prompt_one(q_rotation)?;
+                target["rotation_days"] = serde_json::Value::from(answer.as_int().unwrap());
+            }
+            "Clear expiry date" => {
+                target["expires"] = serde_json::Value::Null;
+            }
+            "Clear rotation interval" => {
+                target["rotation_days"] = serde_json::Value::Null;
+            }
+            _ => (),
+        }
+
+        // cleared settings are left out of the vault rather than stored as null
+        if let Some(target) = target.as_object_mut() {
+            target.retain(|name, value| {
+                !((name == "expires" || name == "rotation_days") && value.is_null())
+            });
+        }
+
+        Ok(())
+    }
+}
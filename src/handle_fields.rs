@@ -0,0 +1,499 @@
+// question stuff
+use requestty::Question;
+
+// password stuff
+use secrecy::{ExposeSecret, Secret};
+
+// other stuff
+use chrono::NaiveDate;
+
+// my stuff
+use crate::errors::AppError;
+use crate::handle_autolock::{prompt_one, VaultLocked};
+use crate::handle_editor::{EditText, EditorHandler};
+use crate::handle_generator::{GeneratePassword, GeneratorSettings, PasswordGenerator};
+use crate::handle_passphrase::{GeneratePassphrase, PassphraseGenerator};
+use crate::handle_policies::{PasswordPolicy, PolicyHandler, ProcessPolicies};
+use crate::handle_strength::{EstimateStrength, StrengthEstimator};
+
+// ------------------ //
+
+pub const FIELD_TYPES: [&str; 11] = [
+    "Username",
+    "Password",
+    "Security Question",
+    "Other",
+    "Multi-line",
+    "Email",
+    "URL",
+    "Phone",
+    "Date",
+    "Number",
+    "PIN",
+];
+
+// usernames and passwords hold a single value, everything else is a pair of
+// question/answer or name/value
+pub fn field_part_count(field_type: &str) -> usize {
+    if field_type == "Username" || field_type == "Password" {
+        1
+    } else {
+        2
+    }
+}
+
+// the values of these fields are masked when an entry is viewed, until the user
+// asks to reveal them
+const CONCEALED_FIELD_TYPES: [&str; 5] = [
+    "Password",
+    "Security Question",
+    "Other",
+    "Multi-line",
+    "PIN",
+];
+
+pub fn is_concealed(field_type: &str) -> bool {
+    CONCEALED_FIELD_TYPES.contains(&field_type)
+}
+
+// the host part of a url, with or without a scheme in front of it
+pub fn url_host(url: &str) -> Option<String> {
+    let without_scheme = match url.find("://") {
+        Some(i) => &url[i + 3..],
+        None => url,
+    };
+
+    let authority = without_scheme.split(['/', '?', '#']).next().unwrap_or("");
+    let host_and_port = authority.rsplit('@').next().unwrap_or("");
+
+    // an ipv6 address is written in brackets since it has colons of its own, it is
+    // given back without them in its usual form
+    if let Some(bracketed) = host_and_port.strip_prefix('[') {
+        let (address, port) = bracketed.split_once(']')?;
+        if !port.is_empty() && !port.starts_with(':') {
+            return None;
+        }
+        return address
+            .parse::<std::net::Ipv6Addr>()
+            .ok()
+            .map(|address| address.to_string());
+    }
+
+    let host = host_and_port
+        .split(':')
+        .next()
+        .unwrap_or("")
+        .trim_end_matches('.')
+        .to_lowercase();
+
+    let valid = !host.is_empty()
+        && (host.contains('.') || host == "localhost")
+        && host
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.');
+
+    if valid {
+        Some(host)
+    } else {
+        None
+    }
+}
+
+// checks the value of a typed field, the message is shown next to the prompt
+pub fn validate_field_value(field_type: &str, value: &str) -> Result<(), String> {
+    match field_type {
+        "Email" => {
+            let valid = match value.split_once('@') {
+                Some((local, domain)) => {
+                    !local.is_empty()
+                        && !domain.contains('@')
+                        && domain.contains('.')
+                        && !domain.starts_with('.')
+                        && !domain.ends_with('.')
+                        && !value.contains(char::is_whitespace)
+                }
+                None => false,
+            };
+
+            if valid {
+                Ok(())
+            } else {
+                Err(String::from(
+                    "Enter an email address like name@example.com.",
+                ))
+            }
+        }
+        "URL" => match url_host(value) {
+            Some(_) if !value.contains(char::is_whitespace) => Ok(()),
+            _ => Err(String::from("Enter a URL like https://example.com.")),
+        },
+        "Phone" => {
+            let digits = value.chars().filter(|c| c.is_ascii_digit()).count();
+            let valid_chars = value
+                .chars()
+                .all(|c| c.is_ascii_digit() || " +-().".contains(c));
+
+            if valid_chars && (7..=15).contains(&digits) {
+                Ok(())
+            } else {
+                Err(String::from("Enter a phone number with 7 to 15 digits."))
+            }
+        }
+        "Date" => match NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+            Ok(_) => Ok(()),
+            Err(_) => Err(String::from("Enter a date as YYYY-MM-DD.")),
+        },
+        "Number" => match value.trim().parse::<f64>() {
+            Ok(number) if number.is_finite() => Ok(()),
+            _ => Err(String::from("Enter a number.")),
+        },
+        "PIN" => {
+            if (4..=12).contains(&value.len()) && value.chars().all(|c| c.is_ascii_digit()) {
+                Ok(())
+            } else {
+                Err(String::from("A PIN is 4 to 12 digits."))
+            }
+        }
+        _ => Ok(()),
+    }
+}
+
+// how the value of a typed field is shown when an entry is viewed
+pub fn format_field_value(field_type: &str, value: &str) -> String {
+    match field_type {
+        "Date" => match NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+            Ok(date) => date.format("%-d %B %Y").to_string(),
+            Err(_) => String::from(value),
+        },
+        _ => String::from(value),
+    }
+}
+
+// a hint about the format a typed field expects, shown when asking for it
+fn field_value_hint(field_type: &str) -> &'static str {
+    match field_type {
+        "Date" => " (YYYY-MM-DD)",
+        "PIN" => " (4 to 12 digits)",
+        _ => "",
+    }
+}
+
+// custom fields carry their own name, templates already know the name so we only
+// ask for it when there isn't one
+fn prompt_field_name(
+    field_name: Option<&str>,
+    label: &str,
+    current: Option<&[Secret<String>]>,
+) -> Result<Secret<String>, VaultLocked> {
+    match field_name {
+        Some(name) => Ok(Secret::new(String::from(name))),
+        None => {
+            let mut q_field_data_name =
+                Question::input("field_data_name").message(format!("Enter name for {}:", label));
+            if let Some(current) = current {
+                q_field_data_name = q_field_data_name.default(current[0].expose_secret().clone());
+            }
+
+            let answer = prompt_one(q_field_data_name.build())?;
+            Ok(Secret::new(String::from(answer.as_string().unwrap())))
+        }
+    }
+}
+
+pub trait PromptField {
+    fn new() -> Self;
+    fn set_policy(&mut self, policy: Option<PasswordPolicy>);
+    fn prompt_field_type(&self, label: &str) -> Result<String, VaultLocked>;
+    fn prompt_field_data(
+        &self,
+        field_type: &str,
+        field_name: Option<&str>,
+        label: &str,
+        current: Option<&[Secret<String>]>,
+    ) -> Result<Vec<Secret<String>>, AppError>;
+}
+
+pub struct FieldPrompter {
+    // the password policy of the entry being filled in, if it has one
+    policy: Option<PasswordPolicy>,
+}
+
+impl FieldPrompter {
+    // passwords and passphrases can be generated instead of made up, if the user
+    // doesn't want a generated one they type it in as usual
+    fn prompt_password(
+        &self,
+        field_name: Option<&str>,
+        label: &str,
+    ) -> Result<Secret<String>, VaultLocked> {
+        let policy_handler = PolicyHandler::new();
+
+        let q_source = Question::select("password_source")
+            .message(match field_name {
+                Some(name) => format!("{}:", name),
+                None => format!("Password for {}:", label),
+            })
+            .choices(vec![
+                "Type it in",
+                "Generate a password",
+                "Generate a passphrase",
+            ])
+            .build();
+
+        let answer = prompt_one(q_source)?;
+        let generated = match answer.as_list_item().unwrap().index {
+            1 => {
+                // generated passwords follow the entry's policy from the start
+                let settings = match &self.policy {
+                    Some(policy) => policy_handler.generator_settings(policy),
+                    None => GeneratorSettings::default_settings(),
+                };
+                PasswordGenerator::new().prompt_generated(settings)?
+            }
+            2 => PassphraseGenerator::new().prompt_generated()?,
+            _ => None,
+        };
+
+        if let Some(password) = generated {
+            return Ok(password);
+        }
+
+        let estimator = StrengthEstimator::new();
+        estimator.prompt_password(&match field_name {
+            Some(name) => format!("Enter {}:", name),
+            None => format!("Enter password for {}:", label),
+        })
+    }
+}
+
+impl PromptField for FieldPrompter {
+    fn new() -> Self {
+        FieldPrompter { policy: None }
+    }
+
+    fn set_policy(&mut self, policy: Option<PasswordPolicy>) {
+        self.policy = policy;
+    }
+
+    fn prompt_field_type(&self, label: &str) -> Result<String, VaultLocked> {
+        let q_field_type = Question::select("field_type")
+            .message(format!("What type is {}?", label))
+            .choices(FIELD_TYPES.to_vec())
+            .build();
+
+        let answer = prompt_one(q_field_type)?;
+        Ok(answer.as_list_item().unwrap().text.clone())
+    }
+
+    fn prompt_field_data(
+        &self,
+        field_type: &str,
+        field_name: Option<&str>,
+        label: &str,
+        current: Option<&[Secret<String>]>,
+    ) -> Result<Vec<Secret<String>>, AppError> {
+        if field_type == "Password" {
+            // a password that breaks the entry's policy can still be kept, but only
+            // after saying so
+            loop {
+                let password = self.prompt_password(field_name, label)?;
+
+                let estimator = StrengthEstimator::new();
+                estimator.print_report(&estimator.estimate(password.expose_secret()));
+
+                let problems = match &self.policy {
+                    Some(policy) => {
+                        PolicyHandler::new().check_password(policy, password.expose_secret())
+                    }
+                    None => Vec::new(),
+                };
+                if problems.is_empty() {
+                    return Ok(vec![password]);
+                }
+
+                println!("That password doesn't meet the entry's password policy:");
+                for problem in problems {
+                    println!("- {}", problem);
+                }
+
+                let q_keep = Question::confirm("keep")
+                    .message("Use it anyway?")
+                    .default(false)
+                    .build();
+
+                let answer = prompt_one(q_keep)?;
+                if answer.as_bool().unwrap() {
+                    return Ok(vec![password]);
+                }
+            }
+        }
+
+        // if field is username, field data is just one secret string
+        if field_type == "Username" {
+            let q_field_data = Question::password("field_data")
+                .message(match field_name {
+                    Some(name) => format!("Enter {}:", name),
+                    None => format!("Enter username for {}:", label),
+                })
+                .mask('*')
+                .build();
+
+            let answer = prompt_one(q_field_data)?;
+            let field_data_str = Secret::new(String::from(answer.as_string().unwrap()));
+
+            Ok(vec![field_data_str])
+        } else if field_type == "Security Question" {
+            // the first part of the field data is the question, the second part is the answer
+            let q_field_data_question = Question::input("field_data_question")
+                .message(format!("Enter question for {}:", label))
+                .build();
+
+            let answer = prompt_one(q_field_data_question)?;
+            let field_data_question = Secret::new(String::from(answer.as_string().unwrap()));
+
+            let q_field_data_answer = Question::password("field_data_answer")
+                .message(format!("Enter answer for {}:", label))
+                .mask('*')
+                .build();
+
+            let answer = prompt_one(q_field_data_answer)?;
+            let field_data_answer = Secret::new(String::from(answer.as_string().unwrap()));
+
+            Ok(vec![field_data_question, field_data_answer])
+        } else if field_type == "Multi-line" {
+            // multi-line text can't be typed into a prompt, so it is written in the
+            // user's editor instead, starting from the current text when editing
+            let field_data_name = prompt_field_name(field_name, label, current)?;
+
+            let current_text = current
+                .and_then(|current| current.get(1))
+                .map(|text| text.expose_secret().clone())
+                .unwrap_or_default();
+
+            let editor_handler = EditorHandler::new();
+            let field_data_data = match editor_handler.edit(&current_text) {
+                Ok(text) => text,
+                Err(e) if e.is_locked() => return Err(e),
+                Err(e) => {
+                    println!("{}", e);
+                    Secret::new(current_text)
+                }
+            };
+
+            Ok(vec![field_data_name, field_data_data])
+        } else {
+            // the "other" field and the typed fields contain the name of the custom
+            // field and the data
+            let field_data_name = prompt_field_name(field_name, label, current)?;
+
+            let message = match field_name {
+                Some(name) => format!("Enter {}{}:", name, field_value_hint(field_type)),
+                None => format!("Enter data for {}{}:", label, field_value_hint(field_type)),
+            };
+            let field_type = String::from(field_type);
+
+            // secret values are masked while typing, the rest can be seen and
+            // start from their current value when editing
+            let q_field_data_data = if is_concealed(&field_type) {
+                Question::password("field_data_data")
+                    .message(message)
+                    .mask('*')
+                    .validate(move |value, _| validate_field_value(&field_type, value))
+                    .build()
+            } else {
+                let mut q_field_data_data = Question::input("field_data_data")
+                    .message(message)
+                    .validate(move |value, _| validate_field_value(&field_type, value));
+                if let Some(current) = current.and_then(|current| current.get(1)) {
+                    q_field_data_data = q_field_data_data.default(current.expose_secret().clone());
+                }
+                q_field_data_data.build()
+            };
+
+            let answer = prompt_one(q_field_data_data)?;
+            let field_data_data = Secret::new(String::from(answer.as_string().unwrap()));
+
+            Ok(vec![field_data_name, field_data_data])
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn url_host_drops_the_scheme_path_port_and_userinfo() {
+        assert_eq!(
+            url_host("https://Mail.Example.com/inbox?x=1#top").as_deref(),
+            Some("mail.example.com")
+        );
+        assert_eq!(
+            url_host("example.com:8443/login").as_deref(),
+            Some("example.com")
+        );
+        assert_eq!(
+            url_host("ftp://user:p@ss@files.example.org:21").as_deref(),
+            Some("files.example.org")
+        );
+        assert_eq!(
+            url_host("http://localhost:3000").as_deref(),
+            Some("localhost")
+        );
+        assert_eq!(url_host("example.com.").as_deref(), Some("example.com"));
+    }
+
+    #[test]
+    fn url_host_takes_ip_addresses() {
+        assert_eq!(
+            url_host("http://192.168.0.1:80").as_deref(),
+            Some("192.168.0.1")
+        );
+        assert_eq!(url_host("http://[::1]:8080").as_deref(), Some("::1"));
+        assert_eq!(
+            url_host("https://admin@[2001:DB8::0001]/status").as_deref(),
+            Some("2001:db8::1")
+        );
+        assert_eq!(url_host("http://[::1]x"), None);
+        assert_eq!(url_host("http://[not-an-address]"), None);
+    }
+
+    #[test]
+    fn url_host_rejects_things_that_arent_hosts() {
+        assert_eq!(url_host(""), None);
+        assert_eq!(url_host("https://"), None);
+        assert_eq!(url_host("just words"), None);
+        assert_eq!(url_host("https://exa_mple.com"), None);
+    }
+
+    #[test]
+    fn validates_urls() {
+        assert!(validate_field_value("URL", "https://example.com:8443/path").is_ok());
+        assert!(validate_field_value("URL", "https://user@example.com").is_ok());
+        assert!(validate_field_value("URL", "http://[::1]:8080").is_ok());
+        assert!(validate_field_value("URL", "https://example.com/a b").is_err());
+        assert!(validate_field_value("URL", "example").is_err());
+    }
+
+    #[test]
+    fn validates_the_other_typed_fields() {
+        assert!(validate_field_value("Email", "name@example.com").is_ok());
+        assert!(validate_field_value("Email", "name@example").is_err());
+        assert!(validate_field_value("Email", "a@b@example.com").is_err());
+
+        assert!(validate_field_value("Phone", "+1 (555) 010-9999").is_ok());
+        assert!(validate_field_value("Phone", "555-01").is_err());
+
+        assert!(validate_field_value("Date", "2026-02-28").is_ok());
+        assert!(validate_field_value("Date", "2026-02-30").is_err());
+
+        assert!(validate_field_value("Number", " 4.5 ").is_ok());
+        assert!(validate_field_value("Number", "inf").is_err());
+
+        assert!(validate_field_value("PIN", "0042").is_ok());
+        assert!(validate_field_value("PIN", "123").is_err());
+        assert!(validate_field_value("PIN", "12a4").is_err());
+
+        assert!(validate_field_value("Other", "anything at all").is_ok());
+    }
+}
@@ -0,0 +1,66 @@
+// file stuff
+use std::fs::{self, File};
+use std::io::prelude::*;
+use std::io::{self, BufWriter};
+use std::path::{Path, PathBuf};
+
+// ------------------ //
+
+// where a file is written before it takes the place of the real one
+pub fn temp_path(path: &Path) -> PathBuf {
+    let mut temp_path = path.to_path_buf().into_os_string();
+    temp_path.push(".tmp");
+    PathBuf::from(temp_path)
+}
+
+// renames only reach the disk once the folder they happen in is synced
+fn sync_dir(path: &Path) -> io::Result<()> {
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => File::open(parent)?.sync_all(),
+        _ => Ok(()),
+    }
+}
+
+// writes the new contents of a file next to it and makes sure they are on disk, the
+// real file is left alone until the staged one is committed
+pub fn write_staged(
+    path: &Path,
+    write: impl FnOnce(&mut dyn Write) -> io::Result<()>,
+) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(temp_path(path))?);
+    write(&mut writer)?;
+
+    let file = writer.into_inner().map_err(|e| e.into_error())?;
+    file.sync_all()
+}
+
+// moves staged files into place, ones that already were are skipped so a commit
+// that was cut short can simply be run again
+pub fn commit_staged(paths: &[PathBuf]) -> io::Result<()> {
+    for path in paths {
+        let temp_path = temp_path(path);
+        if temp_path.exists() {
+            fs::rename(&temp_path, path)?;
+            sync_dir(path)?;
+        }
+    }
+
+    Ok(())
+}
+
+// throws away staged files that are never going to be committed
+pub fn discard_staged(paths: &[PathBuf]) {
+    for path in paths {
+        let _ = fs::remove_file(temp_path(path));
+    }
+}
+
+// replaces a file so that a crash at any point leaves either all of the old
+// contents or all of the new ones, never a mix or an empty file
+pub fn write_atomic(
+    path: &Path,
+    write: impl FnOnce(&mut dyn Write) -> io::Result<()>,
+) -> io::Result<()> {
+    write_staged(path, write)?;
+    commit_staged(&[path.to_path_buf()])
+}
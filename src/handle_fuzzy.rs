@@ -0,0 +1,186 @@
+// how many candidates are offered when a name doesn't match exactly
+const MAX_MATCHES: usize = 10;
+
+pub trait FuzzyMatch {
+    fn new() -> Self;
+    fn score(&self, query: &str, candidate: &str) -> Option<i64>;
+    fn rank(&self, query: &str, candidates: &[&str]) -> Vec<usize>;
+}
+
+pub struct FuzzyMatcher {}
+
+fn is_word_start(chars: &[char], i: usize) -> bool {
+    i == 0 || matches!(chars[i - 1], ' ' | '-' | '_' | '.' | '/' | '@')
+}
+
+// scores the query as a subsequence of the candidate, runs of consecutive letters
+// and letters at the start of words count for more, gaps count against
+fn subsequence_score(query: &[char], candidate: &[char]) -> Option<i64> {
+    let mut score = 0;
+    let mut last_match: Option<usize> = None;
+    let mut position = 0;
+
+    for q in query {
+        let found = (position..candidate.len()).find(|i| candidate[*i] == *q)?;
+
+        score += 10;
+        if last_match.is_some_and(|last| last + 1 == found) {
+            score += 15;
+        }
+        if is_word_start(candidate, found) {
+            score += 10;
+        }
+        score -= (found - last_match.map(|last| last + 1).unwrap_or(0)).min(10) as i64;
+
+        last_match = Some(found);
+        position = found + 1;
+    }
+
+    Some(score)
+}
+
+// the number of single character edits between two strings, adjacent letters
+// that were swapped count as one edit
+fn edit_distance(a: &[char], b: &[char]) -> usize {
+    let mut rows = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in rows.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    rows[0] = (0..=b.len()).collect();
+
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+            rows[i][j] = (rows[i - 1][j] + 1)
+                .min(rows[i][j - 1] + 1)
+                .min(rows[i - 1][j - 1] + cost);
+
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                rows[i][j] = rows[i][j].min(rows[i - 2][j - 2] + 1);
+            }
+        }
+    }
+
+    rows[a.len()][b.len()]
+}
+
+impl FuzzyMatch for FuzzyMatcher {
+    fn new() -> Self {
+        FuzzyMatcher {}
+    }
+
+    // higher is better, exact names come first, then names starting with the
+    // query, then names containing it, then names that have its letters in order,
+    // and last names that are a typo or two away from it
+    fn score(&self, query: &str, candidate: &str) -> Option<i64> {
+        let query = query.trim().to_lowercase();
+        let candidate = candidate.to_lowercase();
+
+        if query.is_empty() {
+            return Some(0);
+        }
+
+        let length_difference = candidate.chars().count().abs_diff(query.chars().count()) as i64;
+
+        if candidate == query {
+            return Some(1000);
+        }
+        if candidate.starts_with(&query) {
+            return Some(900 - length_difference.min(99));
+        }
+        if let Some(position) = candidate.find(&query) {
+            return Some(800 - (position as i64 + length_difference).min(99));
+        }
+
+        let query_chars = query.chars().collect::<Vec<char>>();
+        let candidate_chars = candidate.chars().collect::<Vec<char>>();
+
+        if let Some(score) = subsequence_score(&query_chars, &candidate_chars) {
+            return Some(500 + score.clamp(-99, 299));
+        }
+
+        // short queries would match nearly everything with a typo allowed
+        if query_chars.len() < 3 {
+            return None;
+        }
+
+        // the query might be a mistyped version of the whole name or of its start
+        let allowed = 1 + query_chars.len() / 5;
+        let prefix_length = candidate_chars.len().min(query_chars.len());
+        let distance = edit_distance(&query_chars, &candidate_chars)
+            .min(edit_distance(&query_chars, &candidate_chars[..prefix_length]) + 1);
+
+        if distance <= allowed {
+            Some(300 - 50 * distance as i64)
+        } else {
+            None
+        }
+    }
+
+    // indices of the candidates that match the query, best first
+    fn rank(&self, query: &str, candidates: &[&str]) -> Vec<usize> {
+        let mut matches = candidates
+            .iter()
+            .enumerate()
+            .filter_map(|(i, candidate)| self.score(query, candidate).map(|score| (i, score)))
+            .collect::<Vec<(usize, i64)>>();
+
+        matches.sort_by_key(|(i, score)| (std::cmp::Reverse(*score), candidates[*i].len()));
+        matches.truncate(MAX_MATCHES);
+
+        matches.into_iter().map(|(i, _)| i).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ranked<'a>(query: &str, candidates: &[&'a str]) -> Vec<&'a str> {
+        FuzzyMatcher::new()
+            .rank(query, candidates)
+            .into_iter()
+            .map(|i| candidates[i])
+            .collect()
+    }
+
+    #[test]
+    fn ranks_exact_then_prefix_then_contained_then_typos() {
+        let candidates = ["My GitHub", "Gtihub", "Bank", "GitHub Enterprise", "github"];
+
+        assert_eq!(
+            ranked("GitHub", &candidates),
+            vec!["github", "GitHub Enterprise", "My GitHub", "Gtihub"]
+        );
+    }
+
+    #[test]
+    fn letters_in_order_come_before_typos() {
+        let candidates = ["Gmial", "Google Mail"];
+
+        assert_eq!(ranked("gmail", &candidates), vec!["Google Mail", "Gmial"]);
+    }
+
+    #[test]
+    fn short_queries_allow_no_typos() {
+        let matcher = FuzzyMatcher::new();
+
+        assert!(matcher.score("ab", "ba").is_none());
+        assert!(matcher.score("abc", "bac").is_some());
+    }
+
+    #[test]
+    fn closer_lengths_rank_higher_and_the_list_is_capped() {
+        let candidates = ["Mail two", "Mail", "Mail 1"];
+        assert_eq!(
+            ranked("mail", &candidates),
+            vec!["Mail", "Mail 1", "Mail two"]
+        );
+
+        let candidates = vec!["entry"; MAX_MATCHES + 5];
+        assert_eq!(
+            FuzzyMatcher::new().rank("entry", &candidates).len(),
+            MAX_MATCHES
+        );
+    }
+}
@@ -0,0 +1,390 @@
+// question stuff
+use requestty::Question;
+
+// password stuff
+use rand::rngs::OsRng;
+use rand::seq::SliceRandom;
+use secrecy::{ExposeSecret, Secret};
+
+// other stuff
+use colored::Colorize;
+
+// my stuff
+use crate::errors::AppError;
+use crate::handle_autolock::{prompt_one, VaultLocked};
+
+// ------------------ //
+
+const LOWERCASE: &str = "abcdefghijklmnopqrstuvwxyz";
+const UPPERCASE: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+const DIGITS: &str = "0123456789";
+pub const SYMBOLS: &str = "!@#$%^&*()-_=+[]{};:,.<>/?~";
+
+// characters that are easy to mix up when reading a password off a screen
+const AMBIGUOUS: &str = "Il1|O0o";
+
+const MIN_LENGTH: i64 = 4;
+const MAX_LENGTH: i64 = 128;
+
+pub struct GeneratorSettings {
+    pub length: usize,
+    pub exclude_ambiguous: bool,
+    // the minimum number of characters from each class, a class that is turned off
+    // has no minimum, in the order lowercase, uppercase, digits, symbols
+    pub classes: [(bool, usize); 4],
+    pub symbols: String,
+    // text the password must not contain, ignoring case
+    pub forbidden: Vec<String>,
+}
+
+impl GeneratorSettings {
+    pub fn default_settings() -> Self {
+        GeneratorSettings {
+            length: 20,
+            exclude_ambiguous: false,
+            classes: [(true, 1), (true, 1), (true, 1), (true, 1)],
+            symbols: String::from(SYMBOLS),
+            forbidden: Vec::new(),
+        }
+    }
+}
+
+pub trait GeneratePassword {
+    fn new() -> Self;
+    fn generate(&self, settings: &GeneratorSettings) -> Result<Secret<String>, AppError>;
+    fn prompt_settings(
+        &self,
+        current: &GeneratorSettings,
+    ) -> Result<GeneratorSettings, VaultLocked>;
+    fn prompt_generated(
+        &self,
+        settings: GeneratorSettings,
+    ) -> Result<Option<Secret<String>>, VaultLocked>;
+}
+
+pub struct PasswordGenerator {}
+
+pub const CLASS_NAMES: [&str; 4] = [
+    "lowercase letters",
+    "uppercase letters",
+    "digits",
+    "symbols",
+];
+
+// how many times to try for a password without any of the forbidden text in it
+const MAX_ATTEMPTS: usize = 1000;
+
+fn class_characters(class: usize, settings: &GeneratorSettings) -> Vec<char> {
+    let characters = [LOWERCASE, UPPERCASE, DIGITS, &settings.symbols][class];
+
+    characters
+        .chars()
+        .filter(|c| !settings.exclude_ambiguous || !AMBIGUOUS.contains(*c))
+        .collect()
+}
+
+impl GeneratePassword for PasswordGenerator {
+    fn new() -> Self {
+        PasswordGenerator {}
+    }
+
+    // picks the minimum from each class first, fills the rest from every enabled
+    // class and then shuffles, everything comes from the operating system's CSPRNG
+    fn generate(&self, settings: &GeneratorSettings) -> Result<Secret<String>, AppError> {
+        let enabled = (0..CLASS_NAMES.len())
+            .filter(|class| settings.classes[*class].0)
+            .collect::<Vec<usize>>();
+
+        if enabled.is_empty() {
+            return Err(AppError::new("At least one kind of character is needed."));
+        }
+
+        let minimum = enabled
+            .iter()
+            .map(|class| settings.classes[*class].1)
+            .sum::<usize>();
+        if minimum > settings.length {
+            return Err(AppError::new(
+                "The minimum counts add up to more than the length.",
+            ));
+        }
+
+        let classes = enabled
+            .iter()
+            .map(|class| (*class, class_characters(*class, settings)))
+            .collect::<Vec<(usize, Vec<char>)>>();
+        if classes.iter().any(|(_, characters)| characters.is_empty()) {
+            return Err(AppError::new(
+                "One of the kinds of characters has none left to use.",
+            ));
+        }
+
+        let all_characters = classes
+            .iter()
+            .flat_map(|(_, characters)| characters.iter().copied())
+            .collect::<Vec<char>>();
+        let mut rng = OsRng;
+
+        for _ in 0..MAX_ATTEMPTS {
+            let mut password: Vec<char> = Vec::new();
+
+            for (class, characters) in &classes {
+                for _ in 0..settings.classes[*class].1 {
+                    password.push(*characters.choose(&mut rng).unwrap());
+                }
+            }
+
+            while password.len() < settings.length {
+                password.push(*all_characters.choose(&mut rng).unwrap());
+            }
+            password.shuffle(&mut rng);
+
+            let password = password.into_iter().collect::<String>();
+            let lowercase = password.to_lowercase();
+            if !settings
+                .forbidden
+                .iter()
+                .any(|forbidden| lowercase.contains(&forbidden.to_lowercase()))
+            {
+                return Ok(Secret::new(password));
+            }
+        }
+
+        Err(AppError::new(
+            "Couldn't generate a password without the forbidden text.",
+        ))
+    }
+
+    fn prompt_settings(
+        &self,
+        current: &GeneratorSettings,
+    ) -> Result<GeneratorSettings, VaultLocked> {
+        let q_length = Question::int("length")
+            .message("Password length:")
+            .default(current.length as i64)
+            .validate(|length, _| {
+                if (MIN_LENGTH..=MAX_LENGTH).contains(&length) {
+                    Ok(())
+                } else {
+                    Err(format!(
+                        "The length has to be between {} and {}.",
+                        MIN_LENGTH, MAX_LENGTH
+                    ))
+                }
+            })
+            .build();
+
+        let answer = prompt_one(q_length)?;
+        let length = answer.as_int().unwrap() as usize;
+
+        let q_classes = Question::multi_select("classes")
+            .message("Which kinds of characters should it use?")
+            .choices_with_default(
+                CLASS_NAMES
+                    .iter()
+                    .enumerate()
+                    .map(|(i, name)| (*name, current.classes[i].0))
+                    .collect::<Vec<(&str, bool)>>(),
+            )
+            .build();
+
+        let answer = prompt_one(q_classes)?;
+        let selected = answer
+            .as_list_items()
+            .unwrap()
+            .iter()
+            .map(|item| item.index)
+            .collect::<Vec<usize>>();
+
+        let mut classes = [(false, 0); 4];
+        for class in selected {
+            let q_minimum = Question::int("minimum")
+                .message(format!("At least how many {}?", CLASS_NAMES[class]))
+                .default(current.classes[class].1 as i64)
+                .validate(move |minimum, _| {
+                    if (0..=length as i64).contains(&minimum) {
+                        Ok(())
+                    } else {
+                        Err(format!("Enter a number from 0 to {}.", length))
+                    }
+                })
+                .build();
+
+            let answer = prompt_one(q_minimum)?;
+            classes[class] = (true, answer.as_int().unwrap() as usize);
+        }
+
+        let q_ambiguous = Question::confirm("ambiguous")
+            .message(format!("Leave out look-alike characters ({})?", AMBIGUOUS))
+            .default(current.exclude_ambiguous)
+            .build();
+
+        let answer = prompt_one(q_ambiguous)?;
+
+        Ok(GeneratorSettings {
+            length,
+            exclude_ambiguous: answer.as_bool().unwrap(),
+            classes,
+            symbols: current.symbols.clone(),
+            forbidden: current.forbidden.clone(),
+        })
+    }
+
+    // shows generated passwords until one is accepted, or nothing if the user would
+    // rather type one in
+    fn prompt_generated(
+        &self,
+        mut settings: GeneratorSettings,
+    ) -> Result<Option<Secret<String>>, VaultLocked> {
+        let mut password = None;
+
+        loop {
+            if password.is_none() {
+                match self.generate(&settings) {
+                    Ok(generated) => password = Some(generated),
+                    Err(e) => {
+                        println!("{}", e);
+                        settings = self.prompt_settings(&settings)?;
+                        continue;
+                    }
+                }
+            }
+
+            let generated = password.as_ref().unwrap();
+            let preview = "Generated password:".cyan();
+            println!("{} {}", preview, generated.expose_secret());
+
+            let q_action = Question::select("generated_action")
+                .message("Use this password?")
+                .choices(vec![
+                    "Use it",
+                    "Generate another",
+                    "Change settings",
+                    "Type one in instead",
+                ])
+                .build();
+
+            let answer = prompt_one(q_action)?;
+            match answer.as_list_item().unwrap().index {
+                0 => return Ok(password),
+                1 => password = None,
+                2 => {
+                    settings = self.prompt_settings(&settings)?;
+                    password = None;
+                }
+                _ => return Ok(None),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn count_in(password: &str, characters: &str) -> usize {
+        password.chars().filter(|c| characters.contains(*c)).count()
+    }
+
+    #[test]
+    fn meets_the_minimum_of_each_class() {
+        let generator = PasswordGenerator::new();
+        let settings = GeneratorSettings {
+            length: 12,
+            classes: [(true, 2), (true, 3), (true, 4), (true, 3)],
+            ..GeneratorSettings::default_settings()
+        };
+
+        for _ in 0..50 {
+            let password = generator.generate(&settings).unwrap();
+            let password = password.expose_secret();
+
+            assert_eq!(password.chars().count(), 12);
+            assert_eq!(count_in(password, LOWERCASE), 2);
+            assert_eq!(count_in(password, UPPERCASE), 3);
+            assert_eq!(count_in(password, DIGITS), 4);
+            assert_eq!(count_in(password, SYMBOLS), 3);
+        }
+    }
+
+    #[test]
+    fn leaves_out_classes_that_are_turned_off() {
+        let generator = PasswordGenerator::new();
+        let settings = GeneratorSettings {
+            classes: [(true, 1), (false, 0), (true, 1), (false, 0)],
+            ..GeneratorSettings::default_settings()
+        };
+
+        let password = generator.generate(&settings).unwrap();
+        let password = password.expose_secret();
+        assert_eq!(
+            count_in(password, UPPERCASE) + count_in(password, SYMBOLS),
+            0
+        );
+    }
+
+    #[test]
+    fn leaves_out_ambiguous_characters() {
+        let generator = PasswordGenerator::new();
+        let settings = GeneratorSettings {
+            length: MAX_LENGTH as usize,
+            exclude_ambiguous: true,
+            symbols: String::from("|!"),
+            ..GeneratorSettings::default_settings()
+        };
+
+        for _ in 0..20 {
+            let password = generator.generate(&settings).unwrap();
+            assert_eq!(count_in(password.expose_secret(), AMBIGUOUS), 0);
+        }
+    }
+
+    #[test]
+    fn never_contains_forbidden_text() {
+        let generator = PasswordGenerator::new();
+        let settings = GeneratorSettings {
+            length: 8,
+            classes: [(true, 0), (false, 0), (false, 0), (false, 0)],
+            forbidden: vec![String::from("A"), String::from("e")],
+            ..GeneratorSettings::default_settings()
+        };
+
+        for _ in 0..50 {
+            let password = generator.generate(&settings).unwrap();
+            assert_eq!(count_in(password.expose_secret(), "ae"), 0);
+        }
+
+        // when every password would have it there is nothing to give back
+        let settings = GeneratorSettings {
+            forbidden: LOWERCASE.chars().map(String::from).collect(),
+            ..settings
+        };
+        assert!(generator.generate(&settings).is_err());
+    }
+
+    #[test]
+    fn rejects_settings_that_cant_be_met() {
+        let generator = PasswordGenerator::new();
+
+        let no_classes = GeneratorSettings {
+            classes: [(false, 0); 4],
+            ..GeneratorSettings::default_settings()
+        };
+        assert!(generator.generate(&no_classes).is_err());
+
+        let too_short = GeneratorSettings {
+            length: 5,
+            classes: [(true, 2), (true, 2), (true, 2), (false, 0)],
+            ..GeneratorSettings::default_settings()
+        };
+        assert!(generator.generate(&too_short).is_err());
+
+        let only_ambiguous = GeneratorSettings {
+            exclude_ambiguous: true,
+            classes: [(true, 1), (false, 0), (false, 0), (true, 1)],
+            symbols: String::from("|"),
+            ..GeneratorSettings::default_settings()
+        };
+        assert!(generator.generate(&only_ambiguous).is_err());
+    }
+}
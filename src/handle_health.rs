@@ -0,0 +1,503 @@
+// question stuff
+use requestty::Question;
+
+// password stuff
+use secrecy::{ExposeSecret, Secret};
+use std::collections::HashMap;
+
+// other stuff
+use chrono::prelude::*;
+use colored::Colorize;
+
+// my stuff
+use crate::handle_autolock::{prompt_one, VaultLocked};
+use crate::handle_domains::registrable_domain;
+use crate::handle_fields::url_host;
+use crate::handle_strength::{EstimateStrength, StrengthEstimator, SCORE_NAMES};
+
+// ------------------ //
+
+// the checks made on every entry, their description and how much failing them
+// takes off the entry's score out of 100
+pub const HEALTH_CHECKS: [(&str, &str, usize); 6] = [
+    ("reused", "Reused passwords", 40),
+    ("weak", "Weak passwords", 30),
+    ("old", "Old passwords", 15),
+    ("duplicate", "Duplicate entries", 10),
+    ("no_username", "Missing usernames", 5),
+    ("no_url", "Missing URLs", 5),
+];
+
+// what the checks need to know about an entry, taken from its decrypted fields
+pub struct HealthEntry {
+    pub name: String,
+    // every password along with when it was last changed
+    pub passwords: Vec<(Secret<String>, Option<DateTime<FixedOffset>>)>,
+    pub usernames: Vec<String>,
+    pub urls: Vec<String>,
+}
+
+pub struct HealthIssue {
+    pub check: &'static str,
+    pub entry_name: String,
+    pub detail: String,
+}
+
+pub struct HealthReport {
+    pub entries: usize,
+    pub passwords: usize,
+    pub score: usize,
+    pub issues: Vec<HealthIssue>,
+}
+
+pub trait CheckHealth {
+    fn new(max_age_days: i64) -> Self;
+    fn check(&self, entries: &[HealthEntry]) -> HealthReport;
+    fn print_summary(&self, report: &HealthReport);
+    fn print_issues(&self, report: &HealthReport, check: &str);
+    fn review(&self, report: &HealthReport) -> Result<(), VaultLocked>;
+    fn report_json(&self, report: &HealthReport) -> serde_json::Value;
+}
+
+pub struct HealthChecker {
+    // passwords last changed longer ago than this are old, 0 turns the check off
+    max_age_days: i64,
+    now: DateTime<Local>,
+}
+
+fn issue_count(report: &HealthReport, check: &str) -> usize {
+    report
+        .issues
+        .iter()
+        .filter(|issue| issue.check == check)
+        .count()
+}
+
+impl HealthChecker {
+    // entries sharing a password with another entry, along with the others using it
+    fn reused_passwords(&self, entries: &[HealthEntry], issues: &mut Vec<HealthIssue>) {
+        let mut users: HashMap<&str, Vec<&str>> = HashMap::new();
+
+        for entry in entries {
+            for (password, _) in &entry.passwords {
+                let names = users.entry(password.expose_secret().as_str()).or_default();
+                if !names.contains(&entry.name.as_str()) {
+                    names.push(&entry.name);
+                }
+            }
+        }
+
+        for entry in entries {
+            let mut others: Vec<&str> = Vec::new();
+            for (password, _) in &entry.passwords {
+                for name in &users[password.expose_secret().as_str()] {
+                    if *name != entry.name && !others.contains(name) {
+                        others.push(name);
+                    }
+                }
+            }
+
+            if !others.is_empty() {
+                issues.push(HealthIssue {
+                    check: "reused",
+                    entry_name: entry.name.clone(),
+                    detail: format!("Same password as {}", others.join(", ")),
+                });
+            }
+        }
+    }
+
+    // entries with the same username on the same site are most likely the same
+    // account saved twice
+    fn duplicate_entries(&self, entries: &[HealthEntry], issues: &mut Vec<HealthIssue>) {
+        let mut accounts: HashMap<(String, String), Vec<&str>> = HashMap::new();
+
+        for entry in entries {
+            for username in &entry.usernames {
+                for url in &entry.urls {
+                    if let Some(host) = url_host(url) {
+                        let names = accounts
+                            .entry((username.to_lowercase(), registrable_domain(&host)))
+                            .or_default();
+                        if !names.contains(&entry.name.as_str()) {
+                            names.push(&entry.name);
+                        }
+                    }
+                }
+            }
+        }
+
+        for entry in entries {
+            let mut others: Vec<&str> = Vec::new();
+            for names in accounts.values() {
+                if names.contains(&entry.name.as_str()) {
+                    for name in names {
+                        if *name != entry.name && !others.contains(name) {
+                            others.push(name);
+                        }
+                    }
+                }
+            }
+
+            if !others.is_empty() {
+                others.sort();
+                issues.push(HealthIssue {
+                    check: "duplicate",
+                    entry_name: entry.name.clone(),
+                    detail: format!("Same account as {}", others.join(", ")),
+                });
+            }
+        }
+    }
+}
+
+impl CheckHealth for HealthChecker {
+    fn new(max_age_days: i64) -> Self {
+        HealthChecker {
+            max_age_days,
+            now: Local::now(),
+        }
+    }
+
+    fn check(&self, entries: &[HealthEntry]) -> HealthReport {
+        let estimator = StrengthEstimator::new();
+        let mut issues: Vec<HealthIssue> = Vec::new();
+
+        self.reused_passwords(entries, &mut issues);
+
+        for entry in entries {
+            for (password, changed) in &entry.passwords {
+                let strength = estimator.estimate(password.expose_secret());
+                if strength.score < 3 {
+                    issues.push(HealthIssue {
+                        check: "weak",
+                        entry_name: entry.name.clone(),
+                        detail: format!(
+                            "{}, about 10^{:.0} guesses",
+                            SCORE_NAMES[strength.score], strength.guesses
+                        ),
+                    });
+                }
+
+                let age = changed.map(|changed| (self.now.fixed_offset() - changed).num_days());
+                if let Some(age) = age {
+                    if self.max_age_days > 0 && age > self.max_age_days {
+                        issues.push(HealthIssue {
+                            check: "old",
+                            entry_name: entry.name.clone(),
+                            detail: format!("Last changed {} days ago", age),
+                        });
+                    }
+                }
+            }
+        }
+
+        self.duplicate_entries(entries, &mut issues);
+
+        // a missing username or url only matters for entries used to log in
+        for entry in entries.iter().filter(|entry| !entry.passwords.is_empty()) {
+            if entry.usernames.is_empty() {
+                issues.push(HealthIssue {
+                    check: "no_username",
+                    entry_name: entry.name.clone(),
+                    detail: String::from("Has a password but no username or email"),
+                });
+            }
+            if entry.urls.is_empty() {
+                issues.push(HealthIssue {
+                    check: "no_url",
+                    entry_name: entry.name.clone(),
+                    detail: String::from("Has a password but no URL"),
+                });
+            }
+        }
+
+        // every login starts at 100 and loses points for each kind of problem it
+        // has, the vault's score is the average
+        let logins = entries
+            .iter()
+            .filter(|entry| !entry.passwords.is_empty())
+            .collect::<Vec<&HealthEntry>>();
+        let score = if logins.is_empty() {
+            100
+        } else {
+            let total = logins
+                .iter()
+                .map(|entry| {
+                    let penalty = HEALTH_CHECKS
+                        .iter()
+                        .filter(|(check, _, _)| {
+                            issues.iter().any(|issue| {
+                                issue.check == *check && issue.entry_name == entry.name
+                            })
+                        })
+                        .map(|(_, _, penalty)| penalty)
+                        .sum::<usize>();
+                    100usize.saturating_sub(penalty)
+                })
+                .sum::<usize>();
+            (total as f64 / logins.len() as f64).round() as usize
+        };
+
+        HealthReport {
+            entries: entries.len(),
+            passwords: entries.iter().map(|entry| entry.passwords.len()).sum(),
+            score,
+            issues,
+        }
+    }
+
+    fn print_summary(&self, report: &HealthReport) {
+        let score = format!("{}/100", report.score);
+        let score = match report.score {
+            80.. => score.green(),
+            50.. => score.yellow(),
+            _ => score.red(),
+        };
+
+        let health_title = "Vault health:".cyan();
+        println!("{} {}", health_title, score);
+        println!(
+            "{} entries, {} passwords checked",
+            report.entries, report.passwords
+        );
+
+        for (check, description, _) in HEALTH_CHECKS {
+            let count = issue_count(report, check);
+            if count > 0 {
+                println!("{} {}: {}", "-".cyan(), description, count);
+            }
+        }
+        if report.issues.is_empty() {
+            let no_issues = "No problems found.".cyan();
+            println!("{}", no_issues);
+        }
+        println!();
+    }
+
+    fn print_issues(&self, report: &HealthReport, check: &str) {
+        let description = HEALTH_CHECKS
+            .iter()
+            .find(|(name, _, _)| *name == check)
+            .map(|(_, description, _)| *description)
+            .unwrap();
+
+        let issues_title = format!("{}:", description).cyan();
+        println!("{}", issues_title);
+        for issue in report.issues.iter().filter(|issue| issue.check == check) {
+            println!("{} {}: {}", "-".cyan(), issue.entry_name, issue.detail);
+        }
+        println!();
+    }
+
+    // shows the summary, then the entries behind whichever problem is picked
+    fn review(&self, report: &HealthReport) -> Result<(), VaultLocked> {
+        self.print_summary(report);
+
+        let checks = HEALTH_CHECKS
+            .iter()
+            .filter(|(check, _, _)| issue_count(report, check) > 0)
+            .collect::<Vec<&(&str, &str, usize)>>();
+        if checks.is_empty() {
+            return Ok(());
+        }
+
+        let mut choices = checks
+            .iter()
+            .map(|(check, description, _)| {
+                format!("{} ({})", description, issue_count(report, check))
+            })
+            .collect::<Vec<String>>();
+        choices.push(String::from("Done"));
+
+        loop {
+            let q_check = Question::select("check")
+                .message("Which problems would you like to see?")
+                .choices(choices.clone())
+                .build();
+
+            let answer = prompt_one(q_check)?;
+            match checks.get(answer.as_list_item().unwrap().index) {
+                Some((check, _, _)) => self.print_issues(report, check),
+                None => break,
+            }
+        }
+
+        Ok(())
+    }
+
+    // the report as json for dashboards, it names entries but never includes their
+    // values
+    fn report_json(&self, report: &HealthReport) -> serde_json::Value {
+        let mut issues = serde_json::Map::new();
+        for (check, _, _) in HEALTH_CHECKS {
+            let entries = report
+                .issues
+                .iter()
+                .filter(|issue| issue.check == check)
+                .map(|issue| {
+                    serde_json::json!({
+                        "entry": issue.entry_name,
+                        "detail": issue.detail,
+                    })
+                })
+                .collect::<Vec<serde_json::Value>>();
+            issues.insert(String::from(check), serde_json::Value::Array(entries));
+        }
+
+        serde_json::json!({
+            "score": report.score,
+            "entries": report.entries,
+            "passwords": report.passwords,
+            "max_age_days": self.max_age_days,
+            "checked": self.now.to_rfc3339(),
+            "issues": issues,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a login whose passwords were each last changed the given number of days ago
+    fn entry(
+        name: &str,
+        passwords: &[(&str, Option<i64>)],
+        usernames: &[&str],
+        urls: &[&str],
+    ) -> HealthEntry {
+        HealthEntry {
+            name: String::from(name),
+            passwords: passwords
+                .iter()
+                .map(|(password, days_ago)| {
+                    let changed = days_ago
+                        .map(|days| (Local::now() - chrono::Duration::days(days)).fixed_offset());
+                    (Secret::new(String::from(*password)), changed)
+                })
+                .collect(),
+            usernames: usernames
+                .iter()
+                .map(|username| String::from(*username))
+                .collect(),
+            urls: urls.iter().map(|url| String::from(*url)).collect(),
+        }
+    }
+
+    fn issues<'a>(report: &'a HealthReport, check: &str) -> Vec<(&'a str, &'a str)> {
+        report
+            .issues
+            .iter()
+            .filter(|issue| issue.check == check)
+            .map(|issue| (issue.entry_name.as_str(), issue.detail.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn groups_entries_sharing_a_password() {
+        let report = HealthChecker::new(0).check(&[
+            entry("Alpha", &[("Zq8#vLm2!pTw9Rx4", None)], &["a"], &["a.com"]),
+            entry("Beta", &[("Zq8#vLm2!pTw9Rx4", None)], &["b"], &["b.com"]),
+            entry("Gamma", &[("Zq8#vLm2!pTw9Rx4", None)], &["c"], &["c.com"]),
+            entry(
+                "Delta",
+                &[("Kd4$wNq7@rYs2Hv8", None), ("Kd4$wNq7@rYs2Hv8", None)],
+                &["d"],
+                &["d.com"],
+            ),
+        ]);
+
+        assert_eq!(
+            issues(&report, "reused"),
+            vec![
+                ("Alpha", "Same password as Beta, Gamma"),
+                ("Beta", "Same password as Alpha, Gamma"),
+                ("Gamma", "Same password as Alpha, Beta"),
+            ]
+        );
+    }
+
+    #[test]
+    fn groups_the_same_account_on_the_same_site() {
+        let report = HealthChecker::new(0).check(&[
+            entry(
+                "Mail",
+                &[("Zq8#vLm2!pTw9Rx4", None)],
+                &["Alice"],
+                &["https://mail.example.com"],
+            ),
+            entry(
+                "Example",
+                &[("Kd4$wNq7@rYs2Hv8", None)],
+                &["alice"],
+                &["example.com/login"],
+            ),
+            entry(
+                "Other user",
+                &[("Jp6&tBz3%mXc5Lf9", None)],
+                &["bob"],
+                &["https://example.com"],
+            ),
+        ]);
+
+        assert_eq!(
+            issues(&report, "duplicate"),
+            vec![
+                ("Mail", "Same account as Example"),
+                ("Example", "Same account as Mail"),
+            ]
+        );
+    }
+
+    #[test]
+    fn passwords_are_old_after_the_cut_off() {
+        let entries = [
+            entry("Old", &[("Zq8#vLm2!pTw9Rx4", Some(91))], &["a"], &["a.com"]),
+            entry(
+                "Recent",
+                &[("Kd4$wNq7@rYs2Hv8", Some(89))],
+                &["b"],
+                &["b.com"],
+            ),
+            entry("Unknown", &[("Jp6&tBz3%mXc5Lf9", None)], &["c"], &["c.com"]),
+        ];
+
+        let report = HealthChecker::new(90).check(&entries);
+        assert_eq!(
+            issues(&report, "old"),
+            vec![("Old", "Last changed 91 days ago")]
+        );
+
+        // 0 turns the check off however old a password is
+        let report = HealthChecker::new(0).check(&entries);
+        assert!(issues(&report, "old").is_empty());
+    }
+
+    #[test]
+    fn the_score_is_the_average_over_logins() {
+        let report = HealthChecker::new(0).check(&[
+            entry("Clean", &[("Zq8#vLm2!pTw9Rx4", None)], &["a"], &["a.com"]),
+            // weak, no username and no url, 30 + 5 + 5 off
+            entry("Sloppy", &[("password", None)], &[], &[]),
+            // not a login, so not counted
+            entry("Note", &[], &[], &[]),
+        ]);
+
+        assert_eq!(report.entries, 3);
+        assert_eq!(report.passwords, 2);
+        assert_eq!(report.score, 80);
+        assert_eq!(issues(&report, "weak").len(), 1);
+        assert!(issues(&report, "no_username")
+            .iter()
+            .all(|(name, _)| *name == "Sloppy"));
+    }
+
+    #[test]
+    fn the_score_is_full_without_logins() {
+        let report = HealthChecker::new(0).check(&[entry("Note", &[], &[], &[])]);
+
+        assert_eq!(report.score, 100);
+        assert!(report.issues.is_empty());
+    }
+}
@@ -0,0 +1,313 @@
+// question stuff
+use requestty::Question;
+
+// other stuff
+use chrono::prelude::*;
+use colored::Colorize;
+
+// my stuff
+use crate::handle_autolock::{prompt_one, VaultLocked};
+
+// ------------------ //
+
+// wrong passwords allowed before every further attempt has to wait
+const FREE_ATTEMPTS: usize = 3;
+
+// the wait doubles with every wrong password after the free ones, up to this many
+// seconds
+const MAX_DELAY_SECONDS: i64 = 300;
+
+#[derive(Clone)]
+pub struct LockoutPolicy {
+    // wrong passwords in a row before the vault is locked or wiped, 0 for never
+    pub threshold: usize,
+    pub wipe: bool,
+    pub lockout_minutes: i64,
+}
+
+impl LockoutPolicy {
+    pub fn default_policy() -> Self {
+        LockoutPolicy {
+            threshold: 10,
+            wipe: false,
+            lockout_minutes: 60,
+        }
+    }
+
+    // settings files from before lockouts existed get the default policy
+    pub fn from_json(json: &serde_json::Value) -> Self {
+        let default = LockoutPolicy::default_policy();
+
+        LockoutPolicy {
+            threshold: json["threshold"]
+                .as_u64()
+                .map(|n| n as usize)
+                .unwrap_or(default.threshold),
+            wipe: json["wipe"].as_bool().unwrap_or(default.wipe),
+            lockout_minutes: json["lockout_minutes"]
+                .as_i64()
+                .unwrap_or(default.lockout_minutes),
+        }
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "threshold": self.threshold,
+            "wipe": self.wipe,
+            "lockout_minutes": self.lockout_minutes,
+        })
+    }
+}
+
+pub trait ThrottleUnlock {
+    fn new(policy: LockoutPolicy) -> Self;
+    fn wait_time(&self, failed_unlocks: &[String]) -> chrono::Duration;
+    fn locked_for(&self, failed_unlocks: &[String]) -> Option<chrono::Duration>;
+    fn should_wipe(&self, failed_unlocks: &[String]) -> bool;
+    fn describe_policy(&self) -> String;
+    fn prompt_policy(&self) -> Result<LockoutPolicy, VaultLocked>;
+}
+
+// works out how long the next unlock has to wait from the wrong passwords entered
+// since the last unlock, they are kept in the settings file so starting the program
+// again doesn't reset anything
+pub struct UnlockThrottle {
+    policy: LockoutPolicy,
+}
+
+// how long ago the most recent wrong password was entered
+fn since_last_failure(failed_unlocks: &[String]) -> Option<chrono::Duration> {
+    let last_failed = DateTime::parse_from_rfc3339(failed_unlocks.last()?).ok()?;
+    Some(Local::now().fixed_offset() - last_failed)
+}
+
+impl ThrottleUnlock for UnlockThrottle {
+    fn new(policy: LockoutPolicy) -> Self {
+        UnlockThrottle { policy }
+    }
+
+    // what is left of the wait after the last wrong password, nothing if the next
+    // attempt can go ahead straight away
+    fn wait_time(&self, failed_unlocks: &[String]) -> chrono::Duration {
+        if failed_unlocks.len() < FREE_ATTEMPTS {
+            return chrono::Duration::zero();
+        }
+
+        let doublings = (failed_unlocks.len() - FREE_ATTEMPTS).min(16) as u32;
+        let delay = chrono::Duration::seconds((1i64 << doublings).min(MAX_DELAY_SECONDS));
+
+        match since_last_failure(failed_unlocks) {
+            Some(elapsed) if elapsed < delay => delay - elapsed,
+            _ => chrono::Duration::zero(),
+        }
+    }
+
+    // every wrong password past the threshold locks the vault again from that
+    // moment
+    fn locked_for(&self, failed_unlocks: &[String]) -> Option<chrono::Duration> {
+        if self.policy.threshold == 0
+            || self.policy.wipe
+            || failed_unlocks.len() < self.policy.threshold
+        {
+            return None;
+        }
+
+        let lock = chrono::Duration::minutes(self.policy.lockout_minutes);
+        match since_last_failure(failed_unlocks) {
+            Some(elapsed) if elapsed < lock => Some(lock - elapsed),
+            _ => None,
+        }
+    }
+
+    fn should_wipe(&self, failed_unlocks: &[String]) -> bool {
+        self.policy.threshold > 0
+            && self.policy.wipe
+            && failed_unlocks.len() >= self.policy.threshold
+    }
+
+    fn describe_policy(&self) -> String {
+        if self.policy.threshold == 0 {
+            String::from("wrong passwords only slow down unlocking")
+        } else if self.policy.wipe {
+            format!(
+                "the vault is wiped after {} wrong passwords in a row",
+                self.policy.threshold
+            )
+        } else {
+            format!(
+                "unlocking is locked for {} minutes after {} wrong passwords in a row",
+                self.policy.lockout_minutes, self.policy.threshold
+            )
+        }
+    }
+
+    fn prompt_policy(&self) -> Result<LockoutPolicy, VaultLocked> {
+        let q_threshold = Question::int("threshold")
+            .message("Wrong passwords in a row before the vault is locked (0 for never):")
+            .default(self.policy.threshold as i64)
+            .validate(|threshold, _| {
+                if threshold == 0 || threshold > FREE_ATTEMPTS as i64 {
+                    Ok(())
+                } else {
+                    Err(format!("Enter 0 or a number above {}.", FREE_ATTEMPTS))
+                }
+            })
+            .build();
+
+        let answer = prompt_one(q_threshold)?;
+        let threshold = answer.as_int().unwrap() as usize;
+
+        if threshold == 0 {
+            return Ok(LockoutPolicy {
+                threshold,
+                ..self.policy.clone()
+            });
+        }
+
+        let q_action = Question::select("action")
+            .message("What should happen then?")
+            .choices(vec!["Lock unlocking for a while", "Wipe the vault"])
+            .default(if self.policy.wipe { 1 } else { 0 })
+            .build();
+
+        let answer = prompt_one(q_action)?;
+        if answer.as_list_item().unwrap().index == 1 {
+            let warning = "Wiping deletes the vault, its attachments and audit log for good, anyone who can run the program can set it off on purpose.".red();
+            println!("{}", warning);
+
+            let q_confirm = Question::confirm("confirm")
+                .message("Wipe the vault after that many wrong passwords?")
+                .default(false)
+                .build();
+
+            let answer = prompt_one(q_confirm)?;
+            if answer.as_bool().unwrap() {
+                return Ok(LockoutPolicy {
+                    threshold,
+                    wipe: true,
+                    lockout_minutes: self.policy.lockout_minutes,
+                });
+            }
+        }
+
+        let q_lockout_minutes = Question::int("lockout_minutes")
+            .message("Minutes to stay locked after each wrong password past that:")
+            .default(self.policy.lockout_minutes)
+            .validate(|minutes, _| {
+                if minutes >= 1 {
+                    Ok(())
+                } else {
+                    Err(String::from("Enter at least 1 minute."))
+                }
+            })
+            .build();
+
+        let answer = prompt_one(q_lockout_minutes)?;
+
+        Ok(LockoutPolicy {
+            threshold,
+            wipe: false,
+            lockout_minutes: answer.as_int().unwrap(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // wrong passwords entered this long ago, all at the same moment
+    fn failures(count: usize, seconds_ago: i64) -> Vec<String> {
+        let time = Local::now() - chrono::Duration::seconds(seconds_ago);
+        vec![time.to_rfc3339(); count]
+    }
+
+    fn throttle(threshold: usize, wipe: bool) -> UnlockThrottle {
+        UnlockThrottle::new(LockoutPolicy {
+            threshold,
+            wipe,
+            lockout_minutes: 60,
+        })
+    }
+
+    #[test]
+    fn the_first_wrong_passwords_are_free() {
+        let throttle = throttle(10, false);
+
+        assert_eq!(throttle.wait_time(&[]), chrono::Duration::zero());
+        assert_eq!(
+            throttle.wait_time(&failures(FREE_ATTEMPTS - 1, 0)),
+            chrono::Duration::zero()
+        );
+    }
+
+    #[test]
+    fn the_wait_doubles_up_to_a_limit() {
+        let throttle = throttle(0, false);
+
+        let first = throttle.wait_time(&failures(FREE_ATTEMPTS, 0));
+        let second = throttle.wait_time(&failures(FREE_ATTEMPTS + 1, 0));
+        assert!(first > chrono::Duration::zero() && first <= chrono::Duration::seconds(1));
+        assert!(second > chrono::Duration::seconds(1) && second <= chrono::Duration::seconds(2));
+
+        let longest = throttle.wait_time(&failures(40, 0));
+        assert!(longest <= chrono::Duration::seconds(MAX_DELAY_SECONDS));
+        assert!(longest > chrono::Duration::seconds(MAX_DELAY_SECONDS - 5));
+    }
+
+    #[test]
+    fn the_wait_counts_from_the_last_wrong_password() {
+        let throttle = throttle(0, false);
+
+        assert_eq!(
+            throttle.wait_time(&failures(FREE_ATTEMPTS + 2, 10)),
+            chrono::Duration::zero()
+        );
+    }
+
+    #[test]
+    fn locks_from_the_threshold_for_a_while() {
+        let throttle = throttle(5, false);
+
+        assert!(throttle.locked_for(&failures(4, 0)).is_none());
+        assert!(throttle.locked_for(&failures(5, 0)).is_some());
+        assert!(throttle.locked_for(&failures(5, 61 * 60)).is_none());
+        assert!(!throttle.should_wipe(&failures(20, 0)));
+    }
+
+    #[test]
+    fn wipes_instead_of_locking_when_set_to() {
+        let throttle = throttle(5, true);
+
+        assert!(!throttle.should_wipe(&failures(4, 0)));
+        assert!(throttle.should_wipe(&failures(5, 0)));
+        assert!(throttle.locked_for(&failures(5, 0)).is_none());
+    }
+
+    #[test]
+    fn a_threshold_of_zero_never_locks() {
+        let throttle = throttle(0, true);
+
+        assert!(throttle.locked_for(&failures(100, 0)).is_none());
+        assert!(!throttle.should_wipe(&failures(100, 0)));
+    }
+
+    #[test]
+    fn policies_round_trip_through_json() {
+        let policy = LockoutPolicy {
+            threshold: 7,
+            wipe: true,
+            lockout_minutes: 15,
+        };
+
+        let read = LockoutPolicy::from_json(&policy.to_json());
+        assert_eq!(read.threshold, 7);
+        assert!(read.wipe);
+        assert_eq!(read.lockout_minutes, 15);
+
+        let default = LockoutPolicy::from_json(&serde_json::Value::Null);
+        assert_eq!(default.threshold, 10);
+        assert!(!default.wipe);
+        assert_eq!(default.lockout_minutes, 60);
+    }
+}
@@ -1,6 +1,6 @@
+use chrono::prelude::*;
 use colored::Colorize;
 use std::process::Command;
-use chrono::prelude::*;
 
 pub trait HandleLogo {
     fn print_logo(&self);
@@ -0,0 +1,323 @@
+// question stuff
+use requestty::Question;
+
+// password stuff
+use secrecy::{ExposeSecret, Secret};
+
+// other stuff
+use colored::Colorize;
+
+// my stuff
+use crate::errors::AppError;
+use crate::handle_autolock::{prompt_one, VaultLocked};
+use crate::handle_passphrase::{GeneratePassphrase, PassphraseGenerator};
+use crate::handle_strength::{is_common_password, EstimateStrength, StrengthEstimator};
+
+// ------------------ //
+
+#[derive(Clone)]
+pub struct MasterPolicy {
+    pub min_length: usize,
+    // bits of entropy, going by the strength estimate
+    pub min_entropy: usize,
+    pub reject_common: bool,
+    pub reject_usernames: bool,
+}
+
+impl MasterPolicy {
+    pub fn default_policy() -> Self {
+        MasterPolicy {
+            min_length: 12,
+            min_entropy: 35,
+            reject_common: true,
+            reject_usernames: true,
+        }
+    }
+
+    // settings files from before the policy existed get the default one
+    pub fn from_json(json: &serde_json::Value) -> Self {
+        let default = MasterPolicy::default_policy();
+
+        MasterPolicy {
+            min_length: json["min_length"]
+                .as_u64()
+                .map(|n| n as usize)
+                .unwrap_or(default.min_length),
+            min_entropy: json["min_entropy"]
+                .as_u64()
+                .map(|n| n as usize)
+                .unwrap_or(default.min_entropy),
+            reject_common: json["reject_common"]
+                .as_bool()
+                .unwrap_or(default.reject_common),
+            reject_usernames: json["reject_usernames"]
+                .as_bool()
+                .unwrap_or(default.reject_usernames),
+        }
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "min_length": self.min_length,
+            "min_entropy": self.min_entropy,
+            "reject_common": self.reject_common,
+            "reject_usernames": self.reject_usernames,
+        })
+    }
+}
+
+pub trait CheckMasterPassword {
+    fn new(policy: MasterPolicy, skip_policy: bool) -> Self;
+    fn check_password(&self, password: &str, usernames: &[String]) -> Vec<String>;
+    fn describe_policy(&self) -> String;
+    fn prompt_policy(&self) -> Result<MasterPolicy, VaultLocked>;
+    fn prompt_new_password(&self, usernames: &[String]) -> Result<Secret<String>, AppError>;
+}
+
+pub struct MasterPasswordHandler {
+    policy: MasterPolicy,
+    // only for tests and scripted setups, set by --insecure-skip-password-policy
+    skip_policy: bool,
+}
+
+impl CheckMasterPassword for MasterPasswordHandler {
+    fn new(policy: MasterPolicy, skip_policy: bool) -> Self {
+        MasterPasswordHandler {
+            policy,
+            skip_policy,
+        }
+    }
+
+    // everything about the password that breaks the policy, nothing if it complies
+    fn check_password(&self, password: &str, usernames: &[String]) -> Vec<String> {
+        let mut problems: Vec<String> = Vec::new();
+
+        if password.chars().count() < self.policy.min_length {
+            problems.push(format!(
+                "It is shorter than {} characters.",
+                self.policy.min_length
+            ));
+        }
+
+        let estimator = StrengthEstimator::new();
+        let bits = estimator.estimate(password).guesses * std::f64::consts::LOG2_10;
+        if bits < self.policy.min_entropy as f64 {
+            problems.push(format!(
+                "It is too easy to guess, about {:.0} bits of entropy out of the {} needed.",
+                bits, self.policy.min_entropy
+            ));
+        }
+
+        if self.policy.reject_common && is_common_password(password) {
+            problems.push(String::from("It is one of the most common passwords."));
+        }
+
+        let lowercase = password.trim().to_lowercase();
+        if self.policy.reject_usernames
+            && usernames
+                .iter()
+                .any(|username| username.trim().to_lowercase() == lowercase)
+        {
+            problems.push(String::from("It is the same as a username in the vault."));
+        }
+
+        problems
+    }
+
+    fn describe_policy(&self) -> String {
+        let mut parts = vec![
+            format!("at least {} characters", self.policy.min_length),
+            format!("at least {} bits of entropy", self.policy.min_entropy),
+        ];
+
+        if self.policy.reject_common {
+            parts.push(String::from("not a common password"));
+        }
+        if self.policy.reject_usernames {
+            parts.push(String::from("not a username in the vault"));
+        }
+
+        parts.join(", ")
+    }
+
+    fn prompt_policy(&self) -> Result<MasterPolicy, VaultLocked> {
+        let q_min_length = Question::int("min_length")
+            .message("Shortest master password accepted:")
+            .default(self.policy.min_length as i64)
+            .validate(|length, _| {
+                if (1..=128).contains(&length) {
+                    Ok(())
+                } else {
+                    Err(String::from("The length has to be between 1 and 128."))
+                }
+            })
+            .build();
+
+        let answer = prompt_one(q_min_length)?;
+        let min_length = answer.as_int().unwrap() as usize;
+
+        let q_min_entropy = Question::int("min_entropy")
+            .message("Fewest bits of entropy accepted:")
+            .default(self.policy.min_entropy as i64)
+            .validate(|bits, _| {
+                if (0..=256).contains(&bits) {
+                    Ok(())
+                } else {
+                    Err(String::from("Enter a number from 0 to 256."))
+                }
+            })
+            .build();
+
+        let answer = prompt_one(q_min_entropy)?;
+        let min_entropy = answer.as_int().unwrap() as usize;
+
+        let q_reject_common = Question::confirm("reject_common")
+            .message("Reject common passwords?")
+            .default(self.policy.reject_common)
+            .build();
+
+        let answer = prompt_one(q_reject_common)?;
+        let reject_common = answer.as_bool().unwrap();
+
+        let q_reject_usernames = Question::confirm("reject_usernames")
+            .message("Reject passwords that are a username in the vault?")
+            .default(self.policy.reject_usernames)
+            .build();
+
+        let answer = prompt_one(q_reject_usernames)?;
+
+        Ok(MasterPolicy {
+            min_length,
+            min_entropy,
+            reject_common,
+            reject_usernames: answer.as_bool().unwrap(),
+        })
+    }
+
+    // asks for a master password, either typed in or a generated passphrase, that
+    // meets the policy and is typed in a second time
+    fn prompt_new_password(&self, usernames: &[String]) -> Result<Secret<String>, AppError> {
+        let q_source = Question::select("password_source")
+            .message("How would you like to set your password?")
+            .choices(vec!["Type one in", "Generate a passphrase"])
+            .build();
+
+        let answer = prompt_one(q_source)?;
+        let estimator = StrengthEstimator::new();
+        let generated = if answer.as_list_item().unwrap().index == 1 {
+            PassphraseGenerator::new().prompt_generated()?
+        } else {
+            None
+        };
+
+        let password = match generated {
+            Some(passphrase) => {
+                println!("Make sure you remember it, it can't be recovered if it is lost.");
+                passphrase
+            }
+            None => {
+                let policy = format!("Your password needs {}.", self.describe_policy()).cyan();
+                println!("{}", policy);
+
+                estimator.prompt_password("Enter a new password:")?
+            }
+        };
+
+        estimator.print_report(&estimator.estimate(password.expose_secret()));
+
+        // everything in the vault is only as safe as this password
+        let problems = self.check_password(password.expose_secret(), usernames);
+        if !problems.is_empty() {
+            println!("That password doesn't meet the master password policy:");
+            for problem in &problems {
+                println!("- {}", problem);
+            }
+
+            if !self.skip_policy {
+                return Err(AppError::new("Choose a different password."));
+            }
+            let skipped = "Using it anyway, the policy is being skipped.".cyan();
+            println!("{}", skipped);
+        }
+
+        // a generated passphrase is typed back in too, so we know it was noted down
+        let q_pass_confirm = Question::password("password_confirm")
+            .message("Confirm your password: ")
+            .mask('*')
+            .build();
+
+        let answer = prompt_one(q_pass_confirm)?;
+        if password.expose_secret() != answer.as_string().unwrap() {
+            return Err(AppError::new("Passwords do not match."));
+        }
+
+        Ok(password)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // only the checks that are turned on here can complain
+    fn handler(reject_common: bool, reject_usernames: bool) -> MasterPasswordHandler {
+        MasterPasswordHandler::new(
+            MasterPolicy {
+                min_length: 0,
+                min_entropy: 0,
+                reject_common,
+                reject_usernames,
+            },
+            false,
+        )
+    }
+
+    #[test]
+    fn a_strong_password_complies_with_the_default_policy() {
+        let handler = MasterPasswordHandler::new(MasterPolicy::default_policy(), false);
+
+        assert!(handler
+            .check_password("Zq8#vLm2!pTw9Rx4", &[String::from("alice")])
+            .is_empty());
+    }
+
+    #[test]
+    fn rejects_short_and_guessable_passwords() {
+        let handler = MasterPasswordHandler::new(MasterPolicy::default_policy(), false);
+
+        let problems = handler.check_password("xK9#", &[]);
+        assert!(problems.iter().any(|problem| problem.contains("shorter")));
+
+        let problems = handler.check_password("aaaaaaaaaaaaaaaa", &[]);
+        assert!(problems
+            .iter()
+            .any(|problem| problem.contains("easy to guess")));
+    }
+
+    #[test]
+    fn rejects_common_passwords_when_set_to() {
+        assert_eq!(
+            handler(true, false).check_password("Password", &[]),
+            vec![String::from("It is one of the most common passwords.")]
+        );
+        assert!(handler(false, false)
+            .check_password("Password", &[])
+            .is_empty());
+    }
+
+    #[test]
+    fn rejects_usernames_in_the_vault_when_set_to() {
+        let usernames = [String::from(" Alice@Example.com")];
+
+        assert_eq!(
+            handler(false, true).check_password("alice@example.com ", &usernames),
+            vec![String::from("It is the same as a username in the vault.")]
+        );
+        assert!(handler(false, true)
+            .check_password("alice@example.org", &usernames)
+            .is_empty());
+        assert!(handler(false, false)
+            .check_password("alice@example.com", &usernames)
+            .is_empty());
+    }
+}
@@ -2,10 +2,10 @@
 use requestty::Question;
 
 // file stuff
-use serde_json;
+use std::collections::HashMap;
+use std::fs::File;
 use std::io::prelude::*;
-use std::path::Path;
-use std::{fs::File, vec};
+use std::path::{Path, PathBuf};
 
 // password stuff
 use chacha20::{
@@ -17,24 +17,63 @@ use secrecy::{ExposeSecret, Secret};
 use sha2::{Digest, Sha256};
 
 // other stuff
+use chrono::prelude::*;
 use colored::Colorize;
-use hex;
 
 // my stuff
 use crate::errors::AppError;
+use crate::handle_attachments::{AttachmentHandler, ProcessAttachments, SIDECAR_THRESHOLD};
+use crate::handle_audit::{AuditLog, KeepAuditLog};
+use crate::handle_autolock::{check_locked, prompt_one, VaultLocked};
+use crate::handle_breach::{BreachChecker, CheckBreaches};
+use crate::handle_domains::{MatchUrls, UrlMatch, UrlMatcher, URL_MATCH_RULES};
+use crate::handle_expiry::{expiry_summary, CheckExpiry, ExpiryHandler};
+use crate::handle_fields::{
+    field_part_count, format_field_value, is_concealed, validate_field_value, FieldPrompter,
+    PromptField,
+};
+use crate::handle_files::{discard_staged, write_atomic, write_staged};
+use crate::handle_fuzzy::{FuzzyMatch, FuzzyMatcher};
+use crate::handle_health::{CheckHealth, HealthChecker, HealthEntry, HEALTH_CHECKS};
+use crate::handle_policies::{PolicyHandler, ProcessPolicies};
+use crate::handle_search::{is_secret_part, SearchText, TextSearcher};
+use crate::handle_templates::{EntryTemplate, ProcessTemplates, TemplateHandler};
 
 pub enum DBOperation {
     List,
     View,
+    Search,
+    FindUrl,
     Create,
+    Duplicate,
     Update,
     Delete,
+    Attachments,
+    Trash,
+    Templates,
+    Policies,
+    Health,
+    Breaches,
+    AuditLog,
+    Preferences,
+    MasterPassword,
     Exit,
 }
 
 pub struct DBHandler {
     path: String,
     json: serde_json::Value,
+    // positions of the entries by id, so looking one up doesn't mean scanning the
+    // whole vault, it has to be kept in step whenever entries are added or removed
+    index: HashMap<String, usize>,
+    // the id of each entry by name, for looking entries up by what the user types
+    names: HashMap<String, String>,
+    // changes that aren't worth a write of the whole vault on their own, like when
+    // an entry was last used, they go out with the next save or when flushed
+    unsaved: bool,
+    // the vault re-encrypted for a new master password, it only replaces the one in
+    // memory once the new password is committed
+    staged_json: Option<serde_json::Value>,
 }
 
 pub struct DBField {
@@ -43,20 +82,818 @@ pub struct DBField {
 }
 
 //todo: - extract like half of these operations into separate traits
-//todo: - also there's a lot of repeat code here, so maybe extract that into a
-//todo:   reusable trait as well
 pub trait ProcessDB {
     fn new(path: String) -> Self;
     fn start_up(&mut self) -> Result<(), AppError>;
     fn create_db(&self) -> Result<(), AppError>;
     fn load_db(&mut self) -> Result<(), AppError>;
+    fn flush_db(&mut self);
 
     fn inquire_operation(&self) -> Result<DBOperation, AppError>;
-    fn list_entries(&self) -> Result<(), AppError>;
-    fn view_entry(&self, key: &String) -> Result<(), AppError>;
-    fn create_entry(&mut self, key: &String) -> Result<(), AppError>;
-    fn update_entry(&mut self, key: &String) -> Result<(), AppError>;
-    fn delete_entry(&mut self) -> Result<(), AppError>;
+    fn list_entries(&self, sort: Option<&str>, paged: bool) -> Result<(), AppError>;
+    fn view_entry(&mut self, key: &str) -> Result<(), AppError>;
+    fn view_entry_named(&mut self, key: &str, name: &str, reveal: bool) -> Result<(), AppError>;
+    fn search_entries(&mut self, key: &str) -> Result<(), AppError>;
+    fn find_by_url(&mut self, key: &str, url: Option<&str>) -> Result<(), AppError>;
+    fn create_entry(&mut self, key: &str) -> Result<(), AppError>;
+    fn duplicate_entry(&mut self, key: &str) -> Result<(), AppError>;
+    fn update_entry(&mut self, key: &str) -> Result<(), AppError>;
+    fn delete_entry(&mut self, key: &str) -> Result<(), AppError>;
+    fn manage_attachments(&mut self, key: &str) -> Result<(), AppError>;
+    fn manage_trash(&mut self, key: &str) -> Result<(), AppError>;
+    fn manage_templates(&mut self) -> Result<(), AppError>;
+    fn manage_policies(&mut self) -> Result<(), AppError>;
+    fn manage_preferences(&mut self) -> Result<(), AppError>;
+    fn get_preference(&self, name: &str) -> i64;
+    fn list_expiring(&self, days: Option<i64>) -> Result<usize, AppError>;
+    fn list_matches(&self, query: &str) -> Result<(), AppError>;
+    fn usernames(&self, key: &str) -> Vec<String>;
+    fn health_report(&self, key: &str, json: bool) -> Result<(), AppError>;
+    fn review_health(&self, key: &str) -> Result<(), AppError>;
+    fn check_breaches(&self, key: &str, file: Option<(&str, bool)>) -> Result<(), AppError>;
+    fn stage_key_change(
+        &mut self,
+        old_key: &str,
+        new_key: &str,
+    ) -> Result<(Vec<PathBuf>, serde_json::Value), AppError>;
+    fn finish_key_change(&mut self, new_key: &str);
+    fn record_unlock(&mut self, key: &str, failed_unlocks: &[String]);
+    fn print_audit_log(
+        &self,
+        key: &str,
+        entry: Option<&str>,
+        operation: Option<&str>,
+    ) -> Result<(), AppError>;
+    fn review_audit_log(&self, key: &str) -> Result<(), AppError>;
+}
+
+// preferences that can be changed from the preferences menu, along with their
+// description and default value
+const PREFERENCES: [(&str, &str, i64); 6] = [
+    ("history_limit", "Previous values kept per field", 5),
+    (
+        "trash_days",
+        "Days to keep deleted entries (0 keeps them forever)",
+        30,
+    ),
+    (
+        "expiry_warning_days",
+        "Days before an expiry or rotation to start warning about it",
+        14,
+    ),
+    (
+        "page_size",
+        "Entries shown per page when listing (0 shows them all)",
+        50,
+    ),
+    (
+        "password_age_days",
+        "Days before a password counts as old in the health report (0 never)",
+        365,
+    ),
+    (
+        "lock_minutes",
+        "Minutes without activity before the vault locks (0 never)",
+        10,
+    ),
+];
+
+// expiry settings belong to the field rather than its value, so they stay put when
+// the value is replaced
+const EXPIRY_SETTINGS: [&str; 2] = ["expires", "rotation_days"];
+
+// ways entries can be sorted when listed, favorites always come first
+pub const SORT_ORDERS: [(&str, &str); 4] = [
+    ("created", "Created"),
+    ("name", "Name"),
+    ("used", "Recently used"),
+    ("modified", "Recently modified"),
+];
+
+// every field of an entry is encrypted with a key derived from the master key and
+// the entry name, so the same data in two entries never shares a keystream
+pub fn entry_cipher(key: &str, entry_name: &str, nonce: &[u8]) -> ChaCha20 {
+    let mut comb = Vec::new();
+    comb.extend_from_slice(key.as_bytes());
+    comb.extend_from_slice(entry_name.as_bytes());
+
+    let mut hasher = Sha256::new();
+    hasher.update(comb);
+    let hash = hasher.finalize();
+
+    let nonce = GenericArray::clone_from_slice(nonce);
+    ChaCha20::new(&hash, &nonce)
+}
+
+// decrypts data with the old key and encrypts it with the new one, the nonce can
+// stay the same since the key it is used with is different
+pub fn rekey_data(old_key: &str, new_key: &str, entry_name: &str, nonce: &[u8], data: &mut [u8]) {
+    entry_cipher(old_key, entry_name, nonce).apply_keystream(data);
+    entry_cipher(new_key, entry_name, nonce).apply_keystream(data);
+}
+
+// re-encrypts the values of a field, or of one of its previous values
+fn rekey_field(old_key: &str, new_key: &str, entry_name: &str, field: &mut serde_json::Value) {
+    let nonces = field["nonce"].as_array().unwrap().clone();
+
+    for (data, nonce) in field["data"].as_array_mut().unwrap().iter_mut().zip(nonces) {
+        let nonce = hex::decode(nonce.as_str().unwrap()).unwrap();
+        let mut bytes = hex::decode(data.as_str().unwrap()).unwrap();
+
+        rekey_data(old_key, new_key, entry_name, &nonce, &mut bytes);
+        *data = serde_json::Value::String(hex::encode(bytes));
+    }
+}
+
+// encrypts the values of a field, and its previous values, again for an entry that
+// is getting a new name, since the name is part of what they are encrypted with
+fn rename_field(key: &str, old_name: &str, new_name: &str, field: &mut serde_json::Value) {
+    let decrypted = decrypt_field(key, old_name, field);
+    let renamed = encrypt_field(key, new_name, &decrypted.field_type, &decrypted.field_data);
+    field["data"] = renamed["data"].clone();
+    field["nonce"] = renamed["nonce"].clone();
+
+    if let Some(history) = field["history"].as_array_mut() {
+        for item in history {
+            rename_field(key, old_name, new_name, item);
+        }
+    }
+}
+
+// every value gets a nonce of its own, so nothing is ever encrypted with the same
+// keystream twice
+pub fn random_nonce() -> [u8; 12] {
+    let mut nonce = [0u8; 12];
+    let mut rng = rand::thread_rng();
+    rng.fill(&mut nonce);
+    nonce
+}
+
+fn encrypt_field(
+    key: &str,
+    entry_name: &str,
+    field_type: &str,
+    field_data: &[Secret<String>],
+) -> serde_json::Value {
+    let mut field = serde_json::json!({
+        "type": field_type,
+        "data": [],
+        "nonce": [],
+        "changed": Local::now().to_rfc3339(),
+    });
+
+    for field_data_str in field_data {
+        let nonce = random_nonce();
+
+        let mut cipher = entry_cipher(key, entry_name, &nonce);
+        let mut encrypted = field_data_str.expose_secret().clone().into_bytes();
+
+        cipher.apply_keystream(&mut encrypted);
+
+        // convert the encrypted bytes to a string of the literal hex
+        let encrypted_str = hex::encode(encrypted);
+
+        field["data"]
+            .as_array_mut()
+            .unwrap()
+            .push(serde_json::Value::String(encrypted_str));
+        field["nonce"]
+            .as_array_mut()
+            .unwrap()
+            .push(serde_json::Value::String(hex::encode(nonce)));
+    }
+
+    field
+}
+
+fn decrypt_field(key: &str, entry_name: &str, field: &serde_json::Value) -> DBField {
+    let field_type = field["type"].as_str().unwrap();
+
+    let encrypted_field_data = field["data"].as_array().unwrap();
+    let encrypted_field_nonce = field["nonce"].as_array().unwrap();
+
+    let mut decrypted_field_data: Vec<Secret<String>> = Vec::new();
+
+    for (data, nonce) in encrypted_field_data.iter().zip(encrypted_field_nonce) {
+        let encrypted_field_data_bytes = hex::decode(data.as_str().unwrap()).unwrap();
+        let encrypted_field_nonce_bytes = hex::decode(nonce.as_str().unwrap()).unwrap();
+
+        let mut cipher = entry_cipher(key, entry_name, &encrypted_field_nonce_bytes);
+
+        let mut decrypted_field_data_bytes = encrypted_field_data_bytes;
+        cipher.apply_keystream(&mut decrypted_field_data_bytes);
+
+        let decrypted_field_data_str = String::from_utf8(decrypted_field_data_bytes).unwrap();
+
+        decrypted_field_data.push(Secret::new(decrypted_field_data_str));
+    }
+
+    DBField {
+        field_type: String::from(field_type),
+        field_data: decrypted_field_data,
+    }
+}
+
+// names custom fields by their own name rather than just "Other"
+fn field_label(field: &DBField) -> String {
+    if field.field_type == "Username"
+        || field.field_type == "Password"
+        || field.field_type == "Security Question"
+    {
+        field.field_type.clone()
+    } else {
+        field.field_data[0].expose_secret().clone()
+    }
+}
+
+fn print_field(number: usize, field: &DBField, reveal: bool) {
+    let number = format!("{}.", number).cyan();
+    let field_type = field.field_type.as_str();
+
+    // the length of a masked value isn't shown either
+    let masked = String::from("••••••");
+    let value = |data: &Secret<String>| {
+        if reveal || !is_concealed(field_type) {
+            data.expose_secret().clone()
+        } else {
+            masked.clone()
+        }
+    };
+
+    if field_type == "Username" || field_type == "Password" {
+        let field_data = value(&field.field_data[0]);
+        println!("{} {}: {}", number, field_type, field_data);
+    } else if field_type == "Security Question" {
+        let field_data_question = field.field_data[0].expose_secret();
+        let field_data_answer = value(&field.field_data[1]);
+        println!("{} {}: {}", number, field_type, field_data_question);
+        println!("{} {}: {}", number, "Answer".cyan(), field_data_answer);
+    } else if field_type == "Multi-line" {
+        let field_data_name = field.field_data[0].expose_secret();
+        let field_data_data = value(&field.field_data[1]);
+        println!("{} {}:", number, field_data_name);
+        for line in field_data_data.lines() {
+            println!("   {}", line);
+        }
+    } else {
+        let field_data_name = field.field_data[0].expose_secret();
+        let field_data_data = format_field_value(field_type, &value(&field.field_data[1]));
+        println!("{} {}: {}", number, field_data_name, field_data_data);
+    }
+}
+
+// the value of a field as a single line, used when showing previous values, secret
+// values are masked unless they are to be revealed
+fn field_summary(field: &DBField, reveal: bool) -> String {
+    let value = |data: &Secret<String>| {
+        if reveal || !is_concealed(&field.field_type) {
+            data.expose_secret().clone()
+        } else {
+            String::from("••••••")
+        }
+    };
+
+    if field_part_count(&field.field_type) == 1 {
+        value(&field.field_data[0])
+    } else {
+        format!(
+            "{}: {}",
+            field.field_data[0].expose_secret(),
+            value(&field.field_data[1])
+        )
+    }
+}
+
+// moves the current value of a field into the history of its replacement, keeping
+// at most `limit` previous values
+fn push_history(
+    old_field: &serde_json::Value,
+    mut new_field: serde_json::Value,
+    limit: usize,
+) -> serde_json::Value {
+    let mut history = vec![serde_json::json!({
+        "type": old_field["type"],
+        "data": old_field["data"],
+        "nonce": old_field["nonce"],
+        "replaced": Local::now().to_rfc3339(),
+    })];
+
+    if let Some(old_history) = old_field["history"].as_array() {
+        history.extend(old_history.iter().cloned());
+    }
+    history.truncate(limit);
+
+    if !history.is_empty() {
+        new_field["history"] = serde_json::Value::Array(history);
+    }
+
+    new_field["changed"] = serde_json::Value::String(Local::now().to_rfc3339());
+    for setting in EXPIRY_SETTINGS {
+        if !old_field[setting].is_null() {
+            new_field[setting] = old_field[setting].clone();
+        }
+    }
+
+    new_field
+}
+
+pub fn format_timestamp(timestamp: &str) -> String {
+    match DateTime::parse_from_rfc3339(timestamp) {
+        Ok(time) => time
+            .with_timezone(&Local)
+            .format("%Y-%m-%d %H:%M")
+            .to_string(),
+        Err(_) => String::from(timestamp),
+    }
+}
+
+fn select_field(labels: &[String], message: &str) -> Result<Option<usize>, VaultLocked> {
+    if labels.is_empty() {
+        let no_fields = "No fields.".cyan();
+        println!("{}", no_fields);
+        return Ok(None);
+    }
+
+    let q_field = Question::select("field")
+        .message(message)
+        .choices(
+            labels
+                .iter()
+                .enumerate()
+                .map(|(i, label)| format!("{} {}", format!("{}.", i + 1).cyan(), label))
+                .collect::<Vec<String>>(),
+        )
+        .build();
+
+    let answer = prompt_one(q_field)?;
+    Ok(Some(answer.as_list_item().unwrap().index))
+}
+
+impl DBHandler {
+    fn save_db(&self) {
+        // the json is written straight to a file next to the database rather than
+        // built up as one big string first, and only replaces it once it is all
+        // on disk
+        let path = Path::new(&self.path);
+        if let Err(why) = write_atomic(path, |writer| {
+            serde_json::to_writer(writer, &self.json).map_err(std::io::Error::from)
+        }) {
+            panic!("Couldn't write to database file: {}", why);
+        }
+    }
+
+    fn rebuild_index(&mut self) {
+        self.index.clear();
+        self.names.clear();
+
+        for (i, entry) in self.json["entries"].as_array().unwrap().iter().enumerate() {
+            let id = String::from(entry["id"].as_str().unwrap());
+            let name = String::from(entry["name"].as_str().unwrap());
+
+            self.index.insert(id.clone(), i);
+            self.names.insert(name, id);
+        }
+    }
+
+    // adding to the end doesn't move any other entry, so only the new one is indexed
+    fn push_entry(&mut self, mut entry: serde_json::Value) {
+        if entry["id"].is_null() {
+            entry["id"] = serde_json::Value::String(hex::encode(random_nonce()));
+        }
+
+        let id = String::from(entry["id"].as_str().unwrap());
+        let name = String::from(entry["name"].as_str().unwrap());
+        let entries = self.json["entries"].as_array_mut().unwrap();
+
+        self.index.insert(id.clone(), entries.len());
+        self.names.insert(name, id);
+        entries.push(entry);
+    }
+
+    // removing an entry moves every entry after it, so the index is rebuilt
+    fn take_entry(&mut self, index: usize) -> serde_json::Value {
+        let entry = self.json["entries"].as_array_mut().unwrap().remove(index);
+        self.rebuild_index();
+
+        entry
+    }
+
+    fn find_entry(&self, entry_name: &str) -> Option<usize> {
+        self.names
+            .get(entry_name)
+            .and_then(|id| self.index.get(id))
+            .copied()
+    }
+
+    // asks for an entry name and returns its index, printing a message if the vault
+    // is empty or the entry doesn't exist
+    fn select_entry(&self) -> Result<Option<usize>, VaultLocked> {
+        if self.json["entries"].as_array().unwrap().is_empty() {
+            let no_entries = "No entries.".cyan();
+            println!("{}", no_entries);
+            return Ok(None);
+        }
+
+        let q_entry = Question::input("entry").message("Entry name: ").build();
+
+        let answer = prompt_one(q_entry)?;
+        let entry_name = answer.as_string().unwrap();
+
+        // an exact name doesn't need picking, otherwise the closest names are offered,
+        // or every entry when nothing was typed
+        if let Some(index) = self.find_entry(entry_name) {
+            return Ok(Some(index));
+        }
+
+        let candidates = if entry_name.trim().is_empty() {
+            self.sorted_entries(&self.sort_order())
+        } else {
+            self.match_entries(entry_name)
+        };
+
+        if candidates.is_empty() {
+            let entry_not_found = "Entry not found.".cyan();
+            println!("{}", entry_not_found);
+            return Ok(None);
+        }
+
+        let entries = self.json["entries"].as_array().unwrap();
+        let mut choices = candidates
+            .iter()
+            .map(|i| String::from(entries[*i]["name"].as_str().unwrap()))
+            .collect::<Vec<String>>();
+        choices.push(String::from("Cancel"));
+
+        let q_match = Question::select("match")
+            .message("Which entry?")
+            .choices(choices)
+            .build();
+
+        let answer = prompt_one(q_match)?;
+        Ok(candidates
+            .get(answer.as_list_item().unwrap().index)
+            .copied())
+    }
+
+    // indices of the entries whose names are closest to the query, best first
+    fn match_entries(&self, query: &str) -> Vec<usize> {
+        let names = self.json["entries"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|e| e["name"].as_str().unwrap())
+            .collect::<Vec<&str>>();
+
+        FuzzyMatcher::new().rank(query, &names)
+    }
+
+    // entry names double as part of the encryption key, so they have to be unique
+    fn prompt_new_entry_name(&self, message: &str) -> Result<String, AppError> {
+        let q_name = Question::input("name").message(message).build();
+
+        let answer = prompt_one(q_name)?;
+        let name = String::from(answer.as_string().unwrap());
+
+        if name.is_empty() {
+            return Err(AppError::new("Entry name cannot be empty."));
+        }
+        if self.find_entry(&name).is_some() {
+            return Err(AppError::new("An entry with that name already exists."));
+        }
+
+        Ok(name)
+    }
+
+    // permanently removes anything that has been in the trash for longer than the
+    // configured number of days
+    fn purge_trash(&mut self, key: &str) {
+        let trash_days = self.preference("trash_days");
+
+        if trash_days == 0 {
+            return;
+        }
+
+        let trash = match self.json["trash"].as_array_mut() {
+            Some(trash) => trash,
+            None => return,
+        };
+
+        let cutoff = Local::now() - chrono::Duration::days(trash_days);
+
+        let (purged, kept): (Vec<serde_json::Value>, Vec<serde_json::Value>) =
+            trash.drain(..).partition(|e| {
+                match DateTime::parse_from_rfc3339(e["deleted"].as_str().unwrap_or("")) {
+                    Ok(deleted) => deleted <= cutoff,
+                    Err(_) => false,
+                }
+            });
+        *trash = kept;
+
+        if !purged.is_empty() {
+            for entry in &purged {
+                self.remove_entry_attachments(entry);
+            }
+            self.save_db();
+
+            let detail = format!("kept in the trash for over {} days", trash_days);
+            for entry in &purged {
+                self.log_operation(key, "purge", entry["name"].as_str(), Some(&detail));
+            }
+
+            let purged = format!("Purged {} old entries from the trash.", purged.len()).cyan();
+            eprintln!("{}", purged);
+        }
+    }
+
+    fn attachment_handler(&self) -> AttachmentHandler {
+        // large attachments live in a folder next to the database
+        let dir = match Path::new(&self.path).parent() {
+            Some(parent) => parent.join("attachments"),
+            None => PathBuf::from("attachments"),
+        };
+
+        AttachmentHandler::new(dir)
+    }
+
+    fn audit_log(&self) -> AuditLog {
+        // the log sits next to the database like the attachments folder
+        let path = match Path::new(&self.path).parent() {
+            Some(parent) => parent.join("audit.log"),
+            None => PathBuf::from("audit.log"),
+        };
+
+        AuditLog::new(path)
+    }
+
+    fn log_operation(&self, key: &str, operation: &str, entry: Option<&str>, detail: Option<&str>) {
+        self.audit_log().record(key, operation, entry, detail);
+    }
+
+    // attachments kept in the vault go with the entry, but ones kept in the sidecar
+    // folder have to be cleaned up separately when an entry is gone for good
+    fn remove_entry_attachments(&self, entry: &serde_json::Value) {
+        let attachment_handler = self.attachment_handler();
+
+        if let Some(attachments) = entry["attachments"].as_array() {
+            for attachment in attachments {
+                attachment_handler.remove_attachment(attachment);
+            }
+        }
+    }
+
+    fn sort_order(&self) -> String {
+        String::from(
+            self.json["preferences"]["sort"]
+                .as_str()
+                .unwrap_or("created"),
+        )
+    }
+
+    // indices of the entries in listing order, entries from before timestamps were
+    // recorded sort as the oldest
+    fn sorted_entries(&self, sort: &str) -> Vec<usize> {
+        let entries = self.json["entries"].as_array().unwrap();
+        let mut indices = (0..entries.len()).collect::<Vec<usize>>();
+
+        let timestamp = |i: &usize, field: &str| {
+            DateTime::parse_from_rfc3339(entries[*i][field].as_str().unwrap_or(""))
+                .map(|time| time.timestamp())
+                .unwrap_or(0)
+        };
+
+        // the keys are worked out once per entry rather than on every comparison
+        match sort {
+            "name" => {
+                indices.sort_by_cached_key(|i| entries[*i]["name"].as_str().unwrap().to_lowercase())
+            }
+            "used" => indices.sort_by_cached_key(|i| std::cmp::Reverse(timestamp(i, "used"))),
+            "modified" => {
+                indices.sort_by_cached_key(|i| std::cmp::Reverse(timestamp(i, "modified")))
+            }
+            _ => indices.sort_by_cached_key(|i| timestamp(i, "created")),
+        }
+
+        // the sort is stable, so this keeps the order within favorites and the rest
+        indices.sort_by_key(|i| !entries[*i]["favorite"].as_bool().unwrap_or(false));
+
+        indices
+    }
+
+    fn touch_entry(&mut self, index: usize, field: &str) {
+        self.json["entries"][index][field] = serde_json::Value::String(Local::now().to_rfc3339());
+    }
+
+    fn preference(&self, name: &str) -> i64 {
+        let default = PREFERENCES
+            .iter()
+            .find(|(pref, _, _)| *pref == name)
+            .map(|(_, _, default)| *default)
+            .unwrap();
+
+        self.json["preferences"][name].as_i64().unwrap_or(default)
+    }
+
+    // prints an entry with its fields, concealed values are masked unless `reveal` is
+    // set, and returns the decrypted fields for anything that follows
+    fn print_entry(&mut self, key: &str, index: usize, reveal: bool) -> Vec<DBField> {
+        let entry = &self.json["entries"][index];
+
+        // print the entry
+        let entry_title = format!("Entry: {}", entry["name"].as_str().unwrap()).cyan();
+        match entry["template"].as_str() {
+            Some(template_name) => println!("{} ({})", entry_title, template_name),
+            None => println!("{}", entry_title),
+        }
+
+        // we have to decrypt the fields before we can print them
+        let decrypted_fields = self.decrypt_entry(key, index);
+
+        // only the sort order depends on this, so it doesn't need a write of its own
+        self.touch_entry(index, "used");
+        self.unsaved = true;
+
+        let entry_name = String::from(self.json["entries"][index]["name"].as_str().unwrap());
+        let detail = if reveal {
+            Some("concealed values shown")
+        } else {
+            None
+        };
+        self.log_operation(key, "view", Some(&entry_name), detail);
+
+        // now we can print the fields
+        for (i, field) in decrypted_fields.iter().enumerate() {
+            print_field(i + 1, field, reveal);
+        }
+
+        decrypted_fields
+    }
+
+    fn decrypt_entry(&self, key: &str, index: usize) -> Vec<DBField> {
+        let entry = &self.json["entries"][index];
+        let entry_name = entry["name"].as_str().unwrap();
+
+        entry["fields"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|field| decrypt_field(key, entry_name, field))
+            .collect()
+    }
+
+    // everything the health checks look at, for every entry in the vault
+    fn health_entries(&self, key: &str) -> Vec<HealthEntry> {
+        let entries = self.json["entries"].as_array().unwrap();
+
+        (0..entries.len())
+            .map(|index| {
+                let entry = &entries[index];
+                let fields = self.decrypt_entry(key, index);
+
+                // passwords from before fields kept their own dates go by the entry's
+                let entry_changed = entry["modified"]
+                    .as_str()
+                    .or(entry["created"].as_str())
+                    .and_then(|changed| DateTime::parse_from_rfc3339(changed).ok());
+
+                let passwords = fields
+                    .iter()
+                    .zip(entry["fields"].as_array().unwrap())
+                    .filter(|(field, _)| field.field_type == "Password")
+                    .map(|(field, json)| {
+                        let changed = json["changed"]
+                            .as_str()
+                            .and_then(|changed| DateTime::parse_from_rfc3339(changed).ok())
+                            .or(entry_changed);
+                        (field.field_data[0].clone(), changed)
+                    })
+                    .collect();
+
+                let usernames = fields
+                    .iter()
+                    .filter(|field| field.field_type == "Username" || field.field_type == "Email")
+                    .map(|field| {
+                        field.field_data[field_part_count(&field.field_type) - 1]
+                            .expose_secret()
+                            .clone()
+                    })
+                    .filter(|username| !username.is_empty())
+                    .collect();
+
+                HealthEntry {
+                    name: String::from(entry["name"].as_str().unwrap()),
+                    passwords,
+                    usernames,
+                    urls: self.entry_urls(key, index),
+                }
+            })
+            .collect()
+    }
+
+    // the urls stored in an entry, in URL fields or, for entries from before those
+    // existed, in custom fields named like one
+    fn entry_urls(&self, key: &str, index: usize) -> Vec<String> {
+        self.decrypt_entry(key, index)
+            .into_iter()
+            .filter(|field| {
+                field.field_type == "URL"
+                    || (field.field_type == "Other"
+                        && ["url", "website", "login url"]
+                            .contains(&field.field_data[0].expose_secret().to_lowercase().as_str()))
+            })
+            .map(|field| field.field_data[1].expose_secret().clone())
+            .collect()
+    }
+
+    // entries with a url matching the given one, best matches first, along with how
+    // and which of their urls matched
+    fn match_url(&self, key: &str, url: &str) -> Result<Vec<(usize, UrlMatch, String)>, AppError> {
+        let matcher = match UrlMatcher::new(url) {
+            Some(matcher) => matcher,
+            None => return Err(AppError::new("That doesn't look like a URL.")),
+        };
+
+        let mut matches: Vec<(usize, UrlMatch, String)> = Vec::new();
+        for index in self.sorted_entries(&self.sort_order()) {
+            let rule = self.json["entries"][index]["url_match"]
+                .as_str()
+                .unwrap_or("domain");
+
+            let best = self
+                .entry_urls(key, index)
+                .into_iter()
+                .filter_map(|stored| matcher.match_url(rule, &stored).map(|m| (m, stored)))
+                .min_by_key(|(m, _)| *m);
+
+            if let Some((url_match, stored)) = best {
+                matches.push((index, url_match, stored));
+            }
+        }
+
+        // the sort is stable, so entries that match equally well stay in listing order
+        matches.sort_by_key(|(_, url_match, _)| *url_match);
+
+        Ok(matches)
+    }
+
+    // asks which of the vault's named policies an entry should follow, if there are
+    // any, the answer is stored on the entry as the policy's name
+    fn choose_policy(
+        &self,
+        message: &str,
+        current: &serde_json::Value,
+    ) -> Result<serde_json::Value, VaultLocked> {
+        let policy_handler = PolicyHandler::new();
+        let policies = policy_handler.load_policies(&self.json);
+
+        if policies.is_empty() {
+            return Ok(current.clone());
+        }
+
+        let mut choices = vec![String::from("None")];
+        choices.extend(policies.iter().map(|p| p.name.clone()));
+
+        let default = policies
+            .iter()
+            .position(|p| current.as_str() == Some(p.name.as_str()))
+            .map(|i| i + 1)
+            .unwrap_or(0);
+
+        let q_policy = Question::select("policy")
+            .message(message)
+            .choices(choices)
+            .default(default)
+            .build();
+
+        let answer = prompt_one(q_policy)?;
+        Ok(match answer.as_list_item().unwrap().index {
+            0 => serde_json::Value::Null,
+            i => serde_json::json!(policies[i - 1].name),
+        })
+    }
+
+    fn choose_template(&self) -> Result<Option<EntryTemplate>, VaultLocked> {
+        let template_handler = TemplateHandler::new();
+
+        let mut templates = template_handler.builtin_templates();
+        templates.extend(template_handler.load_templates(&self.json));
+
+        let mut choices = vec![String::from("Custom")];
+        choices.extend(templates.iter().map(|t| t.name.clone()));
+
+        let q_template = Question::select("template")
+            .message("Which template would you like to use?")
+            .choices(choices)
+            .build();
+
+        let answer = prompt_one(q_template)?;
+        let index = answer.as_list_item().unwrap().index;
+
+        if index == 0 {
+            Ok(None)
+        } else {
+            Ok(Some(templates.remove(index - 1)))
+        }
+    }
 }
 
 impl ProcessDB for DBHandler {
@@ -64,6 +901,10 @@ impl ProcessDB for DBHandler {
         DBHandler {
             path,
             json: serde_json::Value::Null,
+            index: HashMap::new(),
+            names: HashMap::new(),
+            unsaved: false,
+            staged_json: None,
         }
     }
 
@@ -72,7 +913,7 @@ impl ProcessDB for DBHandler {
         let path = Path::new(&self.path);
 
         if !path.exists() {
-            println!("Database file does not exist. Creating new database file...");
+            eprintln!("Database file does not exist. Creating new database file...");
             loop {
                 match self.create_db() {
                     Ok(_) => (),
@@ -104,13 +945,15 @@ impl ProcessDB for DBHandler {
 
         // create the json object
         let json = serde_json::json!({
-            "entries": []
+            "entries": [],
+            "trash": [],
+            "templates": [],
+            "preferences": {}
         });
 
         // write the json object to the file
-        match file.write_all(json.to_string().as_bytes()) {
-            Err(why) => panic!("Couldn't write to database file: {}", why),
-            Ok(_) => (),
+        if let Err(why) = file.write_all(json.to_string().as_bytes()) {
+            panic!("Couldn't write to database file: {}", why);
         }
 
         Ok(())
@@ -125,9 +968,8 @@ impl ProcessDB for DBHandler {
 
         // read the file
         let mut contents = String::new();
-        match file.read_to_string(&mut contents) {
-            Err(why) => panic!("Couldn't read database file: {}", why),
-            Ok(_) => (),
+        if let Err(why) = file.read_to_string(&mut contents) {
+            panic!("Couldn't read database file: {}", why);
         }
 
         // parse the json
@@ -138,641 +980,1968 @@ impl ProcessDB for DBHandler {
 
         // set the json
         self.json = json;
+        self.unsaved = false;
+
+        // older databases were created before entries had ids, they are given one
+        // now and written out straight away, so a run that changes nothing else
+        // doesn't hand out ids that are gone the next time
+        let mut assigned = false;
+        for entry in self.json["entries"].as_array_mut().unwrap() {
+            if entry["id"].is_null() {
+                entry["id"] = serde_json::Value::String(hex::encode(random_nonce()));
+                assigned = true;
+            }
+        }
+        if assigned {
+            self.save_db();
+        }
+        self.rebuild_index();
 
         Ok(())
     }
 
+    // writes out the changes that were left for later
+    fn flush_db(&mut self) {
+        if self.unsaved {
+            self.save_db();
+            self.unsaved = false;
+        }
+    }
+
     fn inquire_operation(&self) -> Result<DBOperation, AppError> {
         let q_operation = Question::select("operation")
             .message("What operation would you like to perform?")
-            .choices(vec!["List", "View", "Create", "Update", "Delete", "Exit"])
+            .choices(vec![
+                "List",
+                "View",
+                "Search",
+                "Find by URL",
+                "Create",
+                "Duplicate",
+                "Update",
+                "Delete",
+                "Attachments",
+                "Trash",
+                "Templates",
+                "Policies",
+                "Health report",
+                "Breach check",
+                "Audit log",
+                "Preferences",
+                "Master password",
+                "Exit",
+            ])
             .build();
 
-        let answer = requestty::prompt_one(q_operation).unwrap();
+        let answer = prompt_one(q_operation)?;
         let operation = answer.as_list_item().unwrap().text.as_str();
 
         match operation {
             "List" => Ok(DBOperation::List),
             "View" => Ok(DBOperation::View),
+            "Search" => Ok(DBOperation::Search),
+            "Find by URL" => Ok(DBOperation::FindUrl),
             "Create" => Ok(DBOperation::Create),
+            "Duplicate" => Ok(DBOperation::Duplicate),
             "Update" => Ok(DBOperation::Update),
             "Delete" => Ok(DBOperation::Delete),
+            "Attachments" => Ok(DBOperation::Attachments),
+            "Trash" => Ok(DBOperation::Trash),
+            "Templates" => Ok(DBOperation::Templates),
+            "Policies" => Ok(DBOperation::Policies),
+            "Health report" => Ok(DBOperation::Health),
+            "Breach check" => Ok(DBOperation::Breaches),
+            "Audit log" => Ok(DBOperation::AuditLog),
+            "Preferences" => Ok(DBOperation::Preferences),
+            "Master password" => Ok(DBOperation::MasterPassword),
             "Exit" => Ok(DBOperation::Exit),
             _ => Err(AppError::new("Invalid operation.")),
         }
     }
 
-    fn list_entries(&self) -> Result<(), AppError> {
-        let entries = self.json["entries"].as_array().unwrap();
+    fn list_entries(&self, sort: Option<&str>, paged: bool) -> Result<(), AppError> {
+        let entries = self.json["entries"].as_array().unwrap();
+
+        if entries.is_empty() {
+            let no_entries = "No entries.".cyan();
+            println!("{}", no_entries);
+            return Ok(());
+        }
+
+        let sort = match sort {
+            Some(sort) => String::from(sort),
+            None => self.sort_order(),
+        };
+
+        let sorted = self.sorted_entries(&sort);
+
+        // large vaults are shown a page at a time in the menu, the command line
+        // prints everything so it can be piped
+        let page_size = match self.preference("page_size") {
+            page_size if paged && page_size > 0 => page_size as usize,
+            _ => sorted.len(),
+        };
+        let num_pages = sorted.len().div_ceil(page_size);
+        let mut page = 0;
+
+        loop {
+            let start = page * page_size;
+            let end = (start + page_size).min(sorted.len());
+
+            let entries_title = if num_pages > 1 {
+                format!("Entries (page {} of {}):", page + 1, num_pages).cyan()
+            } else {
+                "Entries:".cyan()
+            };
+            println!("{}", entries_title);
+
+            let stdout = std::io::stdout();
+            let mut out = stdout.lock();
+            for (i, index) in sorted[start..end].iter().enumerate() {
+                let entry = &entries[*index];
+                let number = format!("{}.", start + i + 1).cyan();
+                let name = entry["name"].as_str().unwrap();
+
+                let _ = if entry["favorite"].as_bool().unwrap_or(false) {
+                    writeln!(out, "{} {} {}", number, name, "★".yellow())
+                } else {
+                    writeln!(out, "{} {}", number, name)
+                };
+            }
+            let _ = writeln!(out);
+            drop(out);
+
+            if num_pages <= 1 {
+                break;
+            }
+
+            let mut choices: Vec<&str> = Vec::new();
+            if page + 1 < num_pages {
+                choices.push("Next page");
+            }
+            if page > 0 {
+                choices.push("Previous page");
+            }
+            choices.push("Done");
+
+            let q_page = Question::select("page")
+                .message("More entries")
+                .choices(choices)
+                .build();
+
+            let answer = prompt_one(q_page)?;
+            match answer.as_list_item().unwrap().text.as_str() {
+                "Next page" => page += 1,
+                "Previous page" => page -= 1,
+                _ => break,
+            }
+        }
+
+        Ok(())
+    }
+
+    fn view_entry(&mut self, key: &str) -> Result<(), AppError> {
+        let index = match self.select_entry()? {
+            Some(index) => index,
+            None => return Ok(()),
+        };
+
+        let decrypted_fields = self.print_entry(key, index, false);
+
+        // fields that have been updated keep their previous values, which can be
+        // looked at or brought back from here
+        let entry_name = String::from(self.json["entries"][index]["name"].as_str().unwrap());
+        let labels = decrypted_fields
+            .iter()
+            .map(field_label)
+            .collect::<Vec<String>>();
+
+        let concealed = (0..decrypted_fields.len())
+            .filter(|i| is_concealed(&decrypted_fields[*i].field_type))
+            .collect::<Vec<usize>>();
+
+        loop {
+            let fields = self.json["entries"][index]["fields"].as_array().unwrap();
+            let with_history = (0..fields.len())
+                .filter(|i| {
+                    fields[*i]["history"]
+                        .as_array()
+                        .is_some_and(|history| !history.is_empty())
+                })
+                .collect::<Vec<usize>>();
+
+            if concealed.is_empty() && with_history.is_empty() {
+                break;
+            }
+
+            let mut choices = vec!["Done"];
+            if !concealed.is_empty() {
+                choices.push("Reveal field");
+            }
+            if !with_history.is_empty() {
+                choices.push("View field history");
+                choices.push("Restore previous value");
+            }
+
+            let q_action = Question::select("action")
+                .message("What next?")
+                .choices(choices)
+                .build();
+
+            let answer = prompt_one(q_action)?;
+            let action = answer.as_list_item().unwrap().text.clone();
+
+            if action == "Done" {
+                break;
+            }
+
+            if action == "Reveal field" {
+                let concealed_labels = concealed
+                    .iter()
+                    .map(|i| labels[*i].clone())
+                    .collect::<Vec<String>>();
+
+                if let Some(i) = select_field(&concealed_labels, "Which field?")? {
+                    print_field(concealed[i] + 1, &decrypted_fields[concealed[i]], true);
+
+                    let detail = format!("revealed {}", concealed_labels[i]);
+                    self.log_operation(key, "view", Some(&entry_name), Some(&detail));
+                }
+                continue;
+            }
+
+            let history_labels = with_history
+                .iter()
+                .map(|i| labels[*i].clone())
+                .collect::<Vec<String>>();
+            let field_index = match select_field(&history_labels, "Which field?")? {
+                Some(i) => with_history[i],
+                None => break,
+            };
+
+            let field = &fields[field_index];
+            let history = field["history"].as_array().unwrap();
+            let history_choices = |reveal: bool| {
+                history
+                    .iter()
+                    .map(|item| {
+                        let replaced = format_timestamp(item["replaced"].as_str().unwrap_or(""));
+                        let value = field_summary(&decrypt_field(key, &entry_name, item), reveal);
+                        format!("{} {}", replaced.cyan(), value)
+                    })
+                    .collect::<Vec<String>>()
+            };
+
+            if action == "View field history" {
+                let history_title = format!("Previous values of {}:", labels[field_index]).cyan();
+                println!("{}", history_title);
+                for choice in history_choices(false) {
+                    println!("{} {}", "-".cyan(), choice);
+                }
+
+                let detail = format!("history of {}", labels[field_index]);
+                self.log_operation(key, "view", Some(&entry_name), Some(&detail));
+
+                // previous secrets stay masked like current ones until asked for
+                let any_concealed = history
+                    .iter()
+                    .any(|item| is_concealed(item["type"].as_str().unwrap_or("")));
+                if !any_concealed {
+                    continue;
+                }
+
+                let q_reveal = Question::confirm("reveal")
+                    .message("Reveal the previous values?")
+                    .default(false)
+                    .build();
+
+                let answer = prompt_one(q_reveal)?;
+                if answer.as_bool().unwrap() {
+                    for choice in history_choices(true) {
+                        println!("{} {}", "-".cyan(), choice);
+                    }
+
+                    let detail = format!("revealed history of {}", labels[field_index]);
+                    self.log_operation(key, "view", Some(&entry_name), Some(&detail));
+                }
+                continue;
+            }
+
+            // the values are told apart by when they were replaced, so secret ones
+            // can stay masked here
+            let q_history = Question::select("history")
+                .message("Which value would you like to restore?")
+                .choices(history_choices(false))
+                .build();
+
+            let answer = prompt_one(q_history)?;
+            let history_index = answer.as_list_item().unwrap().index;
+
+            // the restored value takes the place of the current one, which goes into
+            // the history so the restore can itself be undone
+            let mut restored = history[history_index].clone();
+            let mut remaining = history.clone();
+            remaining.remove(history_index);
+
+            let mut current = field.clone();
+            current["history"] = serde_json::Value::Array(remaining);
+
+            if let Some(restored) = restored.as_object_mut() {
+                restored.remove("replaced");
+            }
+
+            let limit = self.preference("history_limit") as usize;
+            self.json["entries"][index]["fields"][field_index] =
+                push_history(&current, restored, limit.max(1));
+            self.touch_entry(index, "modified");
+            self.save_db();
+
+            let detail = format!("restored previous value of {}", labels[field_index]);
+            self.log_operation(key, "update", Some(&entry_name), Some(&detail));
+
+            let restored = format!("Restored previous value of {}.", labels[field_index]).cyan();
+            println!("{}", restored);
+            break;
+        }
+
+        Ok(())
+    }
+
+    fn view_entry_named(&mut self, key: &str, name: &str, reveal: bool) -> Result<(), AppError> {
+        // without a prompt to pick from, a close name is only used if it is the only one
+        let index = match self.find_entry(name) {
+            Some(index) => index,
+            None => {
+                let candidates = self.match_entries(name);
+                let entries = self.json["entries"].as_array().unwrap();
+                let names = candidates
+                    .iter()
+                    .map(|i| format!("\"{}\"", entries[*i]["name"].as_str().unwrap()))
+                    .collect::<Vec<String>>();
+
+                match candidates.len() {
+                    0 => return Err(AppError::new("Entry not found.")),
+                    1 => candidates[0],
+                    _ => {
+                        return Err(AppError::new(&format!(
+                            "Entry not found. Did you mean {}?",
+                            names.join(", ")
+                        )))
+                    }
+                }
+            }
+        };
+
+        self.print_entry(key, index, reveal);
+
+        Ok(())
+    }
+
+    fn search_entries(&mut self, key: &str) -> Result<(), AppError> {
+        if self.json["entries"].as_array().unwrap().is_empty() {
+            let no_entries = "No entries.".cyan();
+            println!("{}", no_entries);
+            return Ok(());
+        }
+
+        let q_query = Question::input("query").message("Search for:").build();
+
+        let answer = prompt_one(q_query)?;
+        let query = String::from(answer.as_string().unwrap());
+        if query.trim().is_empty() {
+            return Ok(());
+        }
+
+        let q_secrets = Question::confirm("secrets")
+            .message("Include passwords and other secret values?")
+            .default(false)
+            .build();
+
+        let answer = prompt_one(q_secrets)?;
+        let include_secrets = answer.as_bool().unwrap();
+
+        // entries are only decrypted in memory, one at a time, while searching
+        let searcher = TextSearcher::new(&query);
+        let mut found: Vec<usize> = Vec::new();
+
+        for index in self.sorted_entries(&self.sort_order()) {
+            let entry_name = self.json["entries"][index]["name"].as_str().unwrap();
+            let mut matches: Vec<(String, String)> = Vec::new();
+
+            if let Some(snippet) = searcher.snippet(entry_name) {
+                matches.push((String::from("Name"), snippet));
+            }
+
+            for field in self.decrypt_entry(key, index) {
+                let part_count = field.field_data.len();
+
+                for (part, value) in field.field_data.iter().enumerate() {
+                    if !include_secrets && is_secret_part(&field.field_type, part, part_count) {
+                        continue;
+                    }
+
+                    let label = if part + 1 == part_count {
+                        field_label(&field)
+                    } else if field.field_type == "Security Question" {
+                        String::from("Question")
+                    } else {
+                        String::from("Field name")
+                    };
+
+                    if let Some(snippet) = searcher.snippet(value.expose_secret()) {
+                        matches.push((label, snippet));
+                    }
+                }
+            }
+
+            if !matches.is_empty() {
+                let entry_title = format!("Entry: {}", entry_name).cyan();
+                println!("{}", entry_title);
+                for (label, snippet) in matches {
+                    println!("  {}: {}", label, snippet);
+                }
+
+                let detail = if include_secrets {
+                    "search result, secret values searched"
+                } else {
+                    "search result"
+                };
+                self.log_operation(key, "view", Some(entry_name), Some(detail));
+
+                found.push(index);
+            }
+        }
+
+        if found.is_empty() {
+            let no_matches = "No matches.".cyan();
+            println!("{}", no_matches);
+            return Ok(());
+        }
+
+        let entries = self.json["entries"].as_array().unwrap();
+        let mut choices = found
+            .iter()
+            .map(|i| String::from(entries[*i]["name"].as_str().unwrap()))
+            .collect::<Vec<String>>();
+        choices.push(String::from("Done"));
+
+        let q_open = Question::select("open")
+            .message("Open an entry?")
+            .choices(choices)
+            .build();
+
+        let answer = prompt_one(q_open)?;
+        if let Some(index) = found.get(answer.as_list_item().unwrap().index) {
+            self.print_entry(key, *index, false);
+        }
+
+        Ok(())
+    }
+
+    fn find_by_url(&mut self, key: &str, url: Option<&str>) -> Result<(), AppError> {
+        // the menu asks for the url and offers to open a match, from the command line
+        // the matches are only printed
+        let interactive = url.is_none();
+        let url = match url {
+            Some(url) => String::from(url),
+            None => {
+                let q_url = Question::input("url").message("URL:").build();
+
+                let answer = prompt_one(q_url)?;
+                String::from(answer.as_string().unwrap())
+            }
+        };
+
+        let matches = self.match_url(key, &url)?;
+
+        if matches.is_empty() {
+            if interactive {
+                let no_matches = "No entries match that URL.".cyan();
+                println!("{}", no_matches);
+                return Ok(());
+            }
+            return Err(AppError::new("No entries match that URL."));
+        }
+
+        let entries = self.json["entries"].as_array().unwrap();
+        let names = matches
+            .iter()
+            .map(|(index, _, _)| String::from(entries[*index]["name"].as_str().unwrap()))
+            .collect::<Vec<String>>();
+
+        for ((_, url_match, stored), name) in matches.iter().zip(&names) {
+            let how = format!("({}: {})", url_match.description(), stored).cyan();
+            println!("{} {}", name, how);
+
+            self.log_operation(key, "view", Some(name), Some("URL match"));
+        }
+
+        if !interactive {
+            return Ok(());
+        }
+
+        let mut choices = names;
+        choices.push(String::from("Done"));
+
+        let q_open = Question::select("open")
+            .message("Open an entry?")
+            .choices(choices)
+            .build();
+
+        let answer = prompt_one(q_open)?;
+        if let Some((index, _, _)) = matches.get(answer.as_list_item().unwrap().index) {
+            self.print_entry(key, *index, false);
+        }
+
+        Ok(())
+    }
+
+    fn create_entry(&mut self, key: &str) -> Result<(), AppError> {
+        let policy_handler = PolicyHandler::new();
+        let mut field_prompter = FieldPrompter::new();
+
+        // get name of whatever website or service this entry is for
+        let name = self.prompt_new_entry_name("What is the name of this entry?")?;
+
+        // passwords set for the entry are checked against its policy, and generated
+        // ones follow it
+        let policy = self.choose_policy(
+            "Which password policy does this entry follow?",
+            &serde_json::Value::Null,
+        )?;
+        field_prompter.set_policy(policy_handler.entry_policy(&self.json, &policy));
+
+        // get the fields
+        let mut field_types: Vec<String> = Vec::new();
+        let mut field_data: Vec<Vec<Secret<String>>> = Vec::new();
+
+        // templates come with their fields already laid out, otherwise we ask the
+        // user to build the entry field by field
+        let template = self.choose_template()?;
+        let template_name = template.as_ref().map(|t| t.name.clone());
+
+        match template {
+            Some(template) => {
+                for (i, template_field) in template.fields.iter().enumerate() {
+                    field_data.push(field_prompter.prompt_field_data(
+                        &template_field.field_type,
+                        template_field.field_name.as_deref(),
+                        &format!("field {}", i + 1),
+                        None,
+                    )?);
+                    field_types.push(template_field.field_type.clone());
+                }
+            }
+            None => {
+                // ask for number of fields for this entry
+                let q_num_fields = Question::int("num_fields")
+                    .message("How many fields would you like to add to this entry?")
+                    .build();
+
+                let answer = prompt_one(q_num_fields)?;
+                let num_fields = answer.as_int().unwrap();
+
+                for i in 0..num_fields {
+                    let label = format!("field {}", i + 1);
+                    let field_type = field_prompter.prompt_field_type(&label)?;
+
+                    field_data.push(field_prompter.prompt_field_data(
+                        &field_type,
+                        None,
+                        &label,
+                        None,
+                    )?);
+                    field_types.push(field_type);
+                }
+            }
+        }
+
+        // assemble json, encrypt, and write to file
+        let mut entry = serde_json::json!({
+            "name": name,
+            "fields": []
+        });
+
+        for (field_type, field_data) in field_types.iter().zip(&field_data) {
+            let field = encrypt_field(key, &name, field_type, field_data);
+            entry["fields"].as_array_mut().unwrap().push(field);
+        }
+
+        // remember what kind of entry this is, like a secure note
+        if let Some(template_name) = template_name {
+            entry["template"] = serde_json::Value::String(template_name);
+        }
+        if !policy.is_null() {
+            entry["policy"] = policy;
+        }
+
+        let now = Local::now().to_rfc3339();
+        entry["created"] = serde_json::Value::String(now.clone());
+        entry["modified"] = serde_json::Value::String(now);
+
+        self.push_entry(entry);
+        self.save_db();
+        self.log_operation(key, "create", Some(&name), None);
+
+        Ok(())
+    }
+
+    fn duplicate_entry(&mut self, key: &str) -> Result<(), AppError> {
+        let policy_handler = PolicyHandler::new();
+        let mut field_prompter = FieldPrompter::new();
+
+        let index = match self.select_entry()? {
+            Some(index) => index,
+            None => return Ok(()),
+        };
+
+        let policy = self.json["entries"][index]["policy"].clone();
+        field_prompter.set_policy(policy_handler.entry_policy(&self.json, &policy));
+
+        let new_name = self.prompt_new_entry_name("What is the name of the copy?")?;
+
+        // the copy is keyed by its own name, so every field has to be decrypted and
+        // encrypted again rather than copying the ciphertext, the copy starts out
+        // without any previous values
+        let mut decrypted_fields = self.decrypt_entry(key, index);
+
+        let labels = decrypted_fields
+            .iter()
+            .map(field_label)
+            .collect::<Vec<String>>();
+
+        let mut changed: Vec<usize> = Vec::new();
+        if !labels.is_empty() {
+            let q_change = Question::multi_select("change")
+                .message("Select any fields you want to change in the copy")
+                .choices(labels.clone())
+                .build();
+
+            let answer = prompt_one(q_change)?;
+            for item in answer.as_list_items().unwrap() {
+                changed.push(item.index);
+
+                let field = &mut decrypted_fields[item.index];
+                let label = format!("\"{}\"", labels[item.index]);
+                field.field_data = field_prompter.prompt_field_data(
+                    &field.field_type,
+                    None,
+                    &label,
+                    Some(&field.field_data),
+                )?;
+            }
+        }
+
+        let mut entry = serde_json::json!({
+            "name": new_name,
+            "fields": []
+        });
+
+        if let Some(template_name) = self.json["entries"][index]["template"].as_str() {
+            entry["template"] = serde_json::Value::String(String::from(template_name));
+        }
+
+        if !policy.is_null() {
+            entry["policy"] = policy;
+        }
+
+        let original = &self.json["entries"][index];
+        for setting in EXPIRY_SETTINGS {
+            if !original[setting].is_null() {
+                entry[setting] = original[setting].clone();
+            }
+        }
+
+        // fields that weren't changed keep counting towards their rotation from when
+        // the original value was set
+        for (i, field) in decrypted_fields.iter().enumerate() {
+            let mut copy = encrypt_field(key, &new_name, &field.field_type, &field.field_data);
+            let original_field = &original["fields"][i];
+
+            if !changed.contains(&i) && !original_field["changed"].is_null() {
+                copy["changed"] = original_field["changed"].clone();
+            }
+            for setting in EXPIRY_SETTINGS {
+                if !original_field[setting].is_null() {
+                    copy[setting] = original_field[setting].clone();
+                }
+            }
+
+            entry["fields"].as_array_mut().unwrap().push(copy);
+        }
+
+        if let Some(attachments) = self.json["entries"][index]["attachments"].as_array() {
+            let attachment_handler = self.attachment_handler();
+            let entry_name = self.json["entries"][index]["name"].as_str().unwrap();
+
+            let copies =
+                attachment_handler.copy_attachments(key, entry_name, &new_name, attachments)?;
+
+            entry["attachments"] = serde_json::Value::Array(copies);
+        }
+
+        let now = Local::now().to_rfc3339();
+        entry["created"] = serde_json::Value::String(now.clone());
+        entry["modified"] = serde_json::Value::String(now);
+
+        self.push_entry(entry);
+        self.save_db();
+
+        let original_name = self.json["entries"][index]["name"].as_str().unwrap();
+        let detail = format!("copy of \"{}\"", original_name);
+        self.log_operation(key, "create", Some(&new_name), Some(&detail));
+
+        let duplicated = format!("Entry duplicated as \"{}\".", new_name).cyan();
+        println!("{}", duplicated);
+
+        Ok(())
+    }
+
+    fn update_entry(&mut self, key: &str) -> Result<(), AppError> {
+        let policy_handler = PolicyHandler::new();
+        let mut field_prompter = FieldPrompter::new();
+
+        let index = match self.select_entry()? {
+            Some(index) => index,
+            None => return Ok(()),
+        };
+
+        let entry_name = String::from(self.json["entries"][index]["name"].as_str().unwrap());
+
+        // edits are made on a copy of the fields and only written back on save, any
+        // field that isn't edited keeps its existing ciphertext
+        let mut fields: Vec<serde_json::Value> = self.json["entries"][index]["fields"]
+            .as_array()
+            .unwrap()
+            .clone();
+        let history_limit = self.preference("history_limit") as usize;
+        let mut favorite = self.json["entries"][index]["favorite"]
+            .as_bool()
+            .unwrap_or(false);
+
+        // the entry's own expiry settings are staged the same way as its fields
+        let mut entry_expiry = serde_json::json!({});
+        for setting in EXPIRY_SETTINGS {
+            if !self.json["entries"][index][setting].is_null() {
+                entry_expiry[setting] = self.json["entries"][index][setting].clone();
+            }
+        }
+        let expiry_handler = ExpiryHandler::new(self.preference("expiry_warning_days"));
+        let mut policy = self.json["entries"][index]["policy"].clone();
+        field_prompter.set_policy(policy_handler.entry_policy(&self.json, &policy));
+
+        let mut url_match = String::from(
+            self.json["entries"][index]["url_match"]
+                .as_str()
+                .unwrap_or("domain"),
+        );
+
+        loop {
+            // print the entry
+            let mut entry_title = format!("Entry: {}", entry_name).cyan().to_string();
+            if favorite {
+                entry_title = format!("{} {}", entry_title, "★".yellow());
+            }
+            match expiry_summary(&entry_expiry) {
+                Some(summary) => println!("{} ({})", entry_title, summary),
+                None => println!("{}", entry_title),
+            }
+
+            let labels = fields
+                .iter()
+                .map(|field| field_label(&decrypt_field(key, &entry_name, field)))
+                .collect::<Vec<String>>();
+
+            if labels.is_empty() {
+                let no_fields = "No fields.".cyan();
+                println!("{}", no_fields);
+            }
+            for (i, label) in labels.iter().enumerate() {
+                let number = format!("{}.", i + 1).cyan();
+                match expiry_summary(&fields[i]) {
+                    Some(summary) => println!("{} {} ({})", number, label, summary),
+                    None => println!("{} {}", number, label),
+                }
+            }
+
+            let q_action = Question::select("action")
+                .message("What would you like to do?")
+                .choices(vec![
+                    "Edit field",
+                    "Add field",
+                    "Remove field",
+                    "Change field type",
+                    "Reorder fields",
+                    "Expiry and rotation",
+                    "URL matching",
+                    "Password policy",
+                    if favorite {
+                        "Remove from favorites"
+                    } else {
+                        "Add to favorites"
+                    },
+                    "Save",
+                    "Cancel",
+                ])
+                .build();
+
+            let answer = prompt_one(q_action)?;
+            let action = answer.as_list_item().unwrap().text.clone();
+
+            match action.as_str() {
+                "Edit field" => {
+                    let field_index =
+                        match select_field(&labels, "Which field would you like to update?")? {
+                            Some(field_index) => field_index,
+                            None => continue,
+                        };
+
+                    // get the new field data
+                    let field_type = String::from(fields[field_index]["type"].as_str().unwrap());
+                    let current = decrypt_field(key, &entry_name, &fields[field_index]);
+                    let field_data = field_prompter.prompt_field_data(
+                        &field_type,
+                        None,
+                        "the field",
+                        Some(&current.field_data),
+                    )?;
+
+                    let new_field = encrypt_field(key, &entry_name, &field_type, &field_data);
+                    fields[field_index] =
+                        push_history(&fields[field_index], new_field, history_limit);
+                }
+                "Add field" => {
+                    let label = format!("field {}", fields.len() + 1);
+                    let field_type = field_prompter.prompt_field_type(&label)?;
+                    let field_data =
+                        field_prompter.prompt_field_data(&field_type, None, &label, None)?;
+
+                    fields.push(encrypt_field(key, &entry_name, &field_type, &field_data));
+                }
+                "Remove field" => {
+                    let field_index =
+                        match select_field(&labels, "Which field would you like to remove?")? {
+                            Some(field_index) => field_index,
+                            None => continue,
+                        };
+
+                    let q_remove = Question::confirm("remove")
+                        .message(format!("Remove \"{}\"?", labels[field_index]))
+                        .build();
+
+                    let answer = prompt_one(q_remove)?;
+                    if answer.as_bool().unwrap() {
+                        fields.remove(field_index);
+                    }
+                }
+                "Change field type" => {
+                    let field_index = match select_field(
+                        &labels,
+                        "Which field would you like to change the type of?",
+                    )? {
+                        Some(field_index) => field_index,
+                        None => continue,
+                    };
+
+                    let old_type = String::from(fields[field_index]["type"].as_str().unwrap());
+                    let new_type = field_prompter.prompt_field_type("the field")?;
+
+                    // if the data has the same shape under the new type, and is still valid
+                    // for it, we only need to relabel it, otherwise the data has to be
+                    // entered again
+                    let current = decrypt_field(key, &entry_name, &fields[field_index]);
+                    let still_valid = current.field_data.last().is_some_and(|value| {
+                        validate_field_value(&new_type, value.expose_secret()).is_ok()
+                    });
+
+                    if field_part_count(&old_type) == field_part_count(&new_type) && still_valid {
+                        // history items carry their own type, so they stay readable
+                        fields[field_index]["type"] = serde_json::Value::String(new_type);
+                    } else {
+                        let field_data =
+                            field_prompter.prompt_field_data(&new_type, None, "the field", None)?;
+                        let new_field = encrypt_field(key, &entry_name, &new_type, &field_data);
+                        fields[field_index] =
+                            push_history(&fields[field_index], new_field, history_limit);
+                    }
+                }
+                "Reorder fields" => {
+                    if fields.len() < 2 {
+                        let nothing_to_reorder = "Nothing to reorder.".cyan();
+                        println!("{}", nothing_to_reorder);
+                        continue;
+                    }
+
+                    let q_order = Question::order_select("order")
+                        .message("Move the fields into the order you want")
+                        .choices(labels.clone())
+                        .build();
+
+                    let answer = prompt_one(q_order)?;
+                    fields = answer
+                        .as_list_items()
+                        .unwrap()
+                        .iter()
+                        .map(|item| fields[item.index].clone())
+                        .collect();
+                }
+                "Expiry and rotation" => {
+                    let mut targets = vec![String::from("The whole entry")];
+                    targets.extend(labels.iter().cloned());
+
+                    let target_index = match select_field(&targets, "Set expiry for what?")? {
+                        Some(target_index) => target_index,
+                        None => continue,
+                    };
+
+                    if target_index == 0 {
+                        expiry_handler.prompt_expiry(&mut entry_expiry, "The whole entry")?;
+                    } else {
+                        expiry_handler
+                            .prompt_expiry(&mut fields[target_index - 1], &targets[target_index])?;
+                    }
+                }
+                "URL matching" => {
+                    let q_rule = Question::select("url_match")
+                        .message("How should this entry's URLs be matched?")
+                        .choices(
+                            URL_MATCH_RULES
+                                .iter()
+                                .map(|(_, description)| *description)
+                                .collect::<Vec<&str>>(),
+                        )
+                        .default(
+                            URL_MATCH_RULES
+                                .iter()
+                                .position(|(rule, _)| *rule == url_match)
+                                .unwrap_or(0),
+                        )
+                        .build();
+
+                    let answer = prompt_one(q_rule)?;
+                    url_match =
+                        String::from(URL_MATCH_RULES[answer.as_list_item().unwrap().index].0);
+                }
+                "Password policy" => {
+                    if let Some(current) = policy_handler.entry_policy(&self.json, &policy) {
+                        let current_policy = format!(
+                            "Current policy: {}",
+                            policy_handler.describe_policy(&current)
+                        )
+                        .cyan();
+                        println!("{}", current_policy);
+                    }
+
+                    let q_kind = Question::select("policy_kind")
+                        .message("What policy should this entry follow?")
+                        .choices(vec![
+                            "No policy",
+                            "A named policy",
+                            "A policy just for this entry",
+                        ])
+                        .build();
+
+                    let answer = prompt_one(q_kind)?;
+                    match answer.as_list_item().unwrap().index {
+                        0 => policy = serde_json::Value::Null,
+                        1 => {
+                            if policy_handler.load_policies(&self.json).is_empty() {
+                                let no_policies =
+                                    "No named policies, add some from the Policies menu.".cyan();
+                                println!("{}", no_policies);
+                                continue;
+                            }
+                            policy = self.choose_policy("Which policy?", &policy)?;
+                        }
+                        _ => match policy_handler.prompt_policy("") {
+                            Ok(new_policy) => policy = policy_handler.policy_to_json(&new_policy),
+                            Err(e) => {
+                                println!("{}", e);
+                                continue;
+                            }
+                        },
+                    }
+
+                    field_prompter.set_policy(policy_handler.entry_policy(&self.json, &policy));
+                }
+                "Add to favorites" | "Remove from favorites" => {
+                    favorite = !favorite;
+                }
+                "Save" => {
+                    self.json["entries"][index]["fields"] = serde_json::Value::Array(fields);
+                    self.json["entries"][index]["favorite"] = serde_json::Value::Bool(favorite);
+
+                    let entry = self.json["entries"][index].as_object_mut().unwrap();
+                    for setting in EXPIRY_SETTINGS {
+                        match entry_expiry.get(setting) {
+                            Some(value) => entry.insert(String::from(setting), value.clone()),
+                            None => entry.remove(setting),
+                        };
+                    }
+
+                    if policy.is_null() {
+                        entry.remove("policy");
+                    } else {
+                        entry.insert(String::from("policy"), policy);
+                    }
+
+                    // the default rule isn't stored
+                    if url_match == "domain" {
+                        entry.remove("url_match");
+                    } else {
+                        entry.insert(String::from("url_match"), serde_json::json!(url_match));
+                    }
+                    self.touch_entry(index, "modified");
+                    self.save_db();
+                    self.log_operation(key, "update", Some(&entry_name), None);
+
+                    let saved = "Entry saved.".cyan();
+                    println!("{}", saved);
+                    break;
+                }
+                _ => {
+                    let update_cancelled = "Update cancelled.".cyan();
+                    println!("{}", update_cancelled);
+                    break;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn delete_entry(&mut self, key: &str) -> Result<(), AppError> {
+        let index = match self.select_entry()? {
+            Some(index) => index,
+            None => return Ok(()),
+        };
+
+        // print the entry
+        let entry_title = format!(
+            "Entry: {}",
+            self.json["entries"][index]["name"].as_str().unwrap()
+        )
+        .cyan();
+        println!("{}", entry_title);
+
+        // ask if they are sure they want to delete the entry
+        let q_delete = Question::confirm("delete")
+            .message("Are you sure you want to move this entry to the trash?")
+            .build();
 
-        if entries.len() == 0 {
-            let no_entries = "No entries.".cyan();
-            println!("{}", no_entries);
+        let answer = prompt_one(q_delete)?;
+        let delete = answer.as_bool().unwrap();
+
+        if !delete {
+            let delete_cancelled = "Delete cancelled.".cyan();
+            println!("{}", delete_cancelled);
             return Ok(());
         }
 
-        let entries_title = "Entries:".cyan();
-        println!("{}", entries_title);
-        for (i, entry) in entries.iter().enumerate() {
-            let number = format!("{}.", i + 1).cyan();
-            let name = entry["name"].as_str().unwrap();
-            println!("{} {}", number, name);
+        // move the entry from the entries to the trash, it keeps its name so it can
+        // still be decrypted if it is restored
+        let mut entry = self.take_entry(index);
+        entry["deleted"] = serde_json::Value::String(Local::now().to_rfc3339());
+
+        // older databases were created before the trash existed
+        if !self.json["trash"].is_array() {
+            self.json["trash"] = serde_json::json!([]);
         }
-        println!();
+
+        let entry_name = String::from(entry["name"].as_str().unwrap());
+        self.json["trash"].as_array_mut().unwrap().push(entry);
+        self.save_db();
+        self.log_operation(key, "delete", Some(&entry_name), None);
+
+        let moved = "Entry moved to the trash.".cyan();
+        println!("{}", moved);
 
         Ok(())
     }
 
-    fn view_entry(&self, key: &String) -> Result<(), AppError> {
-        let entries = self.json["entries"].as_array().unwrap();
+    fn manage_attachments(&mut self, key: &str) -> Result<(), AppError> {
+        let attachment_handler = self.attachment_handler();
 
-        if entries.len() == 0 {
-            let no_entries = "No entries.".cyan();
-            println!("{}", no_entries);
-            return Ok(());
-        }
+        let index = match self.select_entry()? {
+            Some(index) => index,
+            None => return Ok(()),
+        };
+
+        let entry_name = String::from(self.json["entries"][index]["name"].as_str().unwrap());
 
-        let q_entry = Question::input("entry")
-            .message("Entry name: ")
+        let q_action = Question::select("action")
+            .message(format!("Attachments of {}:", entry_name))
+            .choices(vec!["List", "Add", "Extract", "Remove", "Back"])
             .build();
 
-        let answer = requestty::prompt_one(q_entry).unwrap();
-        let entry_name = answer.as_string().unwrap();
+        let answer = prompt_one(q_action)?;
+        let action = answer.as_list_item().unwrap().text.clone();
 
-        // find the entry
-        let mut entry: Option<&serde_json::Value> = None;
-        for e in entries {
-            if e["name"].as_str().unwrap() == entry_name {
-                entry = Some(e);
-                break;
-            }
+        // older entries were created before attachments existed
+        if !self.json["entries"][index]["attachments"].is_array() {
+            self.json["entries"][index]["attachments"] = serde_json::json!([]);
         }
 
-        if entry.is_none() {
-            let entry_not_found = "Entry not found.".cyan();
-            println!("{}", entry_not_found);
+        let attachments = self.json["entries"][index]["attachments"]
+            .as_array()
+            .unwrap()
+            .clone();
+        let labels = attachments
+            .iter()
+            .map(|a| {
+                let name = attachment_handler.attachment_name(key, &entry_name, a);
+                let size = a["size"].as_u64().unwrap_or(0);
+                format!("{} {}", name, format!("({} bytes)", size).cyan())
+            })
+            .collect::<Vec<String>>();
+
+        if action != "Add" && action != "Back" && attachments.is_empty() {
+            let no_attachments = "No attachments.".cyan();
+            println!("{}", no_attachments);
             return Ok(());
         }
 
-        let entry = entry.unwrap();
-
-        // print the entry
-        let entry_title = format!("Entry: {}", entry["name"].as_str().unwrap()).cyan();
-        println!("{}", entry_title);
-
-        // we have to decrypt the fields before we can print them
-        let fields = entry["fields"].as_array().unwrap();
-        let mut decrypted_fields: Vec<DBField> = Vec::new();
+        match action.as_str() {
+            "List" => {
+                let attachments_title = "Attachments:".cyan();
+                println!("{}", attachments_title);
+                for (i, (label, attachment)) in labels.iter().zip(&attachments).enumerate() {
+                    let number = format!("{}.", i + 1).cyan();
+                    let added = format_timestamp(attachment["added"].as_str().unwrap_or(""));
+                    let storage = if attachment["storage"].as_str() == Some("sidecar") {
+                        "attachments folder"
+                    } else {
+                        "vault"
+                    };
+                    println!(
+                        "{} {} {}",
+                        number,
+                        label,
+                        format!("added {}, stored in {}", added, storage).cyan()
+                    );
+                }
+                println!();
+            }
+            "Add" => {
+                let q_path = Question::input("path")
+                    .message("Path of the file to attach:")
+                    .build();
 
-        for field in fields {
-            let field_type = field["type"].as_str().unwrap();
+                let answer = prompt_one(q_path)?;
+                let file_path = PathBuf::from(answer.as_string().unwrap());
+
+                let size = match std::fs::metadata(&file_path) {
+                    Ok(metadata) if metadata.is_file() => metadata.len(),
+                    _ => return Err(AppError::new("That path is not a file.")),
+                };
+
+                let q_storage = Question::select("storage")
+                    .message("Where should the encrypted file be kept?")
+                    .choices(vec![
+                        "Inside the vault",
+                        "In the attachments folder next to the vault",
+                    ])
+                    .default(if size > SIDECAR_THRESHOLD { 1 } else { 0 })
+                    .build();
 
-            let encrypted_field_data = field["data"].as_array().unwrap();
-            let encrypted_field_nonce = field["nonce"].as_array().unwrap();
+                let answer = prompt_one(q_storage)?;
+                let sidecar = answer.as_list_item().unwrap().index == 1;
 
-            let mut decrypted_field_data: Vec<Secret<String>> = Vec::new();
+                let attachment =
+                    attachment_handler.add_attachment(key, &entry_name, &file_path, sidecar)?;
 
-            for i in 0..encrypted_field_data.len() {
-                let encrypted_field_data_str = encrypted_field_data[i].as_str().unwrap();
-                let encrypted_field_nonce_str = encrypted_field_nonce[i].as_str().unwrap();
+                self.json["entries"][index]["attachments"]
+                    .as_array_mut()
+                    .unwrap()
+                    .push(attachment);
+                self.touch_entry(index, "modified");
+                self.save_db();
+                self.log_operation(key, "update", Some(&entry_name), Some("attachment added"));
 
-                let encrypted_field_data_bytes = hex::decode(encrypted_field_data_str).unwrap();
-                let encrypted_field_nonce_bytes = hex::decode(encrypted_field_nonce_str).unwrap();
+                let attached = format!("Attached {} bytes.", size).cyan();
+                println!("{}", attached);
+            }
+            "Extract" => {
+                let attachment_index = match select_field(&labels, "Which attachment?")? {
+                    Some(attachment_index) => attachment_index,
+                    None => return Ok(()),
+                };
+                let attachment = &attachments[attachment_index];
+                let name = attachment_handler.attachment_name(key, &entry_name, attachment);
+
+                let q_path = Question::input("path")
+                    .message("Where should the file be written?")
+                    .default(name)
+                    .build();
 
-                let mut comb = Vec::new();
-                comb.extend_from_slice(key.as_bytes());
-                comb.extend_from_slice(entry_name.as_bytes());
+                let answer = prompt_one(q_path)?;
+                let out_path = PathBuf::from(answer.as_string().unwrap());
 
-                let mut hasher = Sha256::new();
-                hasher.update(comb);
-                let hash = hasher.finalize();
+                if out_path.exists() {
+                    let q_overwrite = Question::confirm("overwrite")
+                        .message("That file already exists, overwrite it?")
+                        .build();
 
-                let nonce = GenericArray::clone_from_slice(&encrypted_field_nonce_bytes);
-                let mut cipher = ChaCha20::new(&hash.into(), &nonce.into());
+                    let answer = prompt_one(q_overwrite)?;
+                    if !answer.as_bool().unwrap() {
+                        return Ok(());
+                    }
+                }
 
-                let mut decrypted_field_data_bytes = encrypted_field_data_bytes.clone();
-                cipher.apply_keystream(&mut decrypted_field_data_bytes);
+                attachment_handler.extract_attachment(key, &entry_name, attachment, &out_path)?;
 
-                let decrypted_field_data_str =
-                    String::from_utf8(decrypted_field_data_bytes).unwrap();
+                let detail = format!("attachment written to {}", out_path.display());
+                self.log_operation(key, "export", Some(&entry_name), Some(&detail));
 
-                decrypted_field_data.push(Secret::new(decrypted_field_data_str));
+                let extracted = format!("Extracted to {}.", out_path.display()).cyan();
+                println!("{}", extracted);
             }
+            "Remove" => {
+                let attachment_index = match select_field(&labels, "Which attachment?")? {
+                    Some(attachment_index) => attachment_index,
+                    None => return Ok(()),
+                };
+
+                let q_remove = Question::confirm("remove")
+                    .message(format!("Remove {}?", labels[attachment_index]))
+                    .build();
 
-            decrypted_fields.push(DBField {
-                field_type: String::from(field_type),
-                field_data: decrypted_field_data,
-            });
-        }
+                let answer = prompt_one(q_remove)?;
+                if !answer.as_bool().unwrap() {
+                    return Ok(());
+                }
 
-        // now we can print the fields
-        for (i, field) in decrypted_fields.iter().enumerate() {
-            let number = format!("{}.", i + 1).cyan();
-            let field_type = field.field_type.as_str();
-
-            if field_type == "Username" || field_type == "Password" {
-                let field_data = field.field_data[0].expose_secret();
-                println!("{} {}: {}", number, field_type, field_data);
-            } else if field_type == "Security Question" {
-                let field_data_question = field.field_data[0].expose_secret();
-                let field_data_answer = field.field_data[1].expose_secret();
-                println!(
-                    "{} {}: {}",
-                    number, field_type, field_data_question
-                );
-                println!(
-                    "{} {}: {}",
-                    number, "Answer".cyan(), field_data_answer
-                );
-            } else {
-                let field_data_name = field.field_data[0].expose_secret();
-                let field_data_data = field.field_data[1].expose_secret();
-                println!(
-                    "{} {}: {}",
-                    number, field_data_name, field_data_data
-                );
+                let attachment = self.json["entries"][index]["attachments"]
+                    .as_array_mut()
+                    .unwrap()
+                    .remove(attachment_index);
+                self.touch_entry(index, "modified");
+                self.save_db();
+                self.log_operation(key, "update", Some(&entry_name), Some("attachment removed"));
+
+                // only delete the sidecar files once the vault no longer points at them
+                attachment_handler.remove_attachment(&attachment);
             }
+            _ => (),
         }
 
         Ok(())
     }
 
-    fn create_entry(&mut self, key: &String) -> Result<(), AppError> {
-        // get name of whatever website or service this entry is for
-        let q_name = Question::input("name")
-            .message("What is the name of this entry?")
-            .build();
-
-        let answer = requestty::prompt_one(q_name).unwrap();
-        let name = answer.as_string().unwrap();
+    fn manage_trash(&mut self, key: &str) -> Result<(), AppError> {
+        let trash = match self.json["trash"].as_array() {
+            Some(trash) if !trash.is_empty() => trash,
+            _ => {
+                let empty = "The trash is empty.".cyan();
+                println!("{}", empty);
+                return Ok(());
+            }
+        };
 
-        // ask for number of fields for this entry
-        let q_num_fields = Question::int("num_fields")
-            .message("How many fields would you like to add to this entry?")
+        let trash_labels = trash
+            .iter()
+            .map(|e| {
+                let deleted = format_timestamp(e["deleted"].as_str().unwrap_or(""));
+                format!(
+                    "{} {}",
+                    e["name"].as_str().unwrap(),
+                    format!("(deleted {})", deleted).cyan()
+                )
+            })
+            .collect::<Vec<String>>();
+
+        let q_action = Question::select("action")
+            .message("What would you like to do with the trash?")
+            .choices(vec!["List", "Restore", "Empty trash", "Back"])
             .build();
 
-        let answer = requestty::prompt_one(q_num_fields).unwrap();
-        let num_fields = answer.as_int().unwrap();
-
-        // get the fields
-        let mut field_types: Vec<String> = Vec::new();
-        let mut field_data: Vec<Vec<Secret<String>>> = Vec::new();
-
-        for i in 0..num_fields {
-            // get the type of the field
-            let q_field_type = Question::select(format!("field_type_{}", i))
-                .message(format!("What type is field {}?", i + 1))
-                .choices(vec!["Username", "Password", "Security Question", "Other"])
-                .build();
-
-            let answer = requestty::prompt_one(q_field_type).unwrap();
-            let field_type = answer.as_list_item().unwrap().text.as_str();
+        let answer = prompt_one(q_action)?;
+        let action = answer.as_list_item().unwrap().text.as_str();
 
-            // if field is username or password, field data is just one secret string
-            if field_type == "Username" || field_type == "Password" {
-                let q_field_data = Question::password(format!("field_data_{}", i))
-                    .message(format!("Enter data for field {}:", i + 1))
-                    .mask('*')
+        match action {
+            "List" => {
+                let trash_title = "Trash:".cyan();
+                println!("{}", trash_title);
+                for (i, label) in trash_labels.iter().enumerate() {
+                    let number = format!("{}.", i + 1).cyan();
+                    println!("{} {}", number, label);
+                }
+                println!();
+            }
+            "Restore" => {
+                let q_entry = Question::select("entry")
+                    .message("Which entry would you like to restore?")
+                    .choices(trash_labels)
                     .build();
 
-                let answer = requestty::prompt_one(q_field_data).unwrap();
-                let field_data_str = Secret::new(String::from(answer.as_string().unwrap()));
+                let answer = prompt_one(q_entry)?;
+                let trash_index = answer.as_list_item().unwrap().index;
+
+                let entry_name = String::from(trash[trash_index]["name"].as_str().unwrap());
+                let mut entry = trash[trash_index].clone();
+
+                // a new entry may have taken the name in the meantime, the restored one
+                // can be given another name instead
+                let mut restore_name = entry_name.clone();
+                if self.find_entry(&entry_name).is_some() {
+                    let q_rename = Question::confirm("rename")
+                        .message(format!(
+                            "An entry named \"{}\" already exists. Restore it under a new name?",
+                            entry_name
+                        ))
+                        .default(true)
+                        .build();
+
+                    let answer = prompt_one(q_rename)?;
+                    if !answer.as_bool().unwrap() {
+                        return Ok(());
+                    }
+
+                    restore_name =
+                        self.prompt_new_entry_name("What should the restored entry be called?")?;
+                }
 
-                field_data.push(vec![field_data_str.clone()]);
-            } else if field_type == "Security Question" {
-                // the first part of the field data is the question, the second part is the answer
-                let q_field_data_question = Question::input(format!("field_data_question_{}", i))
-                    .message(format!("Enter question for field {}:", i + 1))
-                    .build();
+                // like a copy, everything is encrypted again under the new name
+                let renamed = restore_name != entry_name;
+                if renamed {
+                    entry["name"] = serde_json::Value::String(restore_name.clone());
+
+                    if let Some(fields) = entry["fields"].as_array_mut() {
+                        for field in fields {
+                            rename_field(key, &entry_name, &restore_name, field);
+                        }
+                    }
+
+                    if let Some(attachments) = entry["attachments"].as_array() {
+                        let attachment_handler = self.attachment_handler();
+
+                        let copies = attachment_handler.copy_attachments(
+                            key,
+                            &entry_name,
+                            &restore_name,
+                            attachments,
+                        )?;
+
+                        entry["attachments"] = serde_json::Value::Array(copies);
+                    }
+                }
 
-                let answer = requestty::prompt_one(q_field_data_question).unwrap();
-                let field_data_question = Secret::new(String::from(answer.as_string().unwrap()));
+                let trashed = self.json["trash"]
+                    .as_array_mut()
+                    .unwrap()
+                    .remove(trash_index);
+                entry.as_object_mut().unwrap().remove("deleted");
 
-                let q_field_data_answer = Question::password(format!("field_data_answer_{}", i))
-                    .message(format!("Enter answer for field {}:", i + 1))
-                    .mask('*')
-                    .build();
+                self.push_entry(entry);
+                self.save_db();
 
-                let answer = requestty::prompt_one(q_field_data_answer).unwrap();
-                let field_data_answer = Secret::new(String::from(answer.as_string().unwrap()));
+                if renamed {
+                    // the attachments under the old name were only copied
+                    self.remove_entry_attachments(&trashed);
 
-                field_data.push(vec![field_data_question.clone(), field_data_answer.clone()]);
-            } else {
-                // the "other" field contains the name of the custom field and the data
-                let q_field_data_name = Question::input(format!("field_data_name_{}", i))
-                    .message(format!("Enter name for field {}:", i + 1))
-                    .build();
+                    let detail = format!("from \"{}\"", entry_name);
+                    self.log_operation(key, "restore", Some(&restore_name), Some(&detail));
 
-                let answer = requestty::prompt_one(q_field_data_name).unwrap();
-                let field_data_name = Secret::new(String::from(answer.as_string().unwrap()));
+                    let restored =
+                        format!("Restored \"{}\" as \"{}\".", entry_name, restore_name).cyan();
+                    println!("{}", restored);
+                } else {
+                    self.log_operation(key, "restore", Some(&entry_name), None);
 
-                let q_field_data_data = Question::password(format!("field_data_data_{}", i))
-                    .message(format!("Enter data for field {}:", i + 1))
-                    .mask('*')
+                    let restored = format!("Restored \"{}\".", entry_name).cyan();
+                    println!("{}", restored);
+                }
+            }
+            "Empty trash" => {
+                let q_empty = Question::confirm("empty")
+                    .message(format!(
+                        "Permanently delete {} entries in the trash?",
+                        trash.len()
+                    ))
                     .build();
 
-                let answer = requestty::prompt_one(q_field_data_data).unwrap();
-                let field_data_data = Secret::new(String::from(answer.as_string().unwrap()));
-
-                field_data.push(vec![field_data_name.clone(), field_data_data.clone()]);
+                let answer = prompt_one(q_empty)?;
+                if answer.as_bool().unwrap() {
+                    for entry in trash {
+                        self.remove_entry_attachments(entry);
+                    }
+
+                    let entry_names = trash
+                        .iter()
+                        .map(|e| String::from(e["name"].as_str().unwrap()))
+                        .collect::<Vec<String>>();
+                    self.json["trash"] = serde_json::json!([]);
+                    self.save_db();
+
+                    for entry_name in &entry_names {
+                        self.log_operation(key, "purge", Some(entry_name), Some("trash emptied"));
+                    }
+
+                    let emptied = "Trash emptied.".cyan();
+                    println!("{}", emptied);
+                }
             }
-
-            field_types.push(String::from(field_type));
+            _ => (),
         }
 
-        // assemble json, encrypt, and write to file
-        let mut json = self.json.clone();
-
-        let mut entry = serde_json::json!({
-            "name": name,
-            "fields": []
-        });
-
-        for i in 0..num_fields {
-            let field_type = field_types[i as usize].clone();
-            let field_data = field_data[i as usize].clone();
+        Ok(())
+    }
 
-            let mut field = serde_json::json!({
-                "type": field_type,
-                "data": [],
-                "nonce": [],
-            });
+    fn manage_templates(&mut self) -> Result<(), AppError> {
+        let template_handler = TemplateHandler::new();
 
-            for j in 0..field_data.len() {
-                let field_data_str = field_data[j].clone();
+        let q_action = Question::select("action")
+            .message("What would you like to do with templates?")
+            .choices(vec!["List", "Create", "Delete", "Back"])
+            .build();
 
-                let mut comb = Vec::new();
-                comb.extend_from_slice(key.as_bytes());
-                comb.extend_from_slice(name.as_bytes());
+        let answer = prompt_one(q_action)?;
+        let action = answer.as_list_item().unwrap().text.as_str();
 
-                let mut hasher = Sha256::new();
-                hasher.update(comb);
-                let hash = hasher.finalize();
+        // older databases were created before templates existed
+        if !self.json["templates"].is_array() {
+            self.json["templates"] = serde_json::json!([]);
+        }
 
-                let mut nonce = [0u8; 12];
-                let mut rng = rand::thread_rng();
-                rng.fill(&mut nonce);
-                let nonce = GenericArray::clone_from_slice(&nonce);
+        match action {
+            "List" => {
+                let templates_title = "Built-in templates:".cyan();
+                println!("{}", templates_title);
+                for template in template_handler.builtin_templates() {
+                    println!("{} {}", "-".cyan(), template.name);
+                }
 
-                let mut cipher = ChaCha20::new(&hash.into(), &nonce.into());
-                let mut encrypted = field_data_str.expose_secret().clone().into_bytes();
+                let templates_title = "Saved templates:".cyan();
+                println!("{}", templates_title);
+                let templates = template_handler.load_templates(&self.json);
+                if templates.is_empty() {
+                    println!("{}", "None.".cyan());
+                }
+                for template in templates {
+                    let field_names = template
+                        .fields
+                        .iter()
+                        .map(|f| f.field_name.clone().unwrap_or(f.field_type.clone()))
+                        .collect::<Vec<String>>()
+                        .join(", ");
+                    println!("{} {} ({})", "-".cyan(), template.name, field_names);
+                }
+                println!();
+            }
+            "Create" => {
+                let template = template_handler.prompt_template()?;
 
-                cipher.apply_keystream(&mut encrypted);
+                let name_taken = template_handler
+                    .builtin_templates()
+                    .iter()
+                    .chain(template_handler.load_templates(&self.json).iter())
+                    .any(|t| t.name == template.name);
 
-                // convert the encrypted bytes to a string of the literal hex
-                let encrypted_str = hex::encode(encrypted);
+                if name_taken {
+                    return Err(AppError::new("A template with that name already exists."));
+                }
 
-                field["data"]
-                    .as_array_mut()
-                    .unwrap()
-                    .push(serde_json::Value::String(encrypted_str));
-                field["nonce"]
+                let template_json = template_handler.template_to_json(&template);
+                self.json["templates"]
                     .as_array_mut()
                     .unwrap()
-                    .push(serde_json::Value::String(hex::encode(nonce)));
+                    .push(template_json);
+                self.save_db();
+
+                let saved = format!("Template \"{}\" saved.", template.name).cyan();
+                println!("{}", saved);
             }
+            "Delete" => {
+                let templates = template_handler.load_templates(&self.json);
 
-            entry["fields"].as_array_mut().unwrap().push(field);
-        }
+                if templates.is_empty() {
+                    let no_templates = "No saved templates.".cyan();
+                    println!("{}", no_templates);
+                    return Ok(());
+                }
 
-        json["entries"].as_array_mut().unwrap().push(entry);
-        self.json = json.clone();
+                let q_template = Question::select("template")
+                    .message("Which template would you like to delete?")
+                    .choices(
+                        templates
+                            .iter()
+                            .map(|t| t.name.clone())
+                            .collect::<Vec<String>>(),
+                    )
+                    .build();
 
-        // write the json to the file
-        //? file is guaranteed to exist at this point
-        let mut file = match File::create(&self.path) {
-            Err(why) => panic!("Couldn't create database file: {}", why),
-            Ok(file) => file,
-        };
+                let answer = prompt_one(q_template)?;
+                let template_index = answer.as_list_item().unwrap().index;
 
-        match file.write_all(json.to_string().as_bytes()) {
-            Err(why) => panic!("Couldn't write to database file: {}", why),
-            Ok(_) => (),
+                self.json["templates"]
+                    .as_array_mut()
+                    .unwrap()
+                    .remove(template_index);
+                self.save_db();
+            }
+            _ => (),
         }
 
         Ok(())
     }
 
-    fn update_entry(&mut self, key: &String) -> Result<(), AppError> {
-        let entries = self.json["entries"].as_array().unwrap();
-
-        if entries.len() == 0 {
-            let no_entries = "No entries.".cyan();
-            println!("{}", no_entries);
-            return Ok(());
-        }
+    fn manage_policies(&mut self) -> Result<(), AppError> {
+        let policy_handler = PolicyHandler::new();
 
-        let q_entry = Question::input("entry")
-            .message("Entry name: ")
+        let q_action = Question::select("action")
+            .message("What would you like to do with password policies?")
+            .choices(vec!["List", "Create", "Delete", "Back"])
             .build();
 
-        let answer = requestty::prompt_one(q_entry).unwrap();
-        let entry_name = answer.as_string().unwrap();
-
-        // find the entry
-        let mut entry: Option<&serde_json::Value> = None;
-        for e in entries {
-            if e["name"].as_str().unwrap() == entry_name {
-                entry = Some(e);
-                break;
-            }
-        }
+        let answer = prompt_one(q_action)?;
+        let action = answer.as_list_item().unwrap().text.as_str();
 
-        if entry.is_none() {
-            let entry_not_found = "Entry not found.".cyan();
-            println!("{}", entry_not_found);
-            return Ok(());
+        // older databases were created before policies existed
+        if !self.json["policies"].is_array() {
+            self.json["policies"] = serde_json::json!([]);
         }
 
-        let entry = entry.unwrap();
-
-        // print the entry
-        let entry_title = format!("Entry: {}", entry["name"].as_str().unwrap()).cyan();
-        println!("{}", entry_title);
-
-        // we have to decrypt the fields before we can print them
-        let fields = entry["fields"].as_array().unwrap();
-        let mut decrypted_fields: Vec<DBField> = Vec::new();
+        let policies = policy_handler.load_policies(&self.json);
 
-        for field in fields {
-            let field_type = field["type"].as_str().unwrap();
+        match action {
+            "List" => {
+                let policies_title = "Password policies:".cyan();
+                println!("{}", policies_title);
+                if policies.is_empty() {
+                    println!("{}", "None.".cyan());
+                }
+                for policy in &policies {
+                    let description = policy_handler.describe_policy(policy);
+                    println!("{} {} ({})", "-".cyan(), policy.name, description);
+                }
+                println!();
+            }
+            "Create" => {
+                let q_name = Question::input("name")
+                    .message("What is the name of this policy?")
+                    .build();
 
-            let encrypted_field_data = field["data"].as_array().unwrap();
-            let encrypted_field_nonce = field["nonce"].as_array().unwrap();
+                let answer = prompt_one(q_name)?;
+                let name = String::from(answer.as_string().unwrap());
 
-            let mut decrypted_field_data: Vec<Secret<String>> = Vec::new();
+                if name.is_empty() {
+                    return Err(AppError::new("Policy name cannot be empty."));
+                }
+                if policies.iter().any(|p| p.name == name) {
+                    return Err(AppError::new("A policy with that name already exists."));
+                }
 
-            for i in 0..encrypted_field_data.len() {
-                let encrypted_field_data_str = encrypted_field_data[i].as_str().unwrap();
-                let encrypted_field_nonce_str = encrypted_field_nonce[i].as_str().unwrap();
+                let policy = policy_handler.prompt_policy(&name)?;
+                self.json["policies"]
+                    .as_array_mut()
+                    .unwrap()
+                    .push(policy_handler.policy_to_json(&policy));
+                self.save_db();
 
-                let encrypted_field_data_bytes = hex::decode(encrypted_field_data_str).unwrap();
-                let encrypted_field_nonce_bytes = hex::decode(encrypted_field_nonce_str).unwrap();
+                let saved = format!("Policy \"{}\" saved.", name).cyan();
+                println!("{}", saved);
+            }
+            "Delete" => {
+                if policies.is_empty() {
+                    let no_policies = "No saved policies.".cyan();
+                    println!("{}", no_policies);
+                    return Ok(());
+                }
 
-                let mut comb = Vec::new();
-                comb.extend_from_slice(key.as_bytes());
-                comb.extend_from_slice(entry_name.as_bytes());
+                let q_policy = Question::select("policy")
+                    .message("Which policy would you like to delete?")
+                    .choices(
+                        policies
+                            .iter()
+                            .map(|p| p.name.clone())
+                            .collect::<Vec<String>>(),
+                    )
+                    .build();
 
-                let mut hasher = Sha256::new();
-                hasher.update(comb);
-                let hash = hasher.finalize();
+                let answer = prompt_one(q_policy)?;
+                let policy_index = answer.as_list_item().unwrap().index;
+                let name = &policies[policy_index].name;
 
-                let nonce = GenericArray::clone_from_slice(&encrypted_field_nonce_bytes);
-                let mut cipher = ChaCha20::new(&hash.into(), &nonce.into());
+                // entries that follow the policy are left without one
+                let in_use = self.json["entries"]
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .filter(|e| e["policy"].as_str() == Some(name))
+                    .count();
+
+                if in_use > 0 {
+                    let q_delete = Question::confirm("delete")
+                        .message(format!(
+                            "{} entries follow \"{}\", delete it anyway?",
+                            in_use, name
+                        ))
+                        .default(false)
+                        .build();
+
+                    let answer = prompt_one(q_delete)?;
+                    if !answer.as_bool().unwrap() {
+                        return Ok(());
+                    }
+                }
 
-                let mut decrypted_field_data_bytes = encrypted_field_data_bytes.clone();
+                self.json["policies"]
+                    .as_array_mut()
+                    .unwrap()
+                    .remove(policy_index);
+                self.save_db();
+            }
+            _ => (),
+        }
 
-                cipher.apply_keystream(&mut decrypted_field_data_bytes);
+        Ok(())
+    }
 
-                let decrypted_field_data_str =
-                    String::from_utf8(decrypted_field_data_bytes).unwrap();
+    fn manage_preferences(&mut self) -> Result<(), AppError> {
+        let sort = self.sort_order();
+        let sort_description = SORT_ORDERS
+            .iter()
+            .find(|(name, _)| *name == sort)
+            .map(|(_, description)| *description)
+            .unwrap_or("Created");
+
+        let mut choices = PREFERENCES
+            .iter()
+            .map(|(name, description, _)| format!("{} ({})", description, self.preference(name)))
+            .collect::<Vec<String>>();
+        choices.push(format!("Sort entries by ({})", sort_description));
+
+        let q_preference = Question::select("preference")
+            .message("Which preference would you like to change?")
+            .choices(choices)
+            .build();
 
-                decrypted_field_data.push(Secret::new(decrypted_field_data_str));
-            }
+        let answer = prompt_one(q_preference)?;
+        let preference_index = answer.as_list_item().unwrap().index;
 
-            decrypted_fields.push(DBField {
-                field_type: String::from(field_type),
-                field_data: decrypted_field_data,
-            });
+        // older databases were created before preferences existed
+        if !self.json["preferences"].is_object() {
+            self.json["preferences"] = serde_json::json!({});
         }
 
-        // ask which field to update
-        let q_field = Question::select("field")
-            .message("Which field would you like to update?")
-            .choices(
-                decrypted_fields
-                    .iter()
-                    .enumerate()
-                    .map(|(i, field)| {
-                        let number = format!("{}.", i + 1).cyan();
-                        let field_type = field.field_type.as_str();
-                        format!("{} {}", number, field_type)
-                    })
-                    .collect::<Vec<String>>(),
-            )
-            .build();
+        if preference_index == PREFERENCES.len() {
+            let q_sort = Question::select("sort")
+                .message("Sort entries by:")
+                .choices(
+                    SORT_ORDERS
+                        .iter()
+                        .map(|(_, description)| *description)
+                        .collect::<Vec<&str>>(),
+                )
+                .build();
 
-        let answer = requestty::prompt_one(q_field).unwrap();
-        let field_index = answer.as_list_item().unwrap().index;
+            let answer = prompt_one(q_sort)?;
+            let (name, _) = SORT_ORDERS[answer.as_list_item().unwrap().index];
 
-        // get the new field data
-        let field_type = decrypted_fields[field_index].field_type.as_str();
+            self.json["preferences"]["sort"] = serde_json::json!(name);
+            self.save_db();
 
-        let mut field_data: Vec<Secret<String>> = Vec::new();
+            return Ok(());
+        }
 
-        if field_type == "Username" || field_type == "Password" {
-            let q_field_data = Question::password("field_data")
-                .message("Enter new data:")
-                .mask('*')
-                .build();
+        let (name, description, _) = PREFERENCES[preference_index];
 
-            let answer = requestty::prompt_one(q_field_data).unwrap();
-            let field_data_str = Secret::new(String::from(answer.as_string().unwrap()));
+        let q_value = Question::int("value")
+            .message(format!("{}:", description))
+            .default(self.preference(name))
+            .validate(|value, _| {
+                if value >= 0 {
+                    Ok(())
+                } else {
+                    Err(String::from("Value cannot be negative."))
+                }
+            })
+            .build();
 
-            field_data.push(field_data_str.clone());
-        } else if field_type == "Security Question" {
-            // the first part of the field data is the question, the second part is the answer
-            let q_field_data_question = Question::input("field_data_question")
-                .message("Enter new question:")
-                .build();
+        let answer = prompt_one(q_value)?;
+        let value = answer.as_int().unwrap();
 
-            let answer = requestty::prompt_one(q_field_data_question).unwrap();
-            let field_data_question = Secret::new(String::from(answer.as_string().unwrap()));
+        self.json["preferences"][name] = serde_json::json!(value);
+        self.save_db();
 
-            let q_field_data_answer = Question::password("field_data_answer")
-                .message("Enter new answer:")
-                .mask('*')
-                .build();
+        Ok(())
+    }
 
-            let answer = requestty::prompt_one(q_field_data_answer).unwrap();
-            let field_data_answer = Secret::new(String::from(answer.as_string().unwrap()));
+    fn get_preference(&self, name: &str) -> i64 {
+        self.preference(name)
+    }
 
-            field_data.push(field_data_question.clone());
-            field_data.push(field_data_answer.clone());
-        } else {
-            // the "other" field contains the name of the custom field and the data
-            let q_field_data_name = Question::input("field_data_name")
-                .message("Enter new name:")
-                .build();
+    // gives back how many were listed
+    fn list_expiring(&self, days: Option<i64>) -> Result<usize, AppError> {
+        let days = days.unwrap_or(self.preference("expiry_warning_days"));
+        let expiry_handler = ExpiryHandler::new(days);
 
-            let answer = requestty::prompt_one(q_field_data_name).unwrap();
-            let field_data_name = Secret::new(String::from(answer.as_string().unwrap()));
+        let items = expiry_handler.expiring_items(self.json["entries"].as_array().unwrap());
+        expiry_handler.print_items(&items);
 
-            let q_field_data_data = Question::password("field_data_data")
-                .message("Enter new data:")
-                .mask('*')
-                .build();
+        Ok(items.len())
+    }
 
-            let answer = requestty::prompt_one(q_field_data_data).unwrap();
-            let field_data_data = Secret::new(String::from(answer.as_string().unwrap()));
+    fn list_matches(&self, query: &str) -> Result<(), AppError> {
+        let candidates = self.match_entries(query);
+        if candidates.is_empty() {
+            return Err(AppError::new("No matching entries."));
+        }
 
-            field_data.push(field_data_name.clone());
-            field_data.push(field_data_data.clone());
+        // one name per line, best match first, so the output is easy to use in scripts
+        let entries = self.json["entries"].as_array().unwrap();
+        for i in candidates {
+            println!("{}", entries[i]["name"].as_str().unwrap());
         }
 
-        // assemble json, encrypt, and write to file
-        let mut json = self.json.clone();
-        
-        let mut new_entry = serde_json::json!({
-            "name": entry_name,
-            "fields": []
-        });
+        Ok(())
+    }
 
-        for i in 0..fields.len() {
-            let field = fields[i].clone();
+    // the whole report at once, as text or as json for dashboards
+    fn health_report(&self, key: &str, json: bool) -> Result<(), AppError> {
+        let health_checker = HealthChecker::new(self.preference("password_age_days"));
+        let report = health_checker.check(&self.health_entries(key));
+        self.log_operation(key, "view", None, Some("health report"));
 
-            let mut new_field = serde_json::json!({
-                "type": field["type"],
-                "data": [],
-                "nonce": [],
-            });
+        if json {
+            println!("{}", health_checker.report_json(&report));
+        } else {
+            health_checker.print_summary(&report);
+            for (check, _, _) in HEALTH_CHECKS {
+                if report.issues.iter().any(|issue| issue.check == check) {
+                    health_checker.print_issues(&report, check);
+                }
+            }
+        }
 
-            if i == field_index {
-                for j in 0..field_data.len() {
-                    let field_data_str = field_data[j].clone();
+        Ok(())
+    }
 
-                    let mut comb = Vec::new();
-                    comb.extend_from_slice(key.as_bytes());
-                    comb.extend_from_slice(entry_name.as_bytes());
+    fn review_health(&self, key: &str) -> Result<(), AppError> {
+        let health_checker = HealthChecker::new(self.preference("password_age_days"));
+        let report = health_checker.check(&self.health_entries(key));
+        self.log_operation(key, "view", None, Some("health report"));
 
-                    let mut hasher = Sha256::new();
-                    hasher.update(comb);
-                    let hash = hasher.finalize();
+        health_checker.review(&report)?;
 
-                    let mut nonce = [0u8; 12];
-                    let mut rng = rand::thread_rng();
-                    rng.fill(&mut nonce);
-                    let nonce = GenericArray::clone_from_slice(&nonce);
+        Ok(())
+    }
 
-                    let mut cipher = ChaCha20::new(&hash.into(), &nonce.into());
-                    let mut encrypted = field_data_str.expose_secret().clone().into_bytes();
+    // looks up every password in a downloaded breach corpus, asking where it is
+    // when it isn't given
+    fn check_breaches(&self, key: &str, file: Option<(&str, bool)>) -> Result<(), AppError> {
+        let (path, ntlm) = match file {
+            Some((path, ntlm)) => (String::from(path), ntlm),
+            None => {
+                let q_path = Question::input("path")
+                    .message("Where is the Have I Been Pwned file or folder of range files?")
+                    .build();
 
-                    cipher.apply_keystream(&mut encrypted);
+                let answer = prompt_one(q_path)?;
+                let path = String::from(answer.as_string().unwrap().trim());
 
-                    // convert the encrypted bytes to a string of the literal hex
-                    let encrypted_str = hex::encode(encrypted);
+                let q_format = Question::select("format")
+                    .message("Which hashes does it hold?")
+                    .choices(vec!["SHA-1", "NTLM"])
+                    .build();
 
-                    new_field["data"]
-                        .as_array_mut()
-                        .unwrap()
-                        .push(serde_json::Value::String(encrypted_str));
-                    new_field["nonce"]
-                        .as_array_mut()
-                        .unwrap()
-                        .push(serde_json::Value::String(hex::encode(nonce)));
-                }
-            } else {
-                new_field["data"] = field["data"].clone();
-                new_field["nonce"] = field["nonce"].clone();
+                let answer = prompt_one(q_format)?;
+                (path, answer.as_list_item().unwrap().index == 1)
             }
+        };
 
-            new_entry["fields"].as_array_mut().unwrap().push(new_field);
-        }
-
-        // replace the old entry with the new entry
-        let mut new_entries: Vec<serde_json::Value> = Vec::new();
-        for e in entries {
-            if e["name"].as_str().unwrap() == entry_name {
-                new_entries.push(new_entry.clone());
-            } else {
-                new_entries.push(e.clone());
+        let mut breach_checker = BreachChecker::new(Path::new(&path), ntlm)?;
+
+        // the same password in several entries is only looked up once
+        let mut seen: HashMap<String, u64> = HashMap::new();
+        let mut breached: Vec<(String, u64)> = Vec::new();
+        let mut checked = 0;
+
+        for entry in self.health_entries(key) {
+            // a big corpus takes a while, and it shouldn't keep the vault open
+            check_locked()?;
+
+            for (password, _) in &entry.passwords {
+                let password = password.expose_secret();
+                let times = match seen.get(password) {
+                    Some(times) => *times,
+                    None => {
+                        let times = breach_checker.times_seen(password)?;
+                        seen.insert(password.clone(), times);
+                        times
+                    }
+                };
+
+                checked += 1;
+                if times > 0 {
+                    breached.push((entry.name.clone(), times));
+                }
             }
         }
 
-        json["entries"] = serde_json::Value::Array(new_entries);
-        self.json = json.clone();
+        // every password in the vault was decrypted to be looked up
+        self.log_operation(key, "view", None, Some("breach check"));
 
-        // write the json to the file
-        //? file is guaranteed to exist at this point
-        let mut file = match File::create(&self.path) {
-            Err(why) => panic!("Couldn't create database file: {}", why),
-            Ok(file) => file,
-        };
+        let checked = format!("Checked {} passwords.", checked).cyan();
+        println!("{}", checked);
 
-        match file.write_all(json.to_string().as_bytes()) {
-            Err(why) => panic!("Couldn't write to database file: {}", why),
-            Ok(_) => (),
+        if breached.is_empty() {
+            let none_found = "None of them are in the breach data.".cyan();
+            println!("{}", none_found);
+        } else {
+            let breached_title = "Found in breaches, change these passwords:".cyan();
+            println!("{}", breached_title);
+            for (name, times) in breached {
+                println!("{} {}: seen {} times", "-".red(), name, times);
+            }
         }
-        
+
         Ok(())
     }
 
-    fn delete_entry(&mut self) -> Result<(), AppError> {
-        let entries = self.json["entries"].as_array().unwrap();
-
-        if entries.len() == 0 {
-            let no_entries = "No entries.".cyan();
-            println!("{}", no_entries);
-            return Ok(());
+    // the usernames and email addresses stored in the vault, a master password
+    // shouldn't be any of them
+    fn usernames(&self, key: &str) -> Vec<String> {
+        let mut usernames: Vec<String> = Vec::new();
+
+        for entry in self.json["entries"].as_array().unwrap() {
+            let entry_name = entry["name"].as_str().unwrap();
+
+            for field in entry["fields"].as_array().unwrap() {
+                let field = match field["type"].as_str() {
+                    Some("Username") | Some("Email") => decrypt_field(key, entry_name, field),
+                    _ => continue,
+                };
+                let value = field.field_data[field_part_count(&field.field_type) - 1]
+                    .expose_secret()
+                    .clone();
+                usernames.push(value);
+            }
         }
 
-        let q_entry = Question::input("entry")
-            .message("Entry name: ")
-            .build();
-
-        let answer = requestty::prompt_one(q_entry).unwrap();
-        let entry_name = answer.as_string().unwrap();
+        usernames
+    }
 
-        // find the entry
-        let mut entry: Option<&serde_json::Value> = None;
-        for e in entries {
-            if e["name"].as_str().unwrap() == entry_name {
-                entry = Some(e);
-                break;
+    // re-encrypts a copy of everything, trash included, for a new key into files next
+    // to the real ones, nothing the old password opens is touched, the files returned
+    // only take over once the new password is committed to the settings along with
+    // the settings returned, and the copy once the change is finished
+    fn stage_key_change(
+        &mut self,
+        old_key: &str,
+        new_key: &str,
+    ) -> Result<(Vec<PathBuf>, serde_json::Value), AppError> {
+        let attachment_handler = self.attachment_handler();
+        let mut staged: Vec<PathBuf> = Vec::new();
+
+        // sidecar chunks are the only part that can fail to be read, so they go
+        // first while the vault in memory is still on the old key
+        for list in ["entries", "trash"] {
+            for entry in self.json[list].as_array().into_iter().flatten() {
+                let entry_name = entry["name"].as_str().unwrap();
+
+                for attachment in entry["attachments"].as_array().into_iter().flatten() {
+                    match attachment_handler
+                        .stage_rekey_chunks(old_key, new_key, entry_name, attachment)
+                    {
+                        Ok(paths) => staged.extend(paths),
+                        Err(e) => {
+                            discard_staged(&staged);
+                            return Err(e);
+                        }
+                    }
+                }
             }
         }
 
-        if entry.is_none() {
-            let entry_not_found = "Entry not found.".cyan();
-            println!("{}", entry_not_found);
-            return Ok(());
-        }
-
-        let entry = entry.unwrap();
-
-        // print the entry
-        let entry_title = format!("Entry: {}", entry["name"].as_str().unwrap()).cyan();
-        println!("{}", entry_title);
+        let mut json = self.json.clone();
+        for list in ["entries", "trash"] {
+            let entries = match json[list].as_array_mut() {
+                Some(entries) => entries,
+                None => continue,
+            };
+
+            for entry in entries {
+                let entry_name = String::from(entry["name"].as_str().unwrap());
+
+                for field in entry["fields"].as_array_mut().unwrap() {
+                    rekey_field(old_key, new_key, &entry_name, field);
+
+                    if let Some(history) = field["history"].as_array_mut() {
+                        for previous in history {
+                            rekey_field(old_key, new_key, &entry_name, previous);
+                        }
+                    }
+                }
 
-        // ask if they are sure they want to delete the entry
-        let q_delete = Question::confirm("delete")
-            .message("Are you sure you want to delete this entry?")
-            .build();
+                if let Some(attachments) = entry["attachments"].as_array_mut() {
+                    for attachment in attachments {
+                        attachment_handler.rekey_attachment(
+                            old_key,
+                            new_key,
+                            &entry_name,
+                            attachment,
+                        );
+                    }
+                }
+            }
+        }
 
-        let answer = requestty::prompt_one(q_delete).unwrap();
-        let delete = answer.as_bool().unwrap();
+        // the audit log follows the vault onto the new key
+        let mut staged_settings = serde_json::json!({});
+        if let Some((path, settings)) = self.audit_log().stage_rekey_log(old_key, new_key) {
+            staged.push(path);
+            staged_settings = settings;
+        }
 
-        if !delete {
-            let delete_cancelled = "Delete cancelled.".cyan();
-            println!("{}", delete_cancelled);
-            return Ok(());
+        let path = PathBuf::from(&self.path);
+        if let Err(why) = write_staged(&path, |writer| {
+            serde_json::to_writer(writer, &json).map_err(std::io::Error::from)
+        }) {
+            staged.push(path);
+            discard_staged(&staged);
+            return Err(AppError::new(&format!(
+                "Couldn't write to database file: {}",
+                why
+            )));
         }
+        staged.push(path);
+        self.staged_json = Some(json);
 
-        // delete the entry from the json
-        let mut new_entries: Vec<serde_json::Value> = Vec::new();
+        Ok((staged, staged_settings))
+    }
 
-        for e in entries {
-            if e["name"].as_str().unwrap() != entry_name {
-                new_entries.push(e.clone());
-            }
+    // entries keep their places in the copy, so the index still fits it
+    fn finish_key_change(&mut self, new_key: &str) {
+        if let Some(json) = self.staged_json.take() {
+            self.json = json;
         }
+        self.log_operation(new_key, "change_password", None, None);
+    }
 
-        let mut json = self.json.clone();
-        json["entries"] = serde_json::Value::Array(new_entries);
-        self.json = json.clone();
+    // called once the master password has been checked, failed attempts from before
+    // it are written with the time they happened, and the trash is purged now rather
+    // than on load so what it removes can be logged
+    fn record_unlock(&mut self, key: &str, failed_unlocks: &[String]) {
+        let audit_log = self.audit_log();
+        for time in failed_unlocks {
+            audit_log.record_at(key, time, "failed_unlock", None, None);
+        }
+        audit_log.record(key, "unlock", None, None);
 
-        // write the json to the file
-        //? file is guaranteed to exist at this point
-        let mut file = match File::create(&self.path) {
-            Err(why) => panic!("Couldn't create database file: {}", why),
-            Ok(file) => file,
-        };
+        self.purge_trash(key);
+    }
 
-        match file.write_all(json.to_string().as_bytes()) {
-            Err(why) => panic!("Couldn't write to database file: {}", why),
-            Ok(_) => (),
-        }
+    fn print_audit_log(
+        &self,
+        key: &str,
+        entry: Option<&str>,
+        operation: Option<&str>,
+    ) -> Result<(), AppError> {
+        self.audit_log().print_log(key, entry, operation)
+    }
 
-        Ok(())
+    fn review_audit_log(&self, key: &str) -> Result<(), AppError> {
+        self.audit_log().review_log(key)
     }
 }
@@ -5,12 +5,48 @@ use argon2::{
 };
 use requestty::Question;
 use secrecy::{ExposeSecret, Secret};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+// other stuff
+use crossterm::event::{self, Event, KeyCode, KeyModifiers};
+use crossterm::terminal;
+use std::io::{self, IsTerminal};
 
 // my stuff
 use crate::errors::AppError;
+use crate::handle_autolock::{prompt_one, VaultLocked};
 
 // ------------------ //
 
+// reads a line from the terminal without echoing it, with the message on stderr
+fn read_hidden_line(message: &str) -> Secret<String> {
+    eprint!("{}: ", message);
+
+    terminal::enable_raw_mode().unwrap();
+    let mut line = String::new();
+    loop {
+        if let Event::Key(key) = event::read().unwrap() {
+            match key.code {
+                KeyCode::Enter => break,
+                KeyCode::Backspace => {
+                    line.pop();
+                }
+                KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    terminal::disable_raw_mode().unwrap();
+                    eprintln!();
+                    std::process::exit(1);
+                }
+                KeyCode::Char(c) => line.push(c),
+                _ => (),
+            }
+        }
+    }
+    terminal::disable_raw_mode().unwrap();
+    eprintln!();
+
+    Secret::new(line)
+}
+
 pub trait ProcessPassword {
     fn new() -> Self;
     fn verify_password(
@@ -18,17 +54,28 @@ pub trait ProcessPassword {
         password_hash: String,
         derived_key_salt: String,
     ) -> Result<(), AppError>;
-    fn get_decrypt_key(&self) -> Secret<String>;
+    fn prompt_password(&self) -> Result<Secret<String>, VaultLocked>;
+    fn check_password(
+        &mut self,
+        password: &Secret<String>,
+        password_hash: String,
+        derived_key_salt: String,
+    ) -> Result<(), AppError>;
+    fn set_password(&mut self, password: &Secret<String>, derived_key_salt: String);
+    fn set_decrypt_key(&mut self, decrypt_key: Secret<String>);
+    fn decrypt_key(&self) -> MutexGuard<'_, Secret<String>>;
+    fn shared_key(&self) -> Arc<Mutex<Secret<String>>>;
 }
 
 pub struct PasswordHandler {
-    decrypt_key: Secret<String>,
+    // shared with the idle watcher, which wipes it when the vault locks
+    decrypt_key: Arc<Mutex<Secret<String>>>,
 }
 
 impl ProcessPassword for PasswordHandler {
     fn new() -> Self {
         PasswordHandler {
-            decrypt_key: Secret::new(String::from("")),
+            decrypt_key: Arc::new(Mutex::new(Secret::new(String::from("")))),
         }
     }
 
@@ -37,14 +84,33 @@ impl ProcessPassword for PasswordHandler {
         password_hash: String,
         derived_key_salt: String,
     ) -> Result<(), AppError> {
+        let password = self.prompt_password()?;
+        self.check_password(&password, password_hash, derived_key_salt)
+    }
+
+    fn prompt_password(&self) -> Result<Secret<String>, VaultLocked> {
+        // requestty draws on stdout and asks it where the cursor is, which doesn't
+        // work once the output is piped somewhere, so the password is read plainly
+        // on stderr instead
+        if !io::stdout().is_terminal() {
+            return Ok(read_hidden_line("Enter your password"));
+        }
+
         let q_pass = Question::password("password")
             .message("Enter your password")
             .mask('*')
             .build();
 
-        let answer = requestty::prompt_one(q_pass).unwrap();
-        let password = Secret::new(String::from(answer.as_string().unwrap()));
+        let answer = prompt_one(q_pass)?;
+        Ok(Secret::new(String::from(answer.as_string().unwrap())))
+    }
 
+    fn check_password(
+        &mut self,
+        password: &Secret<String>,
+        password_hash: String,
+        derived_key_salt: String,
+    ) -> Result<(), AppError> {
         let argon2 = Argon2::default();
         let password_hash = PasswordHash::new(&password_hash).unwrap();
 
@@ -52,17 +118,7 @@ impl ProcessPassword for PasswordHandler {
             .verify_password(password.expose_secret().as_bytes(), &password_hash)
             .is_ok()
         {
-            // set the decrypt key
-            let salt = SaltString::from_b64(derived_key_salt.as_str()).unwrap();
-            let decryption_key = Secret::new(
-                argon2
-                    .hash_password(password.expose_secret().as_bytes(), &salt)
-                    .unwrap()
-                    .to_string(),
-            );
-
-            // make a secret, then zeroize our decryption key and password
-            self.decrypt_key = decryption_key.clone();
+            self.set_password(password, derived_key_salt);
 
             Ok(())
         } else {
@@ -70,7 +126,35 @@ impl ProcessPassword for PasswordHandler {
         }
     }
 
-    fn get_decrypt_key(&self) -> Secret<String> {
-        self.decrypt_key.clone()
+    // derives the decrypt key from the password, which has to already be verified
+    fn set_password(&mut self, password: &Secret<String>, derived_key_salt: String) {
+        let argon2 = Argon2::default();
+
+        // set the decrypt key
+        let salt = SaltString::from_b64(derived_key_salt.as_str()).unwrap();
+        let decryption_key = Secret::new(
+            argon2
+                .hash_password(password.expose_secret().as_bytes(), &salt)
+                .unwrap()
+                .to_string(),
+        );
+
+        // make a secret, then zeroize our decryption key and password
+        self.set_decrypt_key(decryption_key);
+    }
+
+    // the key that was there before is zeroized as it is dropped
+    fn set_decrypt_key(&mut self, decrypt_key: Secret<String>) {
+        *self.decrypt_key.lock().unwrap() = decrypt_key;
+    }
+
+    // the key is lent out rather than copied, so wiping it when the vault locks
+    // leaves no copy behind, it has to be given back before it can be changed
+    fn decrypt_key(&self) -> MutexGuard<'_, Secret<String>> {
+        self.decrypt_key.lock().unwrap()
+    }
+
+    fn shared_key(&self) -> Arc<Mutex<Secret<String>>> {
+        Arc::clone(&self.decrypt_key)
     }
 }
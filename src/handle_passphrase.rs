@@ -0,0 +1,275 @@
+// question stuff
+use requestty::Question;
+
+// password stuff
+use rand::rngs::OsRng;
+use rand::seq::SliceRandom;
+use rand::Rng;
+use secrecy::{ExposeSecret, Secret};
+
+// other stuff
+use colored::Colorize;
+
+// my stuff
+use crate::handle_autolock::{prompt_one, VaultLocked};
+
+// ------------------ //
+
+// 2048 short, common words where the first four letters are enough to tell any two
+// apart, so every word adds 11 bits
+const WORDLIST: &str = include_str!("wordlist.txt");
+
+const CAPITALIZATION: [&str; 4] = [
+    "all lowercase",
+    "Capitalize Each Word",
+    "Capitalize one random word",
+    "ALL UPPERCASE",
+];
+
+pub struct PassphraseSettings {
+    pub word_count: usize,
+    pub separator: String,
+    pub capitalization: usize,
+    pub add_digit: bool,
+}
+
+impl PassphraseSettings {
+    pub fn default_settings() -> Self {
+        PassphraseSettings {
+            word_count: 6,
+            separator: String::from("-"),
+            capitalization: 0,
+            add_digit: false,
+        }
+    }
+}
+
+pub trait GeneratePassphrase {
+    fn new() -> Self;
+    fn generate(&self, settings: &PassphraseSettings) -> Secret<String>;
+    fn entropy_bits(&self, settings: &PassphraseSettings) -> f64;
+    fn prompt_settings(
+        &self,
+        current: &PassphraseSettings,
+    ) -> Result<PassphraseSettings, VaultLocked>;
+    fn prompt_generated(&self) -> Result<Option<Secret<String>>, VaultLocked>;
+}
+
+pub struct PassphraseGenerator {
+    words: Vec<&'static str>,
+}
+
+impl GeneratePassphrase for PassphraseGenerator {
+    fn new() -> Self {
+        PassphraseGenerator {
+            words: WORDLIST.lines().filter(|word| !word.is_empty()).collect(),
+        }
+    }
+
+    fn generate(&self, settings: &PassphraseSettings) -> Secret<String> {
+        let mut rng = OsRng;
+
+        let mut words = (0..settings.word_count)
+            .map(|_| String::from(*self.words.choose(&mut rng).unwrap()))
+            .collect::<Vec<String>>();
+
+        let capitalize = |word: &str| {
+            let mut chars = word.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars).collect(),
+                None => String::new(),
+            }
+        };
+
+        match settings.capitalization {
+            1 => words = words.iter().map(|word| capitalize(word)).collect(),
+            2 => {
+                let i = rng.gen_range(0..words.len());
+                words[i] = capitalize(&words[i]);
+            }
+            3 => words = words.iter().map(|word| word.to_uppercase()).collect(),
+            _ => (),
+        }
+
+        // the digit goes on the end of a random word
+        if settings.add_digit {
+            let i = rng.gen_range(0..words.len());
+            let digit = rng.gen_range(0..10);
+            words[i] = format!("{}{}", words[i], digit);
+        }
+
+        Secret::new(words.join(&settings.separator))
+    }
+
+    // the bits of entropy in a passphrase made with these settings, assuming an
+    // attacker knows the wordlist and the settings
+    fn entropy_bits(&self, settings: &PassphraseSettings) -> f64 {
+        let word_count = settings.word_count as f64;
+        let mut bits = word_count * (self.words.len() as f64).log2();
+
+        if settings.capitalization == 2 {
+            bits += word_count.log2();
+        }
+        if settings.add_digit {
+            bits += (10.0 * word_count).log2();
+        }
+
+        bits
+    }
+
+    fn prompt_settings(
+        &self,
+        current: &PassphraseSettings,
+    ) -> Result<PassphraseSettings, VaultLocked> {
+        let q_word_count = Question::int("word_count")
+            .message("How many words?")
+            .default(current.word_count as i64)
+            .validate(|word_count, _| {
+                if (3..=20).contains(&word_count) {
+                    Ok(())
+                } else {
+                    Err(String::from("Use between 3 and 20 words."))
+                }
+            })
+            .build();
+
+        let answer = prompt_one(q_word_count)?;
+        let word_count = answer.as_int().unwrap() as usize;
+
+        let q_separator = Question::input("separator")
+            .message("What goes between the words?")
+            .default(current.separator.clone())
+            .build();
+
+        let answer = prompt_one(q_separator)?;
+        let separator = String::from(answer.as_string().unwrap());
+
+        let q_capitalization = Question::select("capitalization")
+            .message("Capitalization:")
+            .choices(CAPITALIZATION.to_vec())
+            .default(current.capitalization)
+            .build();
+
+        let answer = prompt_one(q_capitalization)?;
+        let capitalization = answer.as_list_item().unwrap().index;
+
+        let q_digit = Question::confirm("digit")
+            .message("Add a digit to one of the words?")
+            .default(current.add_digit)
+            .build();
+
+        let answer = prompt_one(q_digit)?;
+
+        Ok(PassphraseSettings {
+            word_count,
+            separator,
+            capitalization,
+            add_digit: answer.as_bool().unwrap(),
+        })
+    }
+
+    // shows generated passphrases until one is accepted, or nothing if the user
+    // would rather type one in
+    fn prompt_generated(&self) -> Result<Option<Secret<String>>, VaultLocked> {
+        let mut settings = PassphraseSettings::default_settings();
+
+        loop {
+            let passphrase = self.generate(&settings);
+
+            let preview = "Generated passphrase:".cyan();
+            let entropy = format!("({:.0} bits of entropy)", self.entropy_bits(&settings)).cyan();
+            println!("{} {} {}", preview, passphrase.expose_secret(), entropy);
+
+            let q_action = Question::select("generated_action")
+                .message("Use this passphrase?")
+                .choices(vec![
+                    "Use it",
+                    "Generate another",
+                    "Change settings",
+                    "Type one in instead",
+                ])
+                .build();
+
+            let answer = prompt_one(q_action)?;
+            match answer.as_list_item().unwrap().index {
+                0 => return Ok(Some(passphrase)),
+                1 => (),
+                2 => settings = self.prompt_settings(&settings)?,
+                _ => return Ok(None),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(word_count: usize, capitalization: usize, add_digit: bool) -> PassphraseSettings {
+        PassphraseSettings {
+            word_count,
+            separator: String::from("."),
+            capitalization,
+            add_digit,
+        }
+    }
+
+    #[test]
+    fn the_wordlist_holds_11_bits_a_word() {
+        let generator = PassphraseGenerator::new();
+        assert_eq!(generator.words.len(), 2048);
+
+        let mut prefixes = generator
+            .words
+            .iter()
+            .map(|word| word.chars().take(4).collect::<String>())
+            .collect::<Vec<String>>();
+        prefixes.sort();
+        prefixes.dedup();
+        assert_eq!(prefixes.len(), 2048);
+    }
+
+    #[test]
+    fn has_the_number_of_words_asked_for() {
+        let generator = PassphraseGenerator::new();
+
+        for word_count in [1, 4, 10] {
+            let passphrase = generator.generate(&settings(word_count, 0, false));
+            let words = passphrase.expose_secret().split('.').collect::<Vec<&str>>();
+
+            assert_eq!(words.len(), word_count);
+            assert!(words.iter().all(|word| generator.words.contains(word)));
+        }
+    }
+
+    #[test]
+    fn capitalizes_and_adds_a_digit() {
+        let generator = PassphraseGenerator::new();
+
+        let passphrase = generator.generate(&settings(5, 3, true));
+        let passphrase = passphrase.expose_secret();
+        assert_eq!(passphrase.chars().filter(|c| c.is_ascii_digit()).count(), 1);
+        assert!(!passphrase.chars().any(|c| c.is_lowercase()));
+
+        let passphrase = generator.generate(&settings(5, 2, false));
+        let capitalized = passphrase
+            .expose_secret()
+            .split('.')
+            .filter(|word| word.starts_with(|c: char| c.is_uppercase()))
+            .count();
+        assert_eq!(capitalized, 1);
+    }
+
+    #[test]
+    fn counts_the_entropy_of_the_settings() {
+        let generator = PassphraseGenerator::new();
+
+        assert_eq!(generator.entropy_bits(&settings(6, 0, false)), 66.0);
+        // capitalizing every word or none adds nothing an attacker doesn't know
+        assert_eq!(generator.entropy_bits(&settings(6, 3, false)), 66.0);
+        assert_eq!(generator.entropy_bits(&settings(4, 2, false)), 46.0);
+
+        let with_digit = generator.entropy_bits(&settings(4, 0, true));
+        assert!((with_digit - (44.0 + 40f64.log2())).abs() < 1e-9);
+    }
+}
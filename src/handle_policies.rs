@@ -0,0 +1,451 @@
+// question stuff
+use requestty::Question;
+
+// my stuff
+use crate::errors::AppError;
+use crate::handle_autolock::prompt_one;
+use crate::handle_generator::{GeneratorSettings, CLASS_NAMES, SYMBOLS};
+
+// ------------------ //
+
+// what a policy says about each class of character, in the order of CLASS_NAMES
+const CLASS_RULES: [&str; 3] = ["required", "allowed", "forbidden"];
+
+pub struct PasswordPolicy {
+    // policies made for a single entry have no name
+    pub name: String,
+    pub min_length: usize,
+    pub max_length: usize,
+    pub classes: [String; 4],
+    // the symbols a site accepts, when symbols aren't forbidden
+    pub symbols: String,
+    // text that must not appear anywhere in the password, ignoring case
+    pub forbidden: Vec<String>,
+}
+
+pub trait ProcessPolicies {
+    fn new() -> Self;
+    fn load_policies(&self, json: &serde_json::Value) -> Vec<PasswordPolicy>;
+    fn policy_from_json(&self, json: &serde_json::Value) -> PasswordPolicy;
+    fn policy_to_json(&self, policy: &PasswordPolicy) -> serde_json::Value;
+    fn entry_policy(
+        &self,
+        json: &serde_json::Value,
+        entry_policy: &serde_json::Value,
+    ) -> Option<PasswordPolicy>;
+    fn describe_policy(&self, policy: &PasswordPolicy) -> String;
+    fn check_password(&self, policy: &PasswordPolicy, password: &str) -> Vec<String>;
+    fn generator_settings(&self, policy: &PasswordPolicy) -> GeneratorSettings;
+    fn prompt_policy(&self, name: &str) -> Result<PasswordPolicy, AppError>;
+}
+
+pub struct PolicyHandler {}
+
+fn class_of(c: char) -> Option<usize> {
+    if c.is_ascii_lowercase() {
+        Some(0)
+    } else if c.is_ascii_uppercase() {
+        Some(1)
+    } else if c.is_ascii_digit() {
+        Some(2)
+    } else if c.is_ascii_punctuation() {
+        Some(3)
+    } else {
+        None
+    }
+}
+
+impl ProcessPolicies for PolicyHandler {
+    fn new() -> Self {
+        PolicyHandler {}
+    }
+
+    fn load_policies(&self, json: &serde_json::Value) -> Vec<PasswordPolicy> {
+        match json["policies"].as_array() {
+            Some(policies) => policies.iter().map(|p| self.policy_from_json(p)).collect(),
+            None => Vec::new(),
+        }
+    }
+
+    fn policy_from_json(&self, json: &serde_json::Value) -> PasswordPolicy {
+        let class_rule =
+            |class: &str| String::from(json["classes"][class].as_str().unwrap_or("allowed"));
+
+        PasswordPolicy {
+            name: String::from(json["name"].as_str().unwrap_or("")),
+            min_length: json["min_length"].as_u64().unwrap_or(8) as usize,
+            max_length: json["max_length"].as_u64().unwrap_or(64) as usize,
+            classes: [
+                class_rule("lowercase"),
+                class_rule("uppercase"),
+                class_rule("digits"),
+                class_rule("symbols"),
+            ],
+            symbols: String::from(json["symbols"].as_str().unwrap_or(SYMBOLS)),
+            forbidden: json["forbidden"]
+                .as_array()
+                .map(|forbidden| {
+                    forbidden
+                        .iter()
+                        .filter_map(|f| f.as_str().map(String::from))
+                        .collect()
+                })
+                .unwrap_or_default(),
+        }
+    }
+
+    fn policy_to_json(&self, policy: &PasswordPolicy) -> serde_json::Value {
+        let mut json = serde_json::json!({
+            "min_length": policy.min_length,
+            "max_length": policy.max_length,
+            "classes": {
+                "lowercase": policy.classes[0],
+                "uppercase": policy.classes[1],
+                "digits": policy.classes[2],
+                "symbols": policy.classes[3],
+            },
+            "symbols": policy.symbols,
+            "forbidden": policy.forbidden,
+        });
+
+        if !policy.name.is_empty() {
+            json["name"] = serde_json::json!(policy.name);
+        }
+
+        json
+    }
+
+    // entries either name a policy from the vault's library or carry their own, a
+    // name that is no longer in the library means no policy
+    fn entry_policy(
+        &self,
+        json: &serde_json::Value,
+        entry_policy: &serde_json::Value,
+    ) -> Option<PasswordPolicy> {
+        match entry_policy {
+            serde_json::Value::String(name) => self
+                .load_policies(json)
+                .into_iter()
+                .find(|policy| policy.name == *name),
+            serde_json::Value::Object(_) => Some(self.policy_from_json(entry_policy)),
+            _ => None,
+        }
+    }
+
+    fn describe_policy(&self, policy: &PasswordPolicy) -> String {
+        let mut parts = vec![format!(
+            "{}-{} characters",
+            policy.min_length, policy.max_length
+        )];
+
+        for (rule, verb) in [("required", "needs"), ("forbidden", "no")] {
+            let classes = (0..CLASS_NAMES.len())
+                .filter(|class| policy.classes[*class] == rule)
+                .map(|class| CLASS_NAMES[class])
+                .collect::<Vec<&str>>();
+
+            if !classes.is_empty() {
+                parts.push(format!("{} {}", verb, classes.join(", ")));
+            }
+        }
+
+        if policy.classes[3] != "forbidden" && policy.symbols != SYMBOLS {
+            parts.push(format!("symbols {}", policy.symbols));
+        }
+        if !policy.forbidden.is_empty() {
+            parts.push(format!("not containing {}", policy.forbidden.join(", ")));
+        }
+
+        parts.join(", ")
+    }
+
+    // everything about the password that breaks the policy, nothing if it complies
+    fn check_password(&self, policy: &PasswordPolicy, password: &str) -> Vec<String> {
+        let mut problems: Vec<String> = Vec::new();
+        let length = password.chars().count();
+
+        if length < policy.min_length {
+            problems.push(format!(
+                "It is shorter than {} characters.",
+                policy.min_length
+            ));
+        }
+        if length > policy.max_length {
+            problems.push(format!(
+                "It is longer than {} characters.",
+                policy.max_length
+            ));
+        }
+
+        for (class, class_name) in CLASS_NAMES.iter().enumerate() {
+            let used = password.chars().any(|c| class_of(c) == Some(class));
+
+            if policy.classes[class] == "required" && !used {
+                problems.push(format!("It needs {}.", class_name));
+            }
+            if policy.classes[class] == "forbidden" && used {
+                problems.push(format!("It can't contain {}.", class_name));
+            }
+        }
+
+        let rejected = password
+            .chars()
+            .filter(|c| match class_of(*c) {
+                Some(3) => policy.classes[3] != "forbidden" && !policy.symbols.contains(*c),
+                Some(_) => false,
+                None => true,
+            })
+            .collect::<String>();
+        if !rejected.is_empty() {
+            problems.push(format!("These characters aren't accepted: {}", rejected));
+        }
+
+        let lowercase = password.to_lowercase();
+        for forbidden in &policy.forbidden {
+            if lowercase.contains(&forbidden.to_lowercase()) {
+                problems.push(format!("It contains \"{}\".", forbidden));
+            }
+        }
+
+        problems
+    }
+
+    // generator settings that only produce passwords the policy accepts, as long as
+    // the policy can be met at all
+    fn generator_settings(&self, policy: &PasswordPolicy) -> GeneratorSettings {
+        let mut settings = GeneratorSettings::default_settings();
+
+        settings.length = 20.clamp(policy.min_length, policy.max_length.max(policy.min_length));
+        for class in 0..CLASS_NAMES.len() {
+            settings.classes[class] = match policy.classes[class].as_str() {
+                "required" => (true, 1),
+                "forbidden" => (false, 0),
+                _ => (true, 0),
+            };
+        }
+        settings.symbols = policy.symbols.clone();
+        settings.forbidden = policy.forbidden.clone();
+
+        settings
+    }
+
+    fn prompt_policy(&self, name: &str) -> Result<PasswordPolicy, AppError> {
+        let q_min_length = Question::int("min_length")
+            .message("Shortest password accepted:")
+            .default(8)
+            .validate(|length, _| {
+                if length >= 1 {
+                    Ok(())
+                } else {
+                    Err(String::from("The length has to be at least 1."))
+                }
+            })
+            .build();
+
+        let answer = prompt_one(q_min_length)?;
+        let min_length = answer.as_int().unwrap() as usize;
+
+        let q_max_length = Question::int("max_length")
+            .message("Longest password accepted:")
+            .default(64.max(min_length as i64))
+            .validate(move |length, _| {
+                if length >= min_length as i64 {
+                    Ok(())
+                } else {
+                    Err(format!("The length has to be at least {}.", min_length))
+                }
+            })
+            .build();
+
+        let answer = prompt_one(q_max_length)?;
+        let max_length = answer.as_int().unwrap() as usize;
+
+        let mut classes: [String; 4] = Default::default();
+        for (class, class_name) in CLASS_NAMES.iter().enumerate() {
+            let q_rule = Question::select("class_rule")
+                .message(format!(
+                    "Are {} required, allowed or forbidden?",
+                    class_name
+                ))
+                .choices(CLASS_RULES.to_vec())
+                .default(1)
+                .build();
+
+            let answer = prompt_one(q_rule)?;
+            classes[class] = answer.as_list_item().unwrap().text.clone();
+        }
+
+        let symbols = if classes[3] == "forbidden" {
+            String::from(SYMBOLS)
+        } else {
+            let q_symbols = Question::input("symbols")
+                .message("Which symbols are accepted?")
+                .default(SYMBOLS)
+                .validate(|symbols, _| {
+                    if !symbols.is_empty() && symbols.chars().all(|c| c.is_ascii_punctuation()) {
+                        Ok(())
+                    } else {
+                        Err(String::from("Enter the accepted symbols, like !@#$."))
+                    }
+                })
+                .build();
+
+            let answer = prompt_one(q_symbols)?;
+            String::from(answer.as_string().unwrap())
+        };
+
+        let q_forbidden = Question::input("forbidden")
+            .message("Text the password must not contain (separate with spaces, blank for none):")
+            .build();
+
+        let answer = prompt_one(q_forbidden)?;
+        let forbidden = answer
+            .as_string()
+            .unwrap()
+            .split_whitespace()
+            .map(String::from)
+            .collect::<Vec<String>>();
+
+        let required = classes.iter().filter(|rule| *rule == "required").count();
+        if classes.iter().all(|rule| rule == "forbidden") {
+            return Err(AppError::new(
+                "A policy can't forbid every kind of character.",
+            ));
+        }
+        if required > max_length {
+            return Err(AppError::new(
+                "The longest password is too short for the required characters.",
+            ));
+        }
+
+        Ok(PasswordPolicy {
+            name: String::from(name),
+            min_length,
+            max_length,
+            classes,
+            symbols,
+            forbidden,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handle_generator::{GeneratePassword, PasswordGenerator};
+    use secrecy::ExposeSecret;
+
+    // 8-16 characters, a digit and an uppercase letter needed, only a few symbols
+    fn site_policy() -> PasswordPolicy {
+        PolicyHandler::new().policy_from_json(&serde_json::json!({
+            "name": "site",
+            "min_length": 8,
+            "max_length": 16,
+            "classes": {
+                "uppercase": "required",
+                "digits": "required",
+                "symbols": "allowed",
+            },
+            "symbols": "!-_",
+            "forbidden": ["acme"],
+        }))
+    }
+
+    #[test]
+    fn accepts_a_compliant_password() {
+        let handler = PolicyHandler::new();
+
+        assert!(handler
+            .check_password(&site_policy(), "Marble-Otter7")
+            .is_empty());
+    }
+
+    #[test]
+    fn reports_every_problem() {
+        let handler = PolicyHandler::new();
+        let policy = site_policy();
+
+        let problems = handler.check_password(&policy, "short");
+        assert!(problems.contains(&String::from("It is shorter than 8 characters.")));
+        assert!(problems.contains(&String::from("It needs uppercase letters.")));
+        assert!(problems.contains(&String::from("It needs digits.")));
+
+        let problems = handler.check_password(&policy, "Marble#Otter7");
+        assert_eq!(
+            problems,
+            vec![String::from("These characters aren't accepted: #")]
+        );
+
+        let problems = handler.check_password(&policy, "MyACMElogin7");
+        assert_eq!(problems, vec![String::from("It contains \"acme\".")]);
+
+        let problems = handler.check_password(&policy, "Marble-Otter-Quartz-7");
+        assert_eq!(
+            problems,
+            vec![String::from("It is longer than 16 characters.")]
+        );
+    }
+
+    #[test]
+    fn forbidden_classes_are_rejected() {
+        let handler = PolicyHandler::new();
+        let mut policy = site_policy();
+        policy.classes[3] = String::from("forbidden");
+
+        let problems = handler.check_password(&policy, "Marble-Otter7");
+        assert!(problems.contains(&String::from("It can't contain symbols.")));
+    }
+
+    #[test]
+    fn policies_round_trip_through_json() {
+        let handler = PolicyHandler::new();
+        let policy = site_policy();
+
+        let read = handler.policy_from_json(&handler.policy_to_json(&policy));
+        assert_eq!(read.name, "site");
+        assert_eq!((read.min_length, read.max_length), (8, 16));
+        assert_eq!(read.classes, policy.classes);
+        assert_eq!(read.symbols, "!-_");
+        assert_eq!(read.forbidden, vec![String::from("acme")]);
+        assert_eq!(
+            handler.describe_policy(&read),
+            handler.describe_policy(&policy)
+        );
+    }
+
+    #[test]
+    fn entries_name_a_policy_or_carry_their_own() {
+        let handler = PolicyHandler::new();
+        let vault = serde_json::json!({
+            "policies": [handler.policy_to_json(&site_policy())],
+        });
+
+        let named = handler.entry_policy(&vault, &serde_json::json!("site"));
+        assert_eq!(named.unwrap().max_length, 16);
+
+        let own = handler.entry_policy(&vault, &serde_json::json!({ "min_length": 30 }));
+        assert_eq!(own.unwrap().min_length, 30);
+
+        assert!(handler
+            .entry_policy(&vault, &serde_json::json!("missing"))
+            .is_none());
+        assert!(handler
+            .entry_policy(&vault, &serde_json::Value::Null)
+            .is_none());
+    }
+
+    #[test]
+    fn generated_passwords_meet_the_policy() {
+        let handler = PolicyHandler::new();
+        let generator = PasswordGenerator::new();
+        let policy = site_policy();
+        let settings = handler.generator_settings(&policy);
+
+        for _ in 0..50 {
+            let password = generator.generate(&settings).unwrap();
+            assert!(handler
+                .check_password(&policy, password.expose_secret())
+                .is_empty());
+        }
+    }
+}
@@ -0,0 +1,113 @@
+// other stuff
+use colored::Colorize;
+
+// my stuff
+use crate::handle_fields::is_concealed;
+
+// ------------------ //
+
+// how much of the text around a match is shown on either side of it
+const CONTEXT_CHARS: usize = 24;
+
+// values that are secret enough to leave out of a search unless asked for, the same
+// ones that are hidden when an entry is shown, only the last part of a field is ever
+// concealed
+pub fn is_secret_part(field_type: &str, part: usize, part_count: usize) -> bool {
+    is_concealed(field_type) && part + 1 == part_count
+}
+
+pub trait SearchText {
+    fn new(query: &str) -> Self;
+    fn snippet(&self, text: &str) -> Option<String>;
+}
+
+pub struct TextSearcher {
+    query: Vec<char>,
+}
+
+fn same_letter(a: char, b: char) -> bool {
+    a == b || a.to_lowercase().eq(b.to_lowercase())
+}
+
+impl TextSearcher {
+    // positions of every match of the query in the text, ignoring case
+    fn find_all(&self, text: &[char]) -> Vec<usize> {
+        if self.query.is_empty() || self.query.len() > text.len() {
+            return Vec::new();
+        }
+
+        let mut positions: Vec<usize> = Vec::new();
+        let mut i = 0;
+
+        while i + self.query.len() <= text.len() {
+            let found = self
+                .query
+                .iter()
+                .zip(&text[i..])
+                .all(|(q, t)| same_letter(*q, *t));
+
+            if found {
+                positions.push(i);
+                i += self.query.len();
+            } else {
+                i += 1;
+            }
+        }
+
+        positions
+    }
+}
+
+impl SearchText for TextSearcher {
+    fn new(query: &str) -> Self {
+        TextSearcher {
+            query: query.trim().chars().collect(),
+        }
+    }
+
+    // the part of the text around the first match, with the matches in it
+    // highlighted, or nothing if the text doesn't match
+    fn snippet(&self, text: &str) -> Option<String> {
+        let text = text.chars().collect::<Vec<char>>();
+        let positions = self.find_all(&text);
+        let first = *positions.first()?;
+
+        let start = first.saturating_sub(CONTEXT_CHARS);
+        let end = (first + self.query.len() + CONTEXT_CHARS).min(text.len());
+
+        // line breaks in notes would break up the results, so they are shown as a
+        // marker instead
+        let plain = |chars: &[char]| {
+            chars
+                .iter()
+                .collect::<String>()
+                .replace("\r\n", " ⏎ ")
+                .replace('\n', " ⏎ ")
+        };
+
+        let mut snippet = String::new();
+        if start > 0 {
+            snippet.push('…');
+        }
+
+        let mut position = start;
+        for found in positions
+            .into_iter()
+            .filter(|p| *p + self.query.len() <= end)
+        {
+            snippet.push_str(&plain(&text[position..found]));
+
+            let matched = plain(&text[found..found + self.query.len()]);
+            snippet.push_str(&matched.black().on_yellow().to_string());
+
+            position = found + self.query.len();
+        }
+        snippet.push_str(&plain(&text[position..end]));
+
+        if end < text.len() {
+            snippet.push('…');
+        }
+
+        Some(snippet)
+    }
+}
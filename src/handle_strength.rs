@@ -0,0 +1,858 @@
+// question stuff
+use requestty_ui::backend::Backend;
+use requestty_ui::events::KeyEvent;
+use requestty_ui::layout::Layout;
+use requestty_ui::style::{self, Color};
+use requestty_ui::widgets::{self, Delimiter};
+use requestty_ui::{Input, Validation, Widget};
+
+// password stuff
+use secrecy::Secret;
+use std::collections::HashMap;
+
+// other stuff
+use chrono::prelude::*;
+use colored::Colorize;
+use std::io::{self, Write};
+
+// my stuff
+use crate::handle_autolock::{run_prompt, VaultLocked};
+
+// ------------------ //
+
+// the most used passwords from public breaches, most common first
+const COMMON_PASSWORDS: &str = include_str!("common_passwords.txt");
+const WORDLIST: &str = include_str!("wordlist.txt");
+
+// anything longer than this is only estimated up to here, the rest counts as random
+const MAX_ANALYZED: usize = 100;
+const MAX_WORD_LENGTH: usize = 20;
+
+// every guess count from here on is kept as its log10, long passwords overflow
+// anything else, a character that isn't part of a pattern takes 10 guesses
+const CHARACTER_GUESSES: f64 = 1.0;
+const KEYBOARD_STARTING_KEYS: f64 = 47.0;
+const KEYBOARD_DIRECTIONS: f64 = 6.0;
+const MIN_YEAR_SPACE: i32 = 20;
+
+pub const SCORE_NAMES: [&str; 5] = ["very weak", "weak", "fair", "strong", "very strong"];
+
+// how fast guesses are made in different kinds of attack, as the log10 of the
+// guesses per second
+const CRACK_SCENARIOS: [(&str, f64); 3] = [
+    ("online, 10 guesses a second", 1.0),
+    ("offline against a slow hash", 4.0),
+    ("offline against a fast hash", 10.0),
+];
+
+const KEYBOARD_ROWS: [&str; 4] = [
+    "`1234567890-=",
+    "qwertyuiop[]\\",
+    "asdfghjkl;'",
+    "zxcvbnm,./",
+];
+const SHIFTED_ROWS: [&str; 4] = [
+    "~!@#$%^&*()_+",
+    "QWERTYUIOP{}|",
+    "ASDFGHJKL:\"",
+    "ZXCVBNM<>?",
+];
+// how far right each row's keys sit compared to the row above, in columns
+const ROW_OFFSETS: [i32; 4] = [0, 1, 0, 0];
+
+const L33T_SUBSTITUTIONS: [(char, char); 14] = [
+    ('4', 'a'),
+    ('@', 'a'),
+    ('8', 'b'),
+    ('(', 'c'),
+    ('3', 'e'),
+    ('6', 'g'),
+    ('9', 'g'),
+    ('!', 'i'),
+    ('|', 'i'),
+    ('0', 'o'),
+    ('$', 's'),
+    ('5', 's'),
+    ('7', 't'),
+    ('+', 't'),
+];
+
+const DATE_SEPARATORS: &str = " -/._\\";
+
+struct PatternMatch {
+    start: usize,
+    end: usize,
+    guesses: f64,
+    description: String,
+}
+
+pub struct Strength {
+    // log10 of the number of guesses an attacker needs
+    pub guesses: f64,
+    pub score: usize,
+    // the patterns the estimate found, empty if it looks random
+    pub patterns: Vec<String>,
+}
+
+pub trait EstimateStrength {
+    fn new() -> Self;
+    fn estimate(&self, password: &str) -> Strength;
+    fn print_report(&self, strength: &Strength);
+    fn prompt_password(&self, message: &str) -> Result<Secret<String>, VaultLocked>;
+}
+
+pub struct StrengthEstimator {
+    // the guesses for each known word, and whether it is a common password
+    ranked: HashMap<String, (f64, bool)>,
+    reference_year: i32,
+}
+
+fn log_factorial(n: usize) -> f64 {
+    (2..=n).map(|i| (i as f64).log10()).sum()
+}
+
+fn log_binomial(n: usize, k: usize) -> f64 {
+    log_factorial(n) - log_factorial(k) - log_factorial(n - k)
+}
+
+// log10(10^a + 10^b)
+fn log_add(a: f64, b: f64) -> f64 {
+    let (high, low) = if a > b { (a, b) } else { (b, a) };
+    high + (1.0 + 10f64.powf(low - high)).log10()
+}
+
+// how many ways the letters could have been capitalized, common habits like a
+// capital first letter are cheap to guess
+fn case_variations(word: &[char]) -> f64 {
+    let upper = word.iter().filter(|c| c.is_uppercase()).count();
+    let lower = word.iter().filter(|c| c.is_lowercase()).count();
+
+    if upper == 0 {
+        return 0.0;
+    }
+    if lower == 0 || (upper == 1 && (word[0].is_uppercase() || word[word.len() - 1].is_uppercase()))
+    {
+        return 2f64.log10();
+    }
+
+    (1..=upper.min(lower))
+        .map(|i| log_binomial(upper + lower, i))
+        .fold(f64::NEG_INFINITY, log_add)
+}
+
+fn key_position(c: char) -> Option<(i32, i32, bool)> {
+    for (row, (keys, shifted)) in KEYBOARD_ROWS.iter().zip(SHIFTED_ROWS).enumerate() {
+        if let Some(col) = keys.chars().position(|k| k == c) {
+            return Some((row as i32, col as i32, false));
+        }
+        if let Some(col) = shifted.chars().position(|k| k == c) {
+            return Some((row as i32, col as i32, true));
+        }
+    }
+    None
+}
+
+// which way you move on the keyboard to get from one key to the next, if they
+// are next to each other
+fn key_direction(from: char, to: char) -> Option<usize> {
+    let (from_row, from_col, _) = key_position(from)?;
+    let (to_row, to_col, _) = key_position(to)?;
+
+    // rows below the keyboard have no keys, so their offset doesn't matter
+    let row_offset = |row: i32| ROW_OFFSETS.get(row as usize).copied().unwrap_or(0);
+    let directions = [
+        (from_row, from_col - 1),
+        (from_row, from_col + 1),
+        (from_row - 1, from_col + row_offset(from_row)),
+        (from_row - 1, from_col + row_offset(from_row) + 1),
+        (from_row + 1, from_col - row_offset(from_row + 1) - 1),
+        (from_row + 1, from_col - row_offset(from_row + 1)),
+    ];
+
+    directions
+        .iter()
+        .position(|(row, col)| *row == to_row && *col == to_col)
+}
+
+fn unl33t(word: &[char], i_or_l: char) -> (String, usize) {
+    let mut substitutions = 0;
+    let plain = word
+        .iter()
+        .map(|c| {
+            let plain = match c {
+                '1' => Some(i_or_l),
+                _ => L33T_SUBSTITUTIONS
+                    .iter()
+                    .find(|(l33t, _)| l33t == c)
+                    .map(|(_, plain)| *plain),
+            };
+            match plain {
+                Some(plain) => {
+                    substitutions += 1;
+                    plain
+                }
+                None => c.to_ascii_lowercase(),
+            }
+        })
+        .collect();
+
+    (plain, substitutions)
+}
+
+fn valid_date(day: u32, month: u32, year: i32) -> bool {
+    NaiveDate::from_ymd_opt(year, month, day).is_some()
+}
+
+fn full_year(year: u32, digits: usize) -> i32 {
+    match (digits, year) {
+        (2, year) if year > 50 => 1900 + year as i32,
+        (2, year) => 2000 + year as i32,
+        (_, year) => year as i32,
+    }
+}
+
+// a day, month and year in any of the usual orders, the year comes first or last
+fn parse_date(parts: &[&str]) -> Option<i32> {
+    let numbers = parts
+        .iter()
+        .map(|part| part.parse::<u32>().ok())
+        .collect::<Option<Vec<u32>>>()?;
+
+    let candidates = [
+        (parts[2], numbers[2], numbers[0], numbers[1]),
+        (parts[2], numbers[2], numbers[1], numbers[0]),
+        (parts[0], numbers[0], numbers[2], numbers[1]),
+        (parts[0], numbers[0], numbers[1], numbers[2]),
+    ];
+
+    candidates
+        .iter()
+        .filter(|(year_text, _, _, _)| year_text.len() == 2 || year_text.len() == 4)
+        .map(|(year_text, year, day, month)| (full_year(*year, year_text.len()), *day, *month))
+        .find(|(year, day, month)| (1900..=2050).contains(year) && valid_date(*day, *month, *year))
+        .map(|(year, _, _)| year)
+}
+
+impl StrengthEstimator {
+    fn year_guesses(&self, year: i32) -> f64 {
+        ((year - self.reference_year).abs().max(MIN_YEAR_SPACE) as f64).log10()
+    }
+
+    fn dictionary_matches(&self, chars: &[char], matches: &mut Vec<PatternMatch>) {
+        for start in 0..chars.len() {
+            for end in start + 1..=chars.len().min(start + MAX_WORD_LENGTH) {
+                let word = &chars[start..end];
+                let lowercase = word.iter().collect::<String>().to_lowercase();
+                let reversed = lowercase.chars().rev().collect::<String>();
+
+                if let Some((rank, common)) = self.ranked.get(&lowercase) {
+                    let kind = if *common { "common password" } else { "word" };
+                    matches.push(PatternMatch {
+                        start,
+                        end,
+                        guesses: rank + case_variations(word),
+                        description: format!("the {} \"{}\"", kind, lowercase),
+                    });
+                } else if word.len() >= 4 && self.ranked.contains_key(&reversed) {
+                    matches.push(PatternMatch {
+                        start,
+                        end,
+                        guesses: self.ranked[&reversed].0 + case_variations(word) + 2f64.log10(),
+                        description: format!("\"{}\" spelled backwards", reversed),
+                    });
+                }
+
+                if word.len() < 3 {
+                    continue;
+                }
+                for i_or_l in ['i', 'l'] {
+                    let (plain, substitutions) = unl33t(word, i_or_l);
+                    if substitutions == 0 || plain == lowercase {
+                        continue;
+                    }
+                    if let Some((rank, _)) = self.ranked.get(&plain) {
+                        matches.push(PatternMatch {
+                            start,
+                            end,
+                            guesses: rank
+                                + case_variations(word)
+                                + (substitutions as f64) * 2f64.log10(),
+                            description: format!(
+                                "\"{}\" with symbols swapped in for letters",
+                                plain
+                            ),
+                        });
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    fn keyboard_matches(&self, chars: &[char], matches: &mut Vec<PatternMatch>) {
+        let mut start = 0;
+
+        while start + 2 < chars.len() {
+            let mut end = start + 1;
+            let mut direction = None;
+            let mut turns = 0;
+
+            while end < chars.len() {
+                match key_direction(chars[end - 1], chars[end]) {
+                    Some(next) => {
+                        if direction != Some(next) {
+                            turns += 1;
+                        }
+                        direction = Some(next);
+                        end += 1;
+                    }
+                    None => break,
+                }
+            }
+
+            if end - start >= 3 {
+                let shifted = chars[start..end]
+                    .iter()
+                    .filter(|c| matches!(key_position(**c), Some((_, _, true))))
+                    .count();
+                let length = end - start;
+                let shift_guesses = match shifted {
+                    0 => 0.0,
+                    shifted if shifted == length => 2f64.log10(),
+                    shifted => log_binomial(length, shifted.min(length - shifted)),
+                };
+
+                matches.push(PatternMatch {
+                    start,
+                    end,
+                    guesses: KEYBOARD_STARTING_KEYS.log10()
+                        + (length as f64).log10()
+                        + (turns as f64) * KEYBOARD_DIRECTIONS.log10()
+                        + shift_guesses,
+                    description: format!(
+                        "the keyboard pattern \"{}\"",
+                        chars[start..end].iter().collect::<String>()
+                    ),
+                });
+                start = end - 1;
+            } else {
+                start += 1;
+            }
+        }
+    }
+
+    fn repeat_matches(&self, chars: &[char], matches: &mut Vec<PatternMatch>) {
+        for start in 0..chars.len() {
+            let mut best: Option<(usize, usize)> = None;
+
+            for block in 1..=(chars.len() - start) / 2 {
+                let mut repeats = 1;
+                while start + (repeats + 1) * block <= chars.len()
+                    && chars[start + repeats * block..start + (repeats + 1) * block]
+                        == chars[start..start + block]
+                {
+                    repeats += 1;
+                }
+
+                let long_enough = repeats >= 3 || (repeats == 2 && block > 1);
+                let longest = best.map(|(b, r)| b * r).unwrap_or(0);
+                if long_enough && block * repeats > longest {
+                    best = Some((block, repeats));
+                }
+            }
+
+            if let Some((block, repeats)) = best {
+                let block_text = chars[start..start + block].iter().collect::<String>();
+                matches.push(PatternMatch {
+                    start,
+                    end: start + block * repeats,
+                    guesses: self.estimate(&block_text).guesses + (repeats as f64).log10(),
+                    description: format!("\"{}\" repeated {} times", block_text, repeats),
+                });
+            }
+        }
+    }
+
+    fn sequence_matches(&self, chars: &[char], matches: &mut Vec<PatternMatch>) {
+        let same_kind = |a: char, b: char| {
+            (a.is_ascii_lowercase() && b.is_ascii_lowercase())
+                || (a.is_ascii_uppercase() && b.is_ascii_uppercase())
+                || (a.is_ascii_digit() && b.is_ascii_digit())
+        };
+
+        let mut start = 0;
+        while start + 2 < chars.len() {
+            let step = chars[start + 1] as i32 - chars[start] as i32;
+            let mut end = start + 1;
+
+            if (1..=2).contains(&step.abs()) {
+                while end < chars.len()
+                    && chars[end] as i32 - chars[end - 1] as i32 == step
+                    && same_kind(chars[end - 1], chars[end])
+                {
+                    end += 1;
+                }
+            }
+
+            if end - start >= 3 {
+                let first = chars[start];
+                let starting_guesses: f64 = if "aAzZ019".contains(first) {
+                    4.0
+                } else if first.is_ascii_digit() {
+                    10.0
+                } else {
+                    26.0
+                };
+                let descending = if step < 0 { 2f64.log10() } else { 0.0 };
+
+                matches.push(PatternMatch {
+                    start,
+                    end,
+                    guesses: starting_guesses.log10() + ((end - start) as f64).log10() + descending,
+                    description: format!(
+                        "the sequence \"{}\"",
+                        chars[start..end].iter().collect::<String>()
+                    ),
+                });
+                start = end - 1;
+            } else {
+                start += 1;
+            }
+        }
+    }
+
+    fn date_matches(&self, chars: &[char], matches: &mut Vec<PatternMatch>) {
+        for start in 0..chars.len() {
+            for end in start + 4..=chars.len().min(start + 10) {
+                let text = chars[start..end].iter().collect::<String>();
+
+                // a year on its own
+                if text.len() == 4 && text.chars().all(|c| c.is_ascii_digit()) {
+                    let year = text.parse::<i32>().unwrap();
+                    if (1900..=2050).contains(&year) {
+                        matches.push(PatternMatch {
+                            start,
+                            end,
+                            guesses: self.year_guesses(year),
+                            description: format!("the year \"{}\"", text),
+                        });
+                    }
+                }
+
+                let separator = text
+                    .chars()
+                    .find(|c| DATE_SEPARATORS.contains(*c))
+                    .filter(|separator| text.matches(*separator).count() == 2);
+
+                let year = match separator {
+                    Some(separator) => {
+                        let parts = text.split(separator).collect::<Vec<&str>>();
+                        if parts.iter().all(|part| {
+                            !part.is_empty()
+                                && part.len() <= 4
+                                && part.chars().all(|c| c.is_ascii_digit())
+                        }) {
+                            parse_date(&parts).map(|year| (year, 4f64.log10()))
+                        } else {
+                            None
+                        }
+                    }
+                    None if text.len() <= 8 && text.chars().all(|c| c.is_ascii_digit()) => {
+                        // without separators the date could be split a few ways
+                        let mut year = None;
+                        for first in 1..text.len() - 1 {
+                            for second in first + 1..text.len() {
+                                let parts = [&text[..first], &text[first..second], &text[second..]];
+                                if parts.iter().all(|part| part.len() <= 4) {
+                                    year = year.or(parse_date(&parts).map(|year| (year, 0.0)));
+                                }
+                            }
+                        }
+                        year
+                    }
+                    None => None,
+                };
+
+                if let Some((year, separator_guesses)) = year {
+                    matches.push(PatternMatch {
+                        start,
+                        end,
+                        guesses: self.year_guesses(year) + 365f64.log10() + separator_guesses,
+                        description: format!("the date \"{}\"", text),
+                    });
+                }
+            }
+        }
+    }
+}
+
+// a masked password prompt that shows how strong the password is as it is typed,
+// the mask turns red while it is still easy to guess
+struct StrengthInput<'a> {
+    estimator: &'a StrengthEstimator,
+    message: &'a str,
+    prompt: widgets::Prompt<&'a str, String>,
+    input: widgets::StringInput,
+    score: usize,
+}
+
+impl<'a> StrengthInput<'a> {
+    fn new(estimator: &'a StrengthEstimator, message: &'a str) -> Self {
+        let mut strength_input = StrengthInput {
+            estimator,
+            message,
+            prompt: widgets::Prompt::new(message),
+            input: widgets::StringInput::default().password(Some('*')),
+            score: 0,
+        };
+        strength_input.update_hint();
+
+        strength_input
+    }
+
+    fn update_hint(&mut self) {
+        let hint = if self.input.value().is_empty() {
+            String::from("strength is shown as you type")
+        } else {
+            let strength = self.estimator.estimate(self.input.value());
+            self.score = strength.score;
+
+            // the slow hash is what a stolen vault or settings file is up against
+            let (_, rate) = CRACK_SCENARIOS[1];
+            format!(
+                "{}, {} to crack offline",
+                SCORE_NAMES[strength.score],
+                display_time(10f64.powf(strength.guesses - rate))
+            )
+        };
+
+        self.prompt = widgets::Prompt::new(self.message)
+            .with_delim(Delimiter::SquareBracket)
+            .with_hint(hint);
+    }
+}
+
+impl requestty_ui::Prompt for StrengthInput<'_> {
+    type ValidateErr = widgets::Text<String>;
+    type Output = String;
+
+    fn validate(&mut self) -> Result<Validation, Self::ValidateErr> {
+        Ok(Validation::Finish)
+    }
+
+    fn finish(self) -> Self::Output {
+        self.input.finish()
+    }
+}
+
+impl Widget for StrengthInput<'_> {
+    fn render<B: Backend>(&mut self, layout: &mut Layout, b: &mut B) -> io::Result<()> {
+        self.prompt.render(layout, b)?;
+
+        if self.score < 3 {
+            b.set_fg(Color::Red)?;
+        }
+        self.input.render(layout, b)?;
+        if self.score < 3 {
+            b.set_fg(Color::Reset)?;
+        }
+
+        Ok(())
+    }
+
+    fn height(&mut self, layout: &mut Layout) -> u16 {
+        self.prompt.height(layout) + self.input.height(layout) - 1
+    }
+
+    fn handle_key(&mut self, key: KeyEvent) -> bool {
+        let handled = self.input.handle_key(key);
+        if handled {
+            self.update_hint();
+        }
+
+        handled
+    }
+
+    fn cursor_pos(&mut self, layout: Layout) -> (u16, u16) {
+        self.input
+            .cursor_pos(layout.with_cursor_pos(self.prompt.cursor_pos(layout)))
+    }
+}
+
+impl EstimateStrength for StrengthEstimator {
+    fn new() -> Self {
+        let mut ranked: HashMap<String, (f64, bool)> = HashMap::new();
+
+        // a common password is guessed about as soon as its rank, a word from the
+        // passphrase wordlist is one of however many words there are
+        for (rank, password) in COMMON_PASSWORDS.lines().enumerate() {
+            ranked.insert(String::from(password), (((rank + 1) as f64).log10(), true));
+        }
+        let words = WORDLIST.lines().filter(|word| !word.is_empty());
+        let word_guesses = (words.clone().count() as f64).log10();
+        for word in words {
+            ranked
+                .entry(String::from(word))
+                .or_insert((word_guesses, false));
+        }
+
+        StrengthEstimator {
+            ranked,
+            reference_year: Local::now().year(),
+        }
+    }
+
+    // finds the easiest way to guess the password as a sequence of patterns, with
+    // anything they don't cover guessed one character at a time
+    fn estimate(&self, password: &str) -> Strength {
+        let all_chars = password.chars().collect::<Vec<char>>();
+        let chars = &all_chars[..all_chars.len().min(MAX_ANALYZED)];
+        let unanalyzed = (all_chars.len() - chars.len()) as f64 * CHARACTER_GUESSES;
+        let length = chars.len();
+
+        let mut matches: Vec<PatternMatch> = Vec::new();
+        self.dictionary_matches(chars, &mut matches);
+        self.keyboard_matches(chars, &mut matches);
+        self.repeat_matches(chars, &mut matches);
+        self.sequence_matches(chars, &mut matches);
+        self.date_matches(chars, &mut matches);
+
+        // characters that aren't part of any pattern
+        for start in 0..length {
+            for end in start + 1..=length {
+                matches.push(PatternMatch {
+                    start,
+                    end,
+                    guesses: (end - start) as f64 * CHARACTER_GUESSES,
+                    description: String::new(),
+                });
+            }
+        }
+
+        // best[end][count] is the fewest guesses for the first end characters using
+        // count patterns, along with the last pattern used
+        let mut best: Vec<Vec<Option<(f64, usize)>>> = vec![vec![None; length + 1]; length + 1];
+        best[0][0] = Some((0.0, usize::MAX));
+
+        let mut by_end: Vec<Vec<usize>> = vec![Vec::new(); length + 1];
+        for (i, pattern) in matches.iter().enumerate() {
+            by_end[pattern.end].push(i);
+        }
+
+        for end in 1..=length {
+            for &i in &by_end[end] {
+                let pattern = &matches[i];
+                for count in 0..length {
+                    if let Some((guesses, _)) = best[pattern.start][count] {
+                        let total = guesses + pattern.guesses;
+                        let better = match best[end][count + 1] {
+                            Some((current, _)) => total < current,
+                            None => true,
+                        };
+                        if better {
+                            best[end][count + 1] = Some((total, i));
+                        }
+                    }
+                }
+            }
+        }
+
+        // more patterns are more ways to combine them, which adds to the guesses
+        let mut fewest: Option<(f64, usize)> = None;
+        for (count, entry) in best[length].iter().enumerate().skip(1) {
+            if let Some((guesses, _)) = entry {
+                let total = log_add(log_factorial(count) + guesses, 4.0 * (count as f64 - 1.0));
+                if fewest.map(|(current, _)| total < current).unwrap_or(true) {
+                    fewest = Some((total, count));
+                }
+            }
+        }
+
+        let mut patterns: Vec<String> = Vec::new();
+        let guesses = match fewest {
+            Some((guesses, count)) => {
+                let mut end = length;
+                for remaining in (1..=count).rev() {
+                    let pattern = &matches[best[end][remaining].unwrap().1];
+                    if !pattern.description.is_empty() {
+                        patterns.push(pattern.description.clone());
+                    }
+                    end = pattern.start;
+                }
+                patterns.reverse();
+                guesses
+            }
+            None => 0.0,
+        } + unanalyzed;
+
+        let score = match guesses {
+            guesses if guesses < 3.0 => 0,
+            guesses if guesses < 6.0 => 1,
+            guesses if guesses < 8.0 => 2,
+            guesses if guesses < 10.0 => 3,
+            _ => 4,
+        };
+
+        Strength {
+            guesses,
+            score,
+            patterns,
+        }
+    }
+
+    fn prompt_password(&self, message: &str) -> Result<Secret<String>, VaultLocked> {
+        let password = run_prompt(|backend, events| {
+            let password = Input::new(StrengthInput::new(self, message), backend).run(events)?;
+
+            widgets::Prompt::write_finished_message(&message, false, backend)?;
+            backend.write_styled(&style::Stylize::dark_grey("[hidden]"))?;
+            backend.write_all(b"\n")?;
+            backend.flush()?;
+
+            Ok(password.unwrap_or_default())
+        })?;
+
+        Ok(Secret::new(password))
+    }
+
+    fn print_report(&self, strength: &Strength) {
+        let name = match strength.score {
+            0 | 1 => SCORE_NAMES[strength.score].red(),
+            2 => SCORE_NAMES[strength.score].yellow(),
+            _ => SCORE_NAMES[strength.score].green(),
+        };
+        let guesses = if strength.guesses < 6.0 {
+            format!("{:.0}", 10f64.powf(strength.guesses))
+        } else {
+            format!("10^{:.0}", strength.guesses)
+        };
+
+        let strength_title = "Strength:".cyan();
+        println!("{} {} (about {} guesses)", strength_title, name, guesses);
+
+        for (scenario, rate) in CRACK_SCENARIOS {
+            let seconds = 10f64.powf(strength.guesses - rate);
+            println!(
+                "  {} {}",
+                format!("{}:", scenario).cyan(),
+                display_time(seconds)
+            );
+        }
+
+        if !strength.patterns.is_empty() {
+            println!(
+                "  {} {}",
+                "Guessable from:".cyan(),
+                strength.patterns.join(", ")
+            );
+        }
+    }
+}
+
+// whether the password is one of the bundled common passwords, ignoring case
+pub fn is_common_password(password: &str) -> bool {
+    let lowercase = password.to_lowercase();
+    COMMON_PASSWORDS.lines().any(|common| common == lowercase)
+}
+
+pub fn display_time(seconds: f64) -> String {
+    const UNITS: [(&str, f64); 6] = [
+        ("second", 1.0),
+        ("minute", 60.0),
+        ("hour", 3600.0),
+        ("day", 86400.0),
+        ("month", 2629800.0),
+        ("year", 31557600.0),
+    ];
+
+    if seconds < 1.0 {
+        return String::from("less than a second");
+    }
+    if seconds >= 100.0 * 31557600.0 {
+        return String::from("centuries");
+    }
+
+    let (unit, length) = UNITS
+        .iter()
+        .rev()
+        .find(|(_, length)| seconds >= *length)
+        .unwrap();
+    let count = (seconds / length).round() as u64;
+
+    format!("{} {}{}", count, unit, if count == 1 { "" } else { "s" })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn found(estimator: &StrengthEstimator, password: &str, pattern: &str) -> bool {
+        estimator
+            .estimate(password)
+            .patterns
+            .iter()
+            .any(|found| found.contains(pattern))
+    }
+
+    #[test]
+    fn common_passwords_are_very_weak() {
+        let estimator = StrengthEstimator::new();
+
+        assert_eq!(estimator.estimate("password").score, 0);
+        assert!(found(
+            &estimator,
+            "password",
+            "the common password \"password\""
+        ));
+        assert!(is_common_password("PassWord"));
+        assert!(!is_common_password("Zq7!marble-Otter-92"));
+    }
+
+    #[test]
+    fn finds_patterns() {
+        let estimator = StrengthEstimator::new();
+
+        assert!(found(&estimator, "wertyuio", "the keyboard pattern"));
+        assert!(found(&estimator, "xkxkxkxkxk", "repeated 5 times"));
+        assert!(found(&estimator, "abcdefgh", "the sequence \"abcdefgh\""));
+        assert!(found(
+            &estimator,
+            "p@ssw0rd",
+            "with symbols swapped in for letters"
+        ));
+        assert!(found(&estimator, "drowssap", "spelled backwards"));
+        assert!(found(&estimator, "born1987", "the year \"1987\""));
+        assert!(found(&estimator, "12/03/1987", "the date"));
+    }
+
+    #[test]
+    fn patterns_are_weaker_than_random() {
+        let estimator = StrengthEstimator::new();
+
+        let walk = estimator.estimate("wertyuiop");
+        let random = estimator.estimate("q7$Kz!mP2x");
+        assert!(walk.guesses < random.guesses);
+        assert!(random.patterns.is_empty());
+    }
+
+    #[test]
+    fn longer_passwords_score_higher() {
+        let estimator = StrengthEstimator::new();
+
+        assert!(estimator.estimate("").score == 0);
+        assert_eq!(estimator.estimate("Zq7!marble-Otter-92").score, 4);
+        assert!(
+            estimator.estimate("marble-otter").guesses
+                < estimator.estimate("marble-otter-quartz").guesses
+        );
+    }
+
+    #[test]
+    fn display_time_rounds_to_a_unit() {
+        assert_eq!(display_time(0.5), "less than a second");
+        assert_eq!(display_time(1.0), "1 second");
+        assert_eq!(display_time(90.0), "2 minutes");
+        assert_eq!(display_time(7200.0), "2 hours");
+        assert_eq!(display_time(1e12), "centuries");
+    }
+}
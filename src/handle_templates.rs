@@ -0,0 +1,232 @@
+// question stuff
+use requestty::Question;
+
+// my stuff
+use crate::errors::AppError;
+use crate::handle_autolock::prompt_one;
+use crate::handle_fields::{FieldPrompter, PromptField};
+
+// ------------------ //
+
+pub struct TemplateField {
+    pub field_type: String,
+    pub field_name: Option<String>,
+}
+
+pub struct EntryTemplate {
+    pub name: String,
+    pub fields: Vec<TemplateField>,
+}
+
+pub trait ProcessTemplates {
+    fn new() -> Self;
+    fn builtin_templates(&self) -> Vec<EntryTemplate>;
+    fn load_templates(&self, json: &serde_json::Value) -> Vec<EntryTemplate>;
+    fn template_to_json(&self, template: &EntryTemplate) -> serde_json::Value;
+    fn prompt_template(&self) -> Result<EntryTemplate, AppError>;
+}
+
+pub struct TemplateHandler {}
+
+// shorthand for building the built-in templates below
+fn field(field_type: &str, field_name: Option<&str>) -> TemplateField {
+    TemplateField {
+        field_type: String::from(field_type),
+        field_name: field_name.map(String::from),
+    }
+}
+
+impl ProcessTemplates for TemplateHandler {
+    fn new() -> Self {
+        TemplateHandler {}
+    }
+
+    fn builtin_templates(&self) -> Vec<EntryTemplate> {
+        vec![
+            EntryTemplate {
+                name: String::from("Secure Note"),
+                fields: vec![field("Multi-line", Some("Note"))],
+            },
+            EntryTemplate {
+                name: String::from("Login"),
+                fields: vec![
+                    field("Username", None),
+                    field("Password", None),
+                    field("URL", Some("URL")),
+                ],
+            },
+            EntryTemplate {
+                name: String::from("Credit Card"),
+                fields: vec![
+                    field("Other", Some("Cardholder Name")),
+                    field("Number", Some("Card Number")),
+                    field("Date", Some("Expiry Date")),
+                    field("Other", Some("CVV")),
+                    field("PIN", Some("PIN")),
+                ],
+            },
+            EntryTemplate {
+                name: String::from("Bank Account"),
+                fields: vec![
+                    field("Other", Some("Bank Name")),
+                    field("Other", Some("Account Holder")),
+                    field("Other", Some("Account Number")),
+                    field("Other", Some("Routing Number")),
+                    field("Other", Some("IBAN")),
+                    field("Other", Some("SWIFT/BIC")),
+                    field("Username", None),
+                    field("Password", None),
+                ],
+            },
+            EntryTemplate {
+                name: String::from("SSH Key"),
+                fields: vec![
+                    field("Username", None),
+                    field("Other", Some("Host")),
+                    field("Multi-line", Some("Private Key")),
+                    field("Multi-line", Some("Public Key")),
+                    // password fields don't keep a name, so the passphrase is a
+                    // concealed custom field to stay labelled
+                    field("Other", Some("Passphrase")),
+                ],
+            },
+            EntryTemplate {
+                name: String::from("Wi-Fi"),
+                fields: vec![
+                    field("Other", Some("SSID")),
+                    field("Password", None),
+                    field("Other", Some("Security Type")),
+                ],
+            },
+            EntryTemplate {
+                name: String::from("Identity"),
+                fields: vec![
+                    field("Other", Some("Full Name")),
+                    field("Date", Some("Date of Birth")),
+                    field("Multi-line", Some("Address")),
+                    field("Phone", Some("Phone")),
+                    field("Email", Some("Email")),
+                    field("Other", Some("Passport Number")),
+                    field("Other", Some("Driver's License")),
+                ],
+            },
+            EntryTemplate {
+                name: String::from("Software License"),
+                fields: vec![
+                    field("Other", Some("Product")),
+                    field("Other", Some("License Key")),
+                    field("Other", Some("Licensed To")),
+                    field("Email", Some("Email")),
+                    field("Date", Some("Purchase Date")),
+                ],
+            },
+            EntryTemplate {
+                name: String::from("Database"),
+                fields: vec![
+                    field("Other", Some("Type")),
+                    field("Other", Some("Host")),
+                    field("Number", Some("Port")),
+                    field("Other", Some("Database")),
+                    field("Username", None),
+                    field("Password", None),
+                ],
+            },
+        ]
+    }
+
+    fn load_templates(&self, json: &serde_json::Value) -> Vec<EntryTemplate> {
+        // older databases won't have a templates section at all
+        let templates = match json["templates"].as_array() {
+            Some(templates) => templates,
+            None => return Vec::new(),
+        };
+
+        templates
+            .iter()
+            .map(|t| EntryTemplate {
+                name: String::from(t["name"].as_str().unwrap()),
+                fields: t["fields"]
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .map(|f| field(f["type"].as_str().unwrap(), f["name"].as_str()))
+                    .collect(),
+            })
+            .collect()
+    }
+
+    fn template_to_json(&self, template: &EntryTemplate) -> serde_json::Value {
+        // template field names are labels like "Card Number", not secrets, so they
+        // are stored in plain text
+        let fields = template
+            .fields
+            .iter()
+            .map(|f| {
+                serde_json::json!({
+                    "type": f.field_type,
+                    "name": f.field_name,
+                })
+            })
+            .collect::<Vec<serde_json::Value>>();
+
+        serde_json::json!({
+            "name": template.name,
+            "fields": fields,
+        })
+    }
+
+    fn prompt_template(&self) -> Result<EntryTemplate, AppError> {
+        let field_prompter = FieldPrompter::new();
+
+        let q_name = Question::input("name")
+            .message("What is the name of this template?")
+            .build();
+
+        let answer = prompt_one(q_name)?;
+        let name = String::from(answer.as_string().unwrap());
+
+        if name.is_empty() {
+            return Err(AppError::new("Template name cannot be empty."));
+        }
+
+        let q_num_fields = Question::int("num_fields")
+            .message("How many fields should this template have?")
+            .build();
+
+        let answer = prompt_one(q_num_fields)?;
+        let num_fields = answer.as_int().unwrap();
+
+        let mut fields: Vec<TemplateField> = Vec::new();
+
+        for i in 0..num_fields {
+            let label = format!("field {}", i + 1);
+            let field_type = field_prompter.prompt_field_type(&label)?;
+
+            // security questions have their own question text, everything else can
+            // be given a label that is shown when the template is filled in
+            let field_name = if field_type == "Security Question" {
+                None
+            } else {
+                let q_field_name = Question::input("field_name")
+                    .message(format!("Label for {} (leave blank for none):", label))
+                    .build();
+
+                let answer = prompt_one(q_field_name)?;
+                let field_name = answer.as_string().unwrap();
+
+                if field_name.is_empty() {
+                    None
+                } else {
+                    Some(String::from(field_name))
+                }
+            };
+
+            fields.push(TemplateField {
+                field_type,
+                field_name,
+            });
+        }
+
+        Ok(EntryTemplate { name, fields })
+    }
+}
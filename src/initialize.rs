@@ -1,7 +1,6 @@
 // file stuff
-use serde_json;
 use std::io::prelude::*;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::{fmt::Debug, fs::File};
 
 // password stuff
@@ -10,10 +9,16 @@ use argon2::{
     Argon2,
 };
 use rand::Rng;
-use requestty::Question;
+use secrecy::ExposeSecret;
+
+// other stuff
+use chrono::prelude::*;
 
 // my stuff
 use crate::errors::AppError;
+use crate::handle_files::{commit_staged, write_atomic};
+use crate::handle_lockout::LockoutPolicy;
+use crate::handle_master::{CheckMasterPassword, MasterPasswordHandler, MasterPolicy};
 
 // ------------------ //
 
@@ -21,9 +26,27 @@ pub trait Initialize {
     fn new(path: String) -> Self;
     fn start_up(&mut self) -> Result<bool, AppError>;
     fn create_settings(&self) -> Result<(), AppError>;
+    fn commit_credentials(
+        &mut self,
+        password_hash: String,
+        hash_salt: String,
+        derived_key_salt: String,
+        staged: &[PathBuf],
+        staged_settings: serde_json::Value,
+    );
+    fn set_master_policy(&mut self, policy: MasterPolicy);
+    fn set_lockout_policy(&mut self, policy: LockoutPolicy);
+    fn set_skip_password_policy(&mut self, skip: bool);
+    fn record_failed_unlock(&mut self);
+    fn withdraw_failed_unlock(&mut self);
+    fn take_failed_unlocks(&mut self) -> Vec<String>;
     fn load_settings(&mut self);
     fn get_password_hash(&self) -> String;
     fn get_key_salt(&self) -> String;
+    fn get_master_policy(&self) -> MasterPolicy;
+    fn get_lockout_policy(&self) -> LockoutPolicy;
+    fn get_failed_unlocks(&self) -> Vec<String>;
+    fn get_skip_password_policy(&self) -> bool;
 }
 
 pub struct SettingsInitializer {
@@ -31,6 +54,15 @@ pub struct SettingsInitializer {
     password_hash: String,
     hash_salt: String,
     derived_key_salt: String,
+    master_policy: MasterPolicy,
+    lockout_policy: LockoutPolicy,
+    skip_password_policy: bool,
+    // wrong passwords can't be written to the encrypted audit log without the key,
+    // so they wait here until the next unlock, which also makes them the count that
+    // unlocking is throttled by
+    failed_unlocks: Vec<String>,
+    // files staged for a new master password that still have to be moved into place
+    pending_renames: Vec<PathBuf>,
 }
 
 impl Initialize for SettingsInitializer {
@@ -40,6 +72,11 @@ impl Initialize for SettingsInitializer {
             password_hash: String::from(""),
             hash_salt: String::from(""),
             derived_key_salt: String::from(""),
+            master_policy: MasterPolicy::default_policy(),
+            lockout_policy: LockoutPolicy::default_policy(),
+            skip_password_policy: false,
+            failed_unlocks: Vec::new(),
+            pending_renames: Vec::new(),
         }
     }
 
@@ -48,8 +85,9 @@ impl Initialize for SettingsInitializer {
         let path = Path::new(&self.path);
         let mut made_new_file = false;
 
+        // status lines go to stderr, so they never mix with what a command prints
         if !path.exists() {
-            println!("Settings file does not exist. Creating new settings file...");
+            eprintln!("Settings file does not exist. Creating new settings file...");
             loop {
                 match self.create_settings() {
                     Ok(_) => break,
@@ -58,7 +96,7 @@ impl Initialize for SettingsInitializer {
             }
             made_new_file = true;
         } else {
-            println!("Settings file exists. Loading settings...");
+            eprintln!("Settings file exists. Loading settings...");
         }
 
         self.load_settings();
@@ -67,76 +105,86 @@ impl Initialize for SettingsInitializer {
     }
 
     fn create_settings(&self) -> Result<(), AppError> {
-        // create the new json file
-        let mut file = match File::create(&self.path) {
-            Err(why) => panic!("Couldn't create settings file: {}", why),
-            Ok(file) => file,
-        };
+        // the policy is checked before anything is written
+        let master_handler =
+            MasterPasswordHandler::new(self.master_policy.clone(), self.skip_password_policy);
+        let password = master_handler.prompt_new_password(&[])?;
 
-        // set up argon2
-        let argon2 = Argon2::default();
-
-        // generate the salt
-        let rng = rand::thread_rng();
-        let salt_string = rng.clone()
-            .sample_iter(rand::distributions::Alphanumeric)
-            .take(32)
-            .map(char::from)
-            .collect::<String>();
-        let salt = SaltString::from_b64(salt_string.as_str()).unwrap();
-
-        // generate the salt used to derive the key
-        let derived_key_salt_string = rng
-            .sample_iter(rand::distributions::Alphanumeric)
-            .take(32)
-            .map(char::from)
-            .collect::<String>();
-
-        // get the password
-        let q_pass = Question::password("password")
-            .message("Enter a new password: ")
-            .mask('*')
-            .build();
-
-        let q_pass_confirm = Question::password("password_confirm")
-            .message("Confirm your password: ")
-            .mask('*')
-            .build();
-
-        let answers = requestty::prompt(vec![q_pass, q_pass_confirm]).unwrap();
-
-        let password = answers.get("password").unwrap().as_string().unwrap() as &str;
-
-        let password_confirm = answers
-            .get("password_confirm")
-            .unwrap()
-            .as_string()
-            .unwrap() as &str;
-
-        // check if the passwords match
-        if password != password_confirm {
-            return Err(AppError::new("Passwords do not match."));
-        } else {
-            // hash the password
-            let password_hash = argon2.hash_password(password.as_bytes(), &salt).unwrap();
-
-            // serialize the password hash and salt to json
-            let json = serde_json::json!({
-                "password_hash": password_hash.to_string(),
-                "hash_salt": salt_string,
-                "derived_key_salt": derived_key_salt_string,
-            });
-
-            // write the json to the file
-            match file.write_all(json.to_string().as_bytes()) {
-                Err(why) => panic!("Couldn't write to settings file: {}", why),
-                Ok(_) => println!("Successfully wrote to settings file."),
-            }
-        }
+        let (password_hash, hash_salt, derived_key_salt) = hash_password(password.expose_secret());
+
+        // serialize the password hash and salt to json
+        self.write_settings(serde_json::json!({
+            "password_hash": password_hash,
+            "hash_salt": hash_salt,
+            "derived_key_salt": derived_key_salt,
+            "master_policy": self.master_policy.to_json(),
+            "lockout_policy": self.lockout_policy.to_json(),
+        }));
 
         Ok(())
     }
 
+    // the new password is saved along with the files that were re-encrypted for it
+    // and any settings that go with them in one write, once that is on disk the
+    // change goes ahead even if the program stops before the files are moved into
+    // place, the next start finishes it
+    fn commit_credentials(
+        &mut self,
+        password_hash: String,
+        hash_salt: String,
+        derived_key_salt: String,
+        staged: &[PathBuf],
+        staged_settings: serde_json::Value,
+    ) {
+        self.password_hash = password_hash;
+        self.hash_salt = hash_salt;
+        self.derived_key_salt = derived_key_salt;
+        self.pending_renames = staged.to_vec();
+
+        let mut json = self.settings_json();
+        if let serde_json::Value::Object(staged_settings) = staged_settings {
+            json.as_object_mut().unwrap().extend(staged_settings);
+        }
+        self.write_settings(json);
+
+        self.finish_renames();
+    }
+
+    fn set_master_policy(&mut self, policy: MasterPolicy) {
+        self.master_policy = policy;
+        self.write_settings(self.settings_json());
+    }
+
+    fn set_lockout_policy(&mut self, policy: LockoutPolicy) {
+        self.lockout_policy = policy;
+        self.write_settings(self.settings_json());
+    }
+
+    fn set_skip_password_policy(&mut self, skip: bool) {
+        self.skip_password_policy = skip;
+    }
+
+    fn record_failed_unlock(&mut self) {
+        self.failed_unlocks.push(Local::now().to_rfc3339());
+        self.write_settings(self.settings_json());
+    }
+
+    // the attempt is recorded before the password is checked, so it is taken back
+    // once the password turns out to be right
+    fn withdraw_failed_unlock(&mut self) {
+        self.failed_unlocks.pop();
+        self.write_settings(self.settings_json());
+    }
+
+    fn take_failed_unlocks(&mut self) -> Vec<String> {
+        let failed_unlocks = std::mem::take(&mut self.failed_unlocks);
+        if !failed_unlocks.is_empty() {
+            self.write_settings(self.settings_json());
+        }
+
+        failed_unlocks
+    }
+
     fn load_settings(&mut self) {
         // open the file
         let mut file = match File::open(&self.path) {
@@ -148,7 +196,7 @@ impl Initialize for SettingsInitializer {
         let mut contents = String::new();
         match file.read_to_string(&mut contents) {
             Err(why) => panic!("Couldn't read settings file: {}", why),
-            Ok(_) => println!("Successfully read settings file."),
+            Ok(_) => eprintln!("Successfully read settings file."),
         }
 
         // parse the json
@@ -158,6 +206,33 @@ impl Initialize for SettingsInitializer {
         self.password_hash = v["password_hash"].as_str().unwrap_or("").to_string();
         self.hash_salt = v["hash_salt"].as_str().unwrap_or("").to_string();
         self.derived_key_salt = v["derived_key_salt"].as_str().unwrap_or("").to_string();
+        self.master_policy = MasterPolicy::from_json(&v["master_policy"]);
+        self.lockout_policy = LockoutPolicy::from_json(&v["lockout_policy"]);
+        self.failed_unlocks = v["failed_unlocks"]
+            .as_array()
+            .map(|times| {
+                times
+                    .iter()
+                    .filter_map(|time| time.as_str().map(String::from))
+                    .collect()
+            })
+            .unwrap_or_default();
+        self.pending_renames = v["pending_renames"]
+            .as_array()
+            .map(|paths| {
+                paths
+                    .iter()
+                    .filter_map(|path| path.as_str().map(PathBuf::from))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        // a master password change was committed but cut short before the files
+        // for it were all in place
+        if !self.pending_renames.is_empty() {
+            eprintln!("Finishing an interrupted master password change...");
+            self.finish_renames();
+        }
     }
 
     fn get_password_hash(&self) -> String {
@@ -167,6 +242,110 @@ impl Initialize for SettingsInitializer {
     fn get_key_salt(&self) -> String {
         self.derived_key_salt.clone()
     }
+
+    fn get_master_policy(&self) -> MasterPolicy {
+        self.master_policy.clone()
+    }
+
+    fn get_lockout_policy(&self) -> LockoutPolicy {
+        self.lockout_policy.clone()
+    }
+
+    fn get_failed_unlocks(&self) -> Vec<String> {
+        self.failed_unlocks.clone()
+    }
+
+    fn get_skip_password_policy(&self) -> bool {
+        self.skip_password_policy
+    }
+}
+
+impl SettingsInitializer {
+    fn settings_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "password_hash": self.password_hash,
+            "hash_salt": self.hash_salt,
+            "derived_key_salt": self.derived_key_salt,
+            "master_policy": self.master_policy.to_json(),
+            "lockout_policy": self.lockout_policy.to_json(),
+            "failed_unlocks": self.failed_unlocks,
+            "pending_renames": self.pending_renames,
+        })
+    }
+
+    fn finish_renames(&mut self) {
+        if let Err(why) = commit_staged(&self.pending_renames) {
+            panic!("Couldn't move re-encrypted files into place: {}", why);
+        }
+
+        self.pending_renames.clear();
+        self.write_settings(self.settings_json());
+    }
+
+    fn write_settings(&self, json: serde_json::Value) {
+        update_settings_file(Path::new(&self.path), json);
+    }
+}
+
+// the settings file as it is on disk, other handlers keep a few things of their own
+// in it
+pub fn read_settings_file(path: &Path) -> serde_json::Value {
+    match std::fs::read_to_string(path) {
+        Ok(contents) => serde_json::from_str(&contents).unwrap_or(serde_json::Value::Null),
+        Err(_) => serde_json::Value::Null,
+    }
+}
+
+// writes the given parts of the settings file and leaves the rest as it is, the new
+// file only replaces the old one once it is on disk, so a crash never leaves it
+// empty or half written
+pub fn update_settings_file(path: &Path, values: serde_json::Value) {
+    let mut json = match read_settings_file(path) {
+        serde_json::Value::Object(json) => json,
+        _ => serde_json::Map::new(),
+    };
+    if let serde_json::Value::Object(values) = values {
+        json.extend(values);
+    }
+
+    let json = serde_json::Value::Object(json);
+    if let Err(why) = write_atomic(path, |writer| writer.write_all(json.to_string().as_bytes())) {
+        panic!("Couldn't write to settings file: {}", why);
+    }
+}
+
+// hashes the password with argon2, returning the hash, its salt and the salt used
+// to derive the key, the salts are new every time so a new password's key has
+// nothing in common with the old one
+pub fn hash_password(password: &str) -> (String, String, String) {
+    // set up argon2
+    let argon2 = Argon2::default();
+
+    // generate the salt
+    let rng = rand::thread_rng();
+    let salt_string = rng
+        .clone()
+        .sample_iter(rand::distributions::Alphanumeric)
+        .take(32)
+        .map(char::from)
+        .collect::<String>();
+    let salt = SaltString::from_b64(salt_string.as_str()).unwrap();
+
+    // generate the salt used to derive the key
+    let derived_key_salt_string = rng
+        .sample_iter(rand::distributions::Alphanumeric)
+        .take(32)
+        .map(char::from)
+        .collect::<String>();
+
+    // hash the password
+    let password_hash = argon2.hash_password(password.as_bytes(), &salt).unwrap();
+
+    (
+        password_hash.to_string(),
+        salt_string,
+        derived_key_salt_string,
+    )
 }
 
 impl Debug for SettingsInitializer {
@@ -1,24 +1,329 @@
 mod errors;
+mod handle_args;
+mod handle_attachments;
+mod handle_audit;
+mod handle_autolock;
+mod handle_breach;
+mod handle_domains;
+mod handle_editor;
+mod handle_expiry;
+mod handle_fields;
+mod handle_files;
+mod handle_fuzzy;
+mod handle_generator;
+mod handle_health;
+mod handle_lockout;
 mod handle_logo;
+mod handle_master;
 mod handle_operations;
 mod handle_pass;
+mod handle_passphrase;
+mod handle_policies;
+mod handle_search;
+mod handle_strength;
+mod handle_templates;
 mod initialize;
 
+use handle_args::{ArgsHandler, CLICommand, ParseArgs};
+use handle_autolock::{prompt_one, IdleWatcher, VaultLocked, WatchIdle};
+use handle_lockout::{ThrottleUnlock, UnlockThrottle};
 use handle_logo::{HandleLogo, LogoHandler};
+use handle_master::{CheckMasterPassword, MasterPasswordHandler};
 use handle_operations::{DBHandler, DBOperation, ProcessDB};
 use handle_pass::{PasswordHandler, ProcessPassword};
-use initialize::{Initialize, SettingsInitializer};
+use handle_strength::display_time;
+use initialize::{hash_password, Initialize, SettingsInitializer};
 
-use secrecy::ExposeSecret;
+use colored::Colorize;
+use errors::AppError;
+use requestty::Question;
+use secrecy::{ExposeSecret, Secret};
+use std::fs;
 use std::process::Command;
 
+// wrong passwords allowed each time the program is run
+const ATTEMPTS_PER_RUN: usize = 3;
+
+// deletes everything the vault is made of, the next run starts from scratch
+fn wipe_vault(current_dir: &str) {
+    for file in ["db.json", "audit.log", "settings.json"] {
+        let _ = fs::remove_file(format!("{}/{}", current_dir, file));
+    }
+    let _ = fs::remove_dir_all(format!("{}/attachments", current_dir));
+}
+
+// asks for the master password until it is right, waiting longer after each wrong
+// one, the wrong ones are kept in the settings so running the program again
+// carries on where it left off
+fn unlock(
+    settings: &mut SettingsInitializer,
+    password_handler: &mut PasswordHandler,
+    current_dir: &str,
+) -> Result<(), AppError> {
+    let throttle = UnlockThrottle::new(settings.get_lockout_policy());
+    let mut attempts = 0;
+
+    loop {
+        let failed_unlocks = settings.get_failed_unlocks();
+        if let Some(remaining) = throttle.locked_for(&failed_unlocks) {
+            return Err(AppError::new(&format!(
+                "Too many wrong passwords, try again in {}.",
+                display_time(remaining.num_seconds() as f64)
+            )));
+        }
+
+        // the wait comes before the password is even asked for, let alone hashed
+        let wait = throttle.wait_time(&failed_unlocks);
+        if wait > chrono::Duration::zero() {
+            let waiting = format!(
+                "{} wrong passwords, waiting {} before the next try...",
+                failed_unlocks.len(),
+                display_time(wait.num_milliseconds() as f64 / 1000.0)
+            )
+            .cyan();
+            eprintln!("{}", waiting);
+            std::thread::sleep(wait.to_std().unwrap());
+        }
+
+        // the attempt counts as wrong until the hash says otherwise, so killing the
+        // program while the password is being checked doesn't get a free try
+        let password = password_handler.prompt_password()?;
+        settings.record_failed_unlock();
+
+        match password_handler.check_password(
+            &password,
+            settings.get_password_hash(),
+            settings.get_key_salt(),
+        ) {
+            Ok(_) => {
+                settings.withdraw_failed_unlock();
+                return Ok(());
+            }
+            Err(e) => {
+                attempts += 1;
+
+                if throttle.should_wipe(&settings.get_failed_unlocks()) {
+                    wipe_vault(current_dir);
+                    return Err(AppError::new(
+                        "Too many wrong passwords, the vault has been wiped.",
+                    ));
+                }
+                if attempts >= ATTEMPTS_PER_RUN {
+                    return Err(e);
+                }
+                eprintln!("{}", e);
+            }
+        }
+    }
+}
+
+// changing the master password needs the settings, the key and the database, so it
+// is done here rather than by any one of them
+fn manage_master_password(
+    settings: &mut SettingsInitializer,
+    password_handler: &mut PasswordHandler,
+    db_handler: &mut DBHandler,
+) -> Result<(), AppError> {
+    let master_handler = MasterPasswordHandler::new(
+        settings.get_master_policy(),
+        settings.get_skip_password_policy(),
+    );
+    let throttle = UnlockThrottle::new(settings.get_lockout_policy());
+
+    let policy = format!("Policy: {}", master_handler.describe_policy()).cyan();
+    println!("{}", policy);
+    let lockout = format!("Wrong passwords: {}", throttle.describe_policy()).cyan();
+    println!("{}", lockout);
+
+    let q_action = Question::select("action")
+        .message("What would you like to do?")
+        .choices(vec![
+            "Change master password",
+            "Change policy",
+            "Change wrong password lockout",
+            "Back",
+        ])
+        .build();
+
+    let answer = prompt_one(q_action)?;
+    match answer.as_list_item().unwrap().index {
+        0 => {
+            // the current password is asked for again before anything changes
+            password_handler
+                .verify_password(settings.get_password_hash(), settings.get_key_salt())?;
+
+            let usernames = db_handler.usernames(password_handler.decrypt_key().expose_secret());
+            let password = master_handler.prompt_new_password(&usernames)?;
+
+            // the vault is re-encrypted into files beside the current ones, which
+            // only take over when the new password is committed to the settings, a
+            // failure or crash before that leaves the old password working
+            let (password_hash, hash_salt, derived_key_salt) =
+                hash_password(password.expose_secret());
+            let mut new_password_handler = PasswordHandler::new();
+            new_password_handler.set_password(&password, derived_key_salt.clone());
+
+            let mut old_key = password_handler.decrypt_key();
+            let mut new_key = new_password_handler.decrypt_key();
+            let (staged, staged_settings) =
+                db_handler.stage_key_change(old_key.expose_secret(), new_key.expose_secret())?;
+
+            settings.commit_credentials(
+                password_hash,
+                hash_salt,
+                derived_key_salt,
+                &staged,
+                staged_settings,
+            );
+            db_handler.finish_key_change(new_key.expose_secret());
+
+            // the keys trade places rather than being copied, the idle watcher holds on
+            // to the same one, and the old one is zeroized along with the new handler
+            std::mem::swap(&mut *old_key, &mut *new_key);
+
+            let changed = "Master password changed.".cyan();
+            println!("{}", changed);
+        }
+        1 => {
+            settings.set_master_policy(master_handler.prompt_policy()?);
+
+            let changed = "Master password policy saved, it applies from the next change.".cyan();
+            println!("{}", changed);
+        }
+        2 => {
+            settings.set_lockout_policy(throttle.prompt_policy()?);
+
+            let changed = "Lockout saved.".cyan();
+            println!("{}", changed);
+        }
+        _ => (),
+    }
+
+    Ok(())
+}
+
+// runs whatever is picked from the menu, returning false once it is time to exit,
+// an operation the vault locks under stops and hands back VaultLocked
+fn run_operation(
+    settings: &mut SettingsInitializer,
+    password_handler: &mut PasswordHandler,
+    db_handler: &mut DBHandler,
+    idle_watcher: &IdleWatcher,
+) -> Result<bool, VaultLocked> {
+    let operation = match db_handler.inquire_operation() {
+        Ok(operation) => operation,
+        Err(e) if e.is_locked() => return Err(VaultLocked),
+        Err(e) => {
+            println!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
+    // the key is only lent to the operation, never copied
+    let result = match operation {
+        DBOperation::List => db_handler.list_entries(None, true),
+        DBOperation::View => db_handler.view_entry(password_handler.decrypt_key().expose_secret()),
+        DBOperation::Search => {
+            db_handler.search_entries(password_handler.decrypt_key().expose_secret())
+        }
+        DBOperation::FindUrl => {
+            db_handler.find_by_url(password_handler.decrypt_key().expose_secret(), None)
+        }
+        DBOperation::Create => {
+            db_handler.create_entry(password_handler.decrypt_key().expose_secret())
+        }
+        DBOperation::Duplicate => {
+            db_handler.duplicate_entry(password_handler.decrypt_key().expose_secret())
+        }
+        DBOperation::Update => {
+            db_handler.update_entry(password_handler.decrypt_key().expose_secret())
+        }
+        DBOperation::Delete => {
+            db_handler.delete_entry(password_handler.decrypt_key().expose_secret())
+        }
+        DBOperation::Attachments => {
+            db_handler.manage_attachments(password_handler.decrypt_key().expose_secret())
+        }
+        DBOperation::Trash => {
+            db_handler.manage_trash(password_handler.decrypt_key().expose_secret())
+        }
+        DBOperation::Templates => db_handler.manage_templates(),
+        DBOperation::Policies => db_handler.manage_policies(),
+        DBOperation::Health => {
+            db_handler.review_health(password_handler.decrypt_key().expose_secret())
+        }
+        DBOperation::Breaches => {
+            db_handler.check_breaches(password_handler.decrypt_key().expose_secret(), None)
+        }
+        DBOperation::AuditLog => {
+            db_handler.review_audit_log(password_handler.decrypt_key().expose_secret())
+        }
+        DBOperation::Preferences => {
+            let result = db_handler.manage_preferences();
+            idle_watcher.set_timeout(db_handler.get_preference("lock_minutes"));
+            result
+        }
+        DBOperation::MasterPassword => {
+            manage_master_password(settings, password_handler, db_handler)
+        }
+        DBOperation::Exit => {
+            db_handler.flush_db();
+            println!("Exit");
+            let _ = Command::new("clear").status();
+            return Ok(false);
+        }
+    };
+
+    match result {
+        Err(e) if e.is_locked() => return Err(VaultLocked),
+        Err(e) => println!("{}", e),
+        Ok(_) => (),
+    }
+
+    Ok(true)
+}
+
 fn main() {
+    // work out what we were asked to do before anything else
+    let args_handler = ArgsHandler::new(std::env::args().collect());
+    let command = match args_handler.parse() {
+        Ok(CLICommand::Help) => {
+            args_handler.print_usage();
+            return;
+        }
+        Ok(command) => command,
+        Err(e) => {
+            println!("{}", e);
+            args_handler.print_usage();
+            std::process::exit(1);
+        }
+    };
+
     // get current directory
     let current_dir = std::env::current_dir().unwrap();
     let current_dir = current_dir.to_str().unwrap();
 
-    let mut settings =
-        SettingsInitializer::new(String::from(format!("{}/settings.json", current_dir)));
+    // expiry dates are kept in the clear, so they can be checked from cron without
+    // the master password, anything due exits with a status of its own so a script
+    // can tell it apart from nothing due or an error
+    if let CLICommand::Expiring { days } = command {
+        let mut db_handler = DBHandler::new(format!("{}/db.json", current_dir));
+        match db_handler
+            .start_up()
+            .and_then(|_| db_handler.list_expiring(days))
+        {
+            Ok(0) => (),
+            Ok(_) => std::process::exit(2),
+            Err(e) => {
+                println!("{}", e);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    let mut settings = SettingsInitializer::new(format!("{}/settings.json", current_dir));
+    settings.set_skip_password_policy(args_handler.skip_password_policy());
 
     let start_up_res = settings.start_up();
     match start_up_res {
@@ -30,11 +335,10 @@ fn main() {
     }
 
     let mut password_handler = PasswordHandler::new();
-    let password_res =
-        password_handler.verify_password(settings.get_password_hash(), settings.get_key_salt());
+    let password_res = unlock(&mut settings, &mut password_handler, current_dir);
     match password_res {
         Ok(_) => {
-            println!("Password verified!");
+            eprintln!("Password verified!");
         }
         Err(e) => {
             println!("{}", e);
@@ -43,7 +347,7 @@ fn main() {
     }
 
     // create the db handler
-    let mut db_handler = DBHandler::new(String::from(format!("{}/db.json", current_dir)));
+    let mut db_handler = DBHandler::new(format!("{}/db.json", current_dir));
     let db_res = db_handler.start_up();
     match db_res {
         Ok(_) => (),
@@ -53,38 +357,130 @@ fn main() {
         }
     }
 
-    // print the title
-    let logo_handler = LogoHandler {};
-    logo_handler.print_logo();
+    db_handler.record_unlock(
+        password_handler.decrypt_key().expose_secret(),
+        &settings.take_failed_unlocks(),
+    );
 
-    // main loop
-    loop {
-        let operation = db_handler.inquire_operation();
-        match operation {
-            Ok(DBOperation::List) => {
-                let _ = db_handler.list_entries();
+    // one-off commands print their output and exit without the interactive menu
+    match command {
+        CLICommand::List { sort } => {
+            if let Err(e) = db_handler.list_entries(sort.as_deref(), false) {
+                println!("{}", e);
+                std::process::exit(1);
             }
-            Ok(DBOperation::View) => {
-                let _ = db_handler.view_entry(password_handler.get_decrypt_key().expose_secret());
+            return;
+        }
+        CLICommand::View { name, reveal } => {
+            let key = password_handler.decrypt_key();
+            if let Err(e) = db_handler.view_entry_named(key.expose_secret(), &name, reveal) {
+                println!("{}", e);
+                std::process::exit(1);
             }
-            Ok(DBOperation::Create) => {
-                let _ = db_handler.create_entry(password_handler.get_decrypt_key().expose_secret());
+            db_handler.flush_db();
+            return;
+        }
+        CLICommand::Match { query } => {
+            if let Err(e) = db_handler.list_matches(&query) {
+                println!("{}", e);
+                std::process::exit(1);
             }
-            Ok(DBOperation::Update) => {
-                let _ = db_handler.update_entry(password_handler.get_decrypt_key().expose_secret());
+            return;
+        }
+        CLICommand::FindUrl { url } => {
+            let key = password_handler.decrypt_key();
+            if let Err(e) = db_handler.find_by_url(key.expose_secret(), Some(&url)) {
+                println!("{}", e);
+                std::process::exit(1);
             }
-            Ok(DBOperation::Delete) => {
-                let _ = db_handler.delete_entry();
+            return;
+        }
+        CLICommand::Health { json } => {
+            let key = password_handler.decrypt_key();
+            if let Err(e) = db_handler.health_report(key.expose_secret(), json) {
+                println!("{}", e);
+                std::process::exit(1);
             }
-            Ok(DBOperation::Exit) => {
-                println!("Exit");
-                let _ = Command::new("clear").status();
-                break;
+            return;
+        }
+        CLICommand::BreachCheck { path, ntlm } => {
+            let key = password_handler.decrypt_key();
+            if let Err(e) = db_handler.check_breaches(key.expose_secret(), Some((&path, ntlm))) {
+                println!("{}", e);
+                std::process::exit(1);
             }
-            Err(e) => {
+            return;
+        }
+        CLICommand::Audit { filter } => {
+            let key = password_handler.decrypt_key();
+            if let Err(e) = db_handler.print_audit_log(
+                key.expose_secret(),
+                filter.entry.as_deref(),
+                filter.operation.as_deref(),
+            ) {
                 println!("{}", e);
                 std::process::exit(1);
             }
+            return;
+        }
+        CLICommand::Interactive | CLICommand::Help | CLICommand::Expiring { .. } => (),
+    }
+
+    // print the title
+    let logo_handler = LogoHandler {};
+    logo_handler.print_logo();
+
+    // anything overdue or expiring soon is shown under the title
+    let _ = db_handler.list_expiring(None);
+
+    // the key is wiped if the vault is left alone for too long
+    let idle_watcher = IdleWatcher::new(password_handler.shared_key());
+    idle_watcher.set_timeout(db_handler.get_preference("lock_minutes"));
+    idle_watcher.start();
+
+    // main loop
+    loop {
+        match run_operation(
+            &mut settings,
+            &mut password_handler,
+            &mut db_handler,
+            &idle_watcher,
+        ) {
+            Ok(true) => (),
+            Ok(false) => break,
+            Err(VaultLocked) => {
+                // operations only stop at a prompt, so the vault in memory is left as
+                // it was, only the key has to go, the watcher can't wipe it while an
+                // operation has it
+                password_handler.set_decrypt_key(Secret::new(String::new()));
+
+                let _ = Command::new("clear").status();
+                let locked = format!(
+                    "Locked after {} without activity.",
+                    display_time((db_handler.get_preference("lock_minutes") * 60) as f64)
+                )
+                .cyan();
+                println!("{}", locked);
+
+                // the password prompt can time out too, with nothing left to lock it is
+                // simply asked again
+                loop {
+                    idle_watcher.restart();
+                    match unlock(&mut settings, &mut password_handler, current_dir) {
+                        Ok(_) => break,
+                        Err(e) if e.is_locked() => (),
+                        Err(e) => {
+                            println!("{}", e);
+                            std::process::exit(1);
+                        }
+                    }
+                }
+
+                db_handler.record_unlock(
+                    password_handler.decrypt_key().expose_secret(),
+                    &settings.take_failed_unlocks(),
+                );
+            }
         }
     }
 }